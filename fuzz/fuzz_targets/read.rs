@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use remarkable_lines::RemarkableFile;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = RemarkableFile::read(data);
+});
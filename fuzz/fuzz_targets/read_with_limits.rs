@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use remarkable_lines::{ParseLimits, RemarkableFile};
+
+fuzz_target!(|data: &[u8]| {
+    let limits = ParseLimits {
+        max_block_size: 4096,
+        max_points_per_line: 256,
+        max_string_length: 256,
+        max_nesting_depth: 4,
+        max_archive_entry_size: 4096,
+        max_archive_size: 16384,
+    };
+    let _ = RemarkableFile::read_with_limits(data, limits);
+});
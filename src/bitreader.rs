@@ -1,6 +1,6 @@
 use std::io::{Cursor, Read};
 
-use crate::{ParseError, ParseErrorKind};
+use crate::{ParseError, ParseErrorKind, ParseLimits};
 
 pub trait Readable: Read + AsRef<[u8]> {}
 impl<T: Read + AsRef<[u8]>> Readable for T {}
@@ -8,13 +8,41 @@ impl<T: Read + AsRef<[u8]>> Readable for T {}
 /// A little endian binary reader
 pub struct Bitreader<N: Readable> {
     cursor: Cursor<N>,
+    limits: ParseLimits,
 }
 
 impl<N: Readable> Bitreader<N> {
     pub fn new(bits: N) -> Bitreader<N> {
+        Self::with_limits(bits, ParseLimits::default())
+    }
+
+    pub fn with_limits(bits: N, limits: ParseLimits) -> Bitreader<N> {
         Bitreader {
             cursor: Cursor::new(bits),
+            limits,
+        }
+    }
+
+    pub fn limits(&self) -> &ParseLimits {
+        &self.limits
+    }
+
+    /// Amount of bytes left to read
+    pub fn remaining(&self) -> u64 {
+        (self.cursor.get_ref().as_ref().len() as u64).saturating_sub(self.position())
+    }
+
+    /// Validate that `count` items of at least `min_item_size` bytes can still be read
+    ///
+    /// Used before trusting counts read from the input
+    pub fn check_count(&self, count: u64, min_item_size: u64) -> Result<(), ParseError> {
+        if count.saturating_mul(min_item_size) > self.remaining() {
+            return Err(ParseError::invalid(format!(
+                "Found {count} items while only {} bytes remaining",
+                self.remaining()
+            )));
         }
+        Ok(())
     }
 
     /// End Of File, returns true if not more bytes can be read
@@ -53,14 +81,27 @@ impl<N: Readable> Bitreader<N> {
     }
 
     pub fn read_bytes(&mut self, amount: usize) -> Result<Vec<u8>, ParseError> {
+        // don't allocate for data that can't be there
+        if amount as u64 > self.remaining() {
+            return Err(ParseError::new(
+                format!("Failed to read {amount} bytes, reached end of input"),
+                ParseErrorKind::Io,
+            ));
+        }
         let mut buffer = vec![0; amount];
         self.read_exact(&mut buffer)?;
         return Ok(buffer);
     }
 
     pub fn read_string(&mut self, length: usize) -> Result<String, ParseError> {
-        return Ok(String::from_utf8(self.read_bytes(length)?)
-            .map_err(|_| ParseError::invalid("String contains invalid utf-8"))?);
+        if length > self.limits.max_string_length as usize {
+            return Err(ParseError::limit_exceeded(format!(
+                "String length {length} exceeds limit of {}",
+                self.limits.max_string_length
+            )));
+        }
+        return String::from_utf8(self.read_bytes(length)?)
+            .map_err(|_| ParseError::invalid("String contains invalid utf-8"));
     }
 
     // https://en.wikipedia.org/wiki/Variable-length_quantity
@@ -70,7 +111,12 @@ impl<N: Readable> Bitreader<N> {
         let mut i;
         loop {
             i = self.read_u8()?;
-            result |= ((i & 0x7F) << shift) as u32;
+            let value = (i & 0x7F) as u32;
+            // a u32 fits in 5 bytes, anything more or any bits past 32 are invalid
+            if shift > 28 || (shift == 28 && value > 0x0F) {
+                return Err(ParseError::invalid("Variable length integer overflows u32"));
+            }
+            result |= value << shift;
             shift += 7;
             if i & 0x80 == 0 {
                 break;
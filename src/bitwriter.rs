@@ -0,0 +1,85 @@
+use crate::{uuid, ParseError};
+
+/// A little endian binary writer, the counterpart of [crate::Bitreader]
+#[derive(Debug, Default)]
+pub struct Bitwriter {
+    data: Vec<u8>,
+}
+
+impl Bitwriter {
+    pub fn new() -> Bitwriter {
+        Self::default()
+    }
+
+    /// Amount of bytes written so far
+    pub fn position(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Overwrite a u32 written earlier at `position`, used to fill in sizes
+    pub fn patch_u32(&mut self, position: usize, value: u32) {
+        self.data[position..position + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_string(&mut self, string: &str) {
+        self.write_bytes(string.as_bytes());
+    }
+
+    // https://en.wikipedia.org/wiki/Variable-length_quantity
+    pub fn write_varuint(&mut self, mut value: u32) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.write_u8(byte);
+                return;
+            }
+            self.write_u8(byte | 0x80);
+        }
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    /// Write uuid in the format read by [crate::Bitreader::read_uuid]
+    pub fn write_uuid(&mut self, uuid: &str) -> Result<(), ParseError> {
+        let mut bytes = uuid::to_bytes(uuid)
+            .ok_or_else(|| ParseError::invalid(format!("Invalid uuid '{uuid}'")))?;
+        // first 3 uuid sections are stored in little endianness
+        bytes[..4].reverse();
+        bytes[4..6].reverse();
+        bytes[6..8].reverse();
+        self.write_varuint(bytes.len() as u32);
+        self.write_bytes(&bytes);
+        Ok(())
+    }
+}
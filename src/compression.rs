@@ -0,0 +1,461 @@
+//! Deflate (RFC 1951), zlib framing (RFC 1950) and the checksums used by zip
+//!
+//! Decompression follows the structure of zlib's reference decoder `puff.c`. Compression only
+//! uses the fixed huffman codes, which is plenty for the mostly empty images we write.
+
+use crate::ParseError;
+
+/// Maximum bits in a huffman code
+const MAX_BITS: usize = 15;
+
+struct BitInput<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitInput<'a> {
+    fn bits(&mut self, need: u32) -> Result<u32, ParseError> {
+        let mut value = self.bit_buffer as u64;
+        while self.bit_count < need {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or_else(|| ParseError::invalid("Deflate data ended unexpectedly"))?;
+            self.position += 1;
+            value |= (byte as u64) << self.bit_count;
+            self.bit_count += 8;
+        }
+        self.bit_buffer = (value >> need) as u32;
+        self.bit_count -= need;
+        Ok((value & ((1u64 << need) - 1)) as u32)
+    }
+}
+
+struct Huffman {
+    /// Number of symbols for each code length
+    counts: [u16; MAX_BITS + 1],
+    /// Symbols ordered by their code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u16]) -> Result<Huffman, ParseError> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        // check for an over-subscribed set of lengths, incomplete sets are allowed
+        let mut left: i32 = 1;
+        for count in counts.iter().skip(1) {
+            left <<= 1;
+            left -= *count as i32;
+            if left < 0 {
+                return Err(ParseError::invalid("Invalid huffman code lengths"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, input: &mut BitInput) -> Result<u16, ParseError> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..=MAX_BITS {
+            code |= input.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return self
+                    .symbols
+                    .get((index + (code - first)) as usize)
+                    .copied()
+                    .ok_or_else(|| ParseError::invalid("Invalid huffman code"));
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err(ParseError::invalid("Invalid huffman code"))
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u16; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u16; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Decompress raw deflate `data`, failing if the output would exceed `max_size` bytes
+pub fn inflate(data: &[u8], max_size: usize) -> Result<Vec<u8>, ParseError> {
+    let mut input = BitInput {
+        data,
+        position: 0,
+        bit_buffer: 0,
+        bit_count: 0,
+    };
+    let mut out = vec![];
+
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => stored(&mut input, &mut out, max_size)?,
+            1 => {
+                let (lengths, distances) = fixed_tables()?;
+                codes(&mut input, &mut out, &lengths, &distances, max_size)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic_tables(&mut input)?;
+                codes(&mut input, &mut out, &lengths, &distances, max_size)?;
+            }
+            _ => return Err(ParseError::invalid("Invalid deflate block type")),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn stored(input: &mut BitInput, out: &mut Vec<u8>, max_size: usize) -> Result<(), ParseError> {
+    // stored blocks start at a byte boundary
+    input.bit_buffer = 0;
+    input.bit_count = 0;
+    let header = input
+        .data
+        .get(input.position..input.position + 4)
+        .ok_or_else(|| ParseError::invalid("Deflate data ended unexpectedly"))?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if length != !complement {
+        return Err(ParseError::invalid("Invalid stored deflate block length"));
+    }
+    input.position += 4;
+    let block = input
+        .data
+        .get(input.position..input.position + length as usize)
+        .ok_or_else(|| ParseError::invalid("Deflate data ended unexpectedly"))?;
+    if out.len() + block.len() > max_size {
+        return Err(ParseError::limit_exceeded(
+            "Decompressed data exceeds limit",
+        ));
+    }
+    out.extend_from_slice(block);
+    input.position += length as usize;
+    Ok(())
+}
+
+fn codes(
+    input: &mut BitInput,
+    out: &mut Vec<u8>,
+    lengths: &Huffman,
+    distances: &Huffman,
+    max_size: usize,
+) -> Result<(), ParseError> {
+    loop {
+        let symbol = lengths.decode(input)? as usize;
+        match symbol {
+            0..=255 => {
+                if out.len() >= max_size {
+                    return Err(ParseError::limit_exceeded(
+                        "Decompressed data exceeds limit",
+                    ));
+                }
+                out.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            _ => {
+                let symbol = symbol - 257;
+                if symbol >= 29 {
+                    return Err(ParseError::invalid("Invalid deflate length symbol"));
+                }
+                let length = LENGTH_BASE[symbol] as usize
+                    + input.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+                let symbol = distances.decode(input)? as usize;
+                if symbol >= 30 {
+                    return Err(ParseError::invalid("Invalid deflate distance symbol"));
+                }
+                let distance = DISTANCE_BASE[symbol] as usize
+                    + input.bits(DISTANCE_EXTRA[symbol] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(ParseError::invalid("Deflate distance too far back"));
+                }
+                if out.len() + length > max_size {
+                    return Err(ParseError::limit_exceeded(
+                        "Decompressed data exceeds limit",
+                    ));
+                }
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+fn fixed_tables() -> Result<(Huffman, Huffman), ParseError> {
+    let mut lengths = [0u16; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_tables(input: &mut BitInput) -> Result<(Huffman, Huffman), ParseError> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+    let amount_lengths = input.bits(5)? as usize + 257;
+    let amount_distances = input.bits(5)? as usize + 1;
+    let amount_codes = input.bits(4)? as usize + 4;
+    if amount_lengths > 286 || amount_distances > 30 {
+        return Err(ParseError::invalid("Invalid dynamic deflate table sizes"));
+    }
+
+    let mut code_lengths = [0u16; 19];
+    for &index in ORDER.iter().take(amount_codes) {
+        code_lengths[index] = input.bits(3)? as u16;
+    }
+    let code_huffman = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u16; amount_lengths + amount_distances];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_huffman.decode(input)?;
+        if symbol < 16 {
+            lengths[index] = symbol;
+            index += 1;
+            continue;
+        }
+        let (value, repeat) = match symbol {
+            16 => {
+                if index == 0 {
+                    return Err(ParseError::invalid(
+                        "Deflate repeat without previous length",
+                    ));
+                }
+                (lengths[index - 1], 3 + input.bits(2)? as usize)
+            }
+            17 => (0, 3 + input.bits(3)? as usize),
+            _ => (0, 11 + input.bits(7)? as usize),
+        };
+        if index + repeat > lengths.len() {
+            return Err(ParseError::invalid("Too many deflate code lengths"));
+        }
+        lengths[index..index + repeat].fill(value);
+        index += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(ParseError::invalid(
+            "Deflate table without end of block code",
+        ));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..amount_lengths])?,
+        Huffman::new(&lengths[amount_lengths..])?,
+    ))
+}
+
+/// Writes bits starting at the least significant bit of every byte
+struct BitOutput {
+    data: Vec<u8>,
+    bit_buffer: u64,
+    bit_count: u32,
+}
+
+impl BitOutput {
+    fn bits(&mut self, value: u32, count: u32) {
+        self.bit_buffer |= (value as u64) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.data.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Write a huffman code, these are stored starting at their most significant bit
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.data.push(self.bit_buffer as u8);
+        }
+        self.data
+    }
+}
+
+fn fixed_literal(output: &mut BitOutput, symbol: u32) {
+    match symbol {
+        0..=143 => output.code(0x30 + symbol, 8),
+        144..=255 => output.code(0x190 + symbol - 144, 9),
+        256..=279 => output.code(symbol - 256, 7),
+        _ => output.code(0xC0 + symbol - 280, 8),
+    }
+}
+
+/// Index of the last entry in `bases` that is at most `value`
+fn base_index(bases: &[u16], value: usize) -> usize {
+    bases
+        .iter()
+        .rposition(|base| *base as usize <= value)
+        .unwrap_or(0)
+}
+
+/// Compress `data` into raw deflate with a single block using the fixed huffman codes
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    const WINDOW: usize = 32 * 1024;
+    const MAX_CHAIN: usize = 64;
+    const HASH_SIZE: usize = 1 << 15;
+
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize)
+            & (HASH_SIZE - 1)
+    };
+    // most recent position for every hash and the previous position with the same hash
+    let mut head = vec![usize::MAX; HASH_SIZE];
+    let mut previous = vec![usize::MAX; data.len()];
+
+    let mut output = BitOutput {
+        data: Vec::with_capacity(data.len() / 4),
+        bit_buffer: 0,
+        bit_count: 0,
+    };
+    output.bits(1, 1); // last block
+    output.bits(1, 2); // fixed huffman codes
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best_length = 0;
+        let mut best_distance = 0;
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            let mut candidate = head[h];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let max_length = (data.len() - i).min(258);
+                let length = (0..max_length)
+                    .take_while(|&k| data[candidate + k] == data[i + k])
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = i - candidate;
+                    if length == max_length {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+
+        let step = if best_length >= 3 {
+            let index = base_index(&LENGTH_BASE, best_length);
+            fixed_literal(&mut output, 257 + index as u32);
+            output.bits(
+                (best_length - LENGTH_BASE[index] as usize) as u32,
+                LENGTH_EXTRA[index] as u32,
+            );
+            let index = base_index(&DISTANCE_BASE, best_distance);
+            output.code(index as u32, 5);
+            output.bits(
+                (best_distance - DISTANCE_BASE[index] as usize) as u32,
+                DISTANCE_EXTRA[index] as u32,
+            );
+            best_length
+        } else {
+            fixed_literal(&mut output, data[i] as u32);
+            1
+        };
+
+        let hashed = (i + step).min(data.len().saturating_sub(2));
+        for (position, link) in previous.iter_mut().enumerate().take(hashed).skip(i) {
+            let h = hash(position);
+            *link = head[h];
+            head[h] = position;
+        }
+        i += step;
+    }
+    fixed_literal(&mut output, 256);
+    output.finish()
+}
+
+/// Compress `data` with zlib framing
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Decompress zlib framed data as used by pdf streams, the trailing checksum is not verified
+pub fn zlib_decompress(data: &[u8], max_size: usize) -> Result<Vec<u8>, ParseError> {
+    if data.len() < 2 || (((data[0] as u16) << 8) | data[1] as u16) % 31 != 0 {
+        return Err(ParseError::invalid("Invalid zlib header"));
+    }
+    if data[0] & 0x0F != 8 {
+        return Err(ParseError::unsupported(
+            "Unsupported zlib compression method",
+        ));
+    }
+    inflate(&data[2..], max_size)
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,357 @@
+//! Differences between two versions of a page
+//!
+//! v6 strokes, text and layers are matched by their [CrdtId], strokes of v3 to v5 files have no
+//! id and are matched by their points instead. [PageDiff] implements [fmt::Display] to print a
+//! readable summary of the changes.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    shared::{pen_color::PenColor, tool::Tool},
+    v6::{
+        crdt::CrdtId,
+        scene_item::{glyph_range::Highlight, group::Group, text::Text, SceneItem},
+        scene_tree::SceneTree,
+    },
+    RemarkableFile,
+};
+
+/// Largest distance between two points that are still considered the same
+const POINT_TOLERANCE: f32 = 0.01;
+
+/// A change of a single part of a page
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<T> {
+    Added(T),
+    Removed(T),
+    Modified { before: T, after: T },
+}
+
+/// A stroke as compared by [diff]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffStroke {
+    /// Id the line was first drawn as, only v6 lines have one
+    ///
+    /// Lines moved to another layer keep it, see [crate::v6::crdt::CrdtSequenceItem::origin_id].
+    pub id: Option<CrdtId>,
+    /// Name of the layer the stroke is in
+    pub layer: String,
+    pub tool: Tool,
+    pub color: PenColor,
+    /// Position of every point
+    pub points: Vec<(f32, f32)>,
+}
+
+impl DiffStroke {
+    fn same_points(&self, other: &DiffStroke) -> bool {
+        self.points.len() == other.points.len()
+            && self.points.iter().zip(other.points.iter()).all(|(a, b)| {
+                (a.0 - b.0).abs() <= POINT_TOLERANCE && (a.1 - b.1).abs() <= POINT_TOLERANCE
+            })
+    }
+
+    fn same_style(&self, other: &DiffStroke) -> bool {
+        self.layer == other.layer && self.tool == other.tool && self.color == other.color
+    }
+}
+
+/// A change of a layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayerChange {
+    Added(String),
+    Removed(String),
+    Renamed { from: String, to: String },
+    Hidden(String),
+    Shown(String),
+}
+
+/// Everything that changed between two versions of a page, see [diff]
+#[derive(Debug, Clone, Default)]
+pub struct PageDiff {
+    pub strokes: Vec<Change<DiffStroke>>,
+    /// Runs of typed text that were added or removed, only v6 files have text
+    pub text: Vec<Change<String>>,
+    pub layers: Vec<LayerChange>,
+    /// Highlights of the newer version that the older one did not have
+    pub new_highlights: Vec<Highlight>,
+}
+
+impl PageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty()
+            && self.text.is_empty()
+            && self.layers.is_empty()
+            && self.new_highlights.is_empty()
+    }
+}
+
+impl fmt::Display for PageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        let stroke = |s: &DiffStroke| {
+            format!(
+                "{:?} {:?} stroke with {} points in '{}'",
+                s.color,
+                s.tool,
+                s.points.len(),
+                s.layer
+            )
+        };
+        for change in self.layers.iter() {
+            match change {
+                LayerChange::Added(name) => writeln!(f, "+ layer '{name}'")?,
+                LayerChange::Removed(name) => writeln!(f, "- layer '{name}'")?,
+                LayerChange::Renamed { from, to } => {
+                    writeln!(f, "~ layer '{from}' renamed to '{to}'")?
+                }
+                LayerChange::Hidden(name) => writeln!(f, "~ layer '{name}' hidden")?,
+                LayerChange::Shown(name) => writeln!(f, "~ layer '{name}' shown")?,
+            }
+        }
+        for change in self.strokes.iter() {
+            match change {
+                Change::Added(s) => writeln!(f, "+ {}", stroke(s))?,
+                Change::Removed(s) => writeln!(f, "- {}", stroke(s))?,
+                Change::Modified { before, after } => {
+                    writeln!(f, "~ {} is now {}", stroke(before), stroke(after))?
+                }
+            }
+        }
+        for change in self.text.iter() {
+            match change {
+                Change::Added(text) => writeln!(f, "+ text {text:?}")?,
+                Change::Removed(text) => writeln!(f, "- text {text:?}")?,
+                Change::Modified { before, after } => {
+                    writeln!(f, "~ text {before:?} is now {after:?}")?
+                }
+            }
+        }
+        for highlight in self.new_highlights.iter() {
+            writeln!(f, "+ {:?} highlight {:?}", highlight.color, highlight.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Changes needed to get from page `a` to page `b`
+///
+/// Strokes are matched by id if both pages are v6 and by their points otherwise.
+pub fn diff(a: &RemarkableFile, b: &RemarkableFile) -> PageDiff {
+    let mut diff = PageDiff {
+        strokes: diff_strokes(strokes(a), strokes(b)),
+        ..Default::default()
+    };
+
+    match (a, b) {
+        (RemarkableFile::V6 { tree: a, .. }, RemarkableFile::V6 { tree: b, .. }) => {
+            diff.layers = diff_v6_layers(a, b);
+            diff.text = diff_text(a, b);
+            let old = a.highlights();
+            diff.new_highlights = b
+                .highlights()
+                .into_iter()
+                .filter(|h| {
+                    !old.iter().any(|o| {
+                        o.start == h.start
+                            && o.length == h.length
+                            && o.color == h.color
+                            && o.text == h.text
+                    })
+                })
+                .collect();
+        }
+        (a, b) => {
+            let (a, b) = (layer_names(a), layer_names(b));
+            diff.layers.extend(
+                a.iter()
+                    .filter(|name| !b.contains(name))
+                    .cloned()
+                    .map(LayerChange::Removed),
+            );
+            diff.layers.extend(
+                b.iter()
+                    .filter(|name| !a.contains(name))
+                    .cloned()
+                    .map(LayerChange::Added),
+            );
+        }
+    }
+    diff
+}
+
+fn v3_layer_name(index: usize) -> String {
+    format!("Layer {}", index + 1)
+}
+
+fn layer_names(file: &RemarkableFile) -> Vec<String> {
+    match file {
+        RemarkableFile::V6 { tree, .. } => tree
+            .layers()
+            .into_iter()
+            .map(|g| g.label.value.clone())
+            .collect(),
+        RemarkableFile::Other { pages, .. } => {
+            let layers = pages.iter().map(|p| p.layers.len()).max().unwrap_or(0);
+            (0..layers).map(v3_layer_name).collect()
+        }
+    }
+}
+
+/// All strokes of a page, including the ones in hidden layers
+fn strokes(file: &RemarkableFile) -> Vec<DiffStroke> {
+    let mut strokes = vec![];
+    match file {
+        RemarkableFile::V6 { tree, .. } => {
+            let mut visited = HashSet::new();
+            for layer in tree.layers() {
+                collect_v6_strokes(tree, layer, &layer.label.value, &mut strokes, &mut visited);
+            }
+        }
+        RemarkableFile::Other { pages, .. } => {
+            for page in pages {
+                for (index, layer) in page.layers.iter().enumerate() {
+                    strokes.extend(layer.lines.iter().map(|line| DiffStroke {
+                        id: None,
+                        layer: v3_layer_name(index),
+                        tool: line.tool,
+                        color: line.color,
+                        points: line.points.iter().map(|p| (p.x, p.y)).collect(),
+                    }));
+                }
+            }
+        }
+    }
+    strokes
+}
+
+fn collect_v6_strokes(
+    tree: &SceneTree,
+    group: &Group,
+    layer: &str,
+    strokes: &mut Vec<DiffStroke>,
+    visited: &mut HashSet<CrdtId>,
+) {
+    if !visited.insert(group.node_id) {
+        return;
+    }
+    for item in group.children.iter() {
+        match &item.value {
+            SceneItem::Group(child) => {
+                if let Some(child) = tree.node(&child.node_id) {
+                    collect_v6_strokes(tree, child, layer, strokes, visited);
+                }
+            }
+            SceneItem::Line(line) => strokes.push(DiffStroke {
+                id: Some(item.origin_id()),
+                layer: layer.to_owned(),
+                tool: line.tool,
+                color: line.color,
+                points: line.points.iter().map(|p| (p.x, p.y)).collect(),
+            }),
+            _ => (),
+        }
+    }
+}
+
+fn diff_strokes(a: Vec<DiffStroke>, b: Vec<DiffStroke>) -> Vec<Change<DiffStroke>> {
+    let mut changes = vec![];
+    let mut unmatched_b: Vec<Option<DiffStroke>> = b.into_iter().map(Some).collect();
+    let b_ids: HashMap<CrdtId, usize> = unmatched_b
+        .iter()
+        .enumerate()
+        .filter_map(|(i, s)| Some((s.as_ref()?.id?, i)))
+        .collect();
+
+    let mut unmatched_a = vec![];
+    for stroke in a {
+        // by id first, by points for strokes without one
+        let index = match stroke.id {
+            Some(id) if !b_ids.is_empty() => b_ids.get(&id).copied(),
+            _ => unmatched_b
+                .iter()
+                .position(|s| matches!(s, Some(s) if s.same_points(&stroke))),
+        };
+        match index.and_then(|i| unmatched_b[i].take()) {
+            Some(other) => {
+                if !(other.same_style(&stroke) && other.same_points(&stroke)) {
+                    changes.push(Change::Modified {
+                        before: stroke,
+                        after: other,
+                    });
+                }
+            }
+            None => unmatched_a.push(stroke),
+        }
+    }
+
+    changes.extend(unmatched_a.into_iter().map(Change::Removed));
+    changes.extend(unmatched_b.into_iter().flatten().map(Change::Added));
+    changes
+}
+
+fn diff_v6_layers(a: &SceneTree, b: &SceneTree) -> Vec<LayerChange> {
+    let (a, b) = (a.layers(), b.layers());
+    let mut changes = vec![];
+    for layer in a.iter() {
+        match b.iter().find(|other| other.node_id == layer.node_id) {
+            Some(other) => {
+                let (from, to) = (&layer.label.value, &other.label.value);
+                if from != to {
+                    changes.push(LayerChange::Renamed {
+                        from: from.clone(),
+                        to: to.clone(),
+                    });
+                }
+                match (layer.visible.value, other.visible.value) {
+                    (true, false) => changes.push(LayerChange::Hidden(to.clone())),
+                    (false, true) => changes.push(LayerChange::Shown(to.clone())),
+                    _ => (),
+                }
+            }
+            None => changes.push(LayerChange::Removed(layer.label.value.clone())),
+        }
+    }
+    for layer in b.iter() {
+        if !a.iter().any(|other| other.node_id == layer.node_id) {
+            changes.push(LayerChange::Added(layer.label.value.clone()));
+        }
+    }
+    changes
+}
+
+/// Characters of the page's text in order with their ids, deleted ones left out
+fn text_chars(tree: &SceneTree) -> Vec<(CrdtId, char)> {
+    tree.root_text().map(Text::chars).unwrap_or_default()
+}
+
+fn diff_text(a: &SceneTree, b: &SceneTree) -> Vec<Change<String>> {
+    let (a, b) = (text_chars(a), text_chars(b));
+    // consecutive characters only on one side make up one run
+    let runs = |from: &[(CrdtId, char)], other: &[(CrdtId, char)]| {
+        let other: HashSet<CrdtId> = other.iter().map(|(id, _)| *id).collect();
+        let mut runs = vec![];
+        let mut run = String::new();
+        for (id, text) in from {
+            if other.contains(id) {
+                if !run.is_empty() {
+                    runs.push(std::mem::take(&mut run));
+                }
+            } else {
+                run.push(*text);
+            }
+        }
+        if !run.is_empty() {
+            runs.push(run);
+        }
+        runs
+    };
+
+    let mut changes: Vec<Change<String>> = runs(&a, &b).into_iter().map(Change::Removed).collect();
+    changes.extend(runs(&b, &a).into_iter().map(Change::Added));
+    changes
+}
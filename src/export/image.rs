@@ -0,0 +1,318 @@
+//! Raster images and reading and writing of PNG files
+//!
+//! Only non-interlaced PNGs are supported, which is what the tablet's templates use.
+
+use crate::{
+    compression::{crc32, zlib_compress, zlib_decompress},
+    pdf::paeth,
+    ParseError,
+};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Upper bound for the decoded size of an image
+const MAX_IMAGE_SIZE: usize = 256 * 1024 * 1024;
+
+/// Image with 8 bits per channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// 1 for gray, 2 for gray with alpha, 3 for RGB and 4 for RGBA
+    pub channels: u8,
+    /// Rows of pixels from top to bottom
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn from_png(png: &[u8]) -> Result<Image, ParseError> {
+        if !png.starts_with(PNG_SIGNATURE) {
+            return Err(ParseError::invalid("Not a png file"));
+        }
+
+        let mut header = None;
+        let mut palette = vec![];
+        let mut transparency = vec![];
+        let mut compressed = vec![];
+        let mut position = PNG_SIGNATURE.len();
+        while position + 8 <= png.len() {
+            let length = be_u32(&png[position..position + 4]) as usize;
+            let chunk_type = &png[position + 4..position + 8];
+            let chunk = png
+                .get(position + 8..(position + 8).saturating_add(length))
+                .ok_or_else(|| ParseError::invalid("Png file ended unexpectedly"))?;
+            position += 12 + length;
+
+            match chunk_type {
+                b"IHDR" if chunk.len() >= 13 => header = Some(chunk.to_vec()),
+                b"PLTE" => palette = chunk.to_vec(),
+                b"tRNS" => transparency = chunk.to_vec(),
+                b"IDAT" => compressed.extend_from_slice(chunk),
+                b"IEND" => break,
+                _ => (),
+            }
+        }
+
+        let header = header.ok_or_else(|| ParseError::invalid("Png file has no header"))?;
+        let width = be_u32(&header[0..4]);
+        let height = be_u32(&header[4..8]);
+        if width == 0 || height == 0 {
+            return Err(ParseError::invalid("Png image is empty"));
+        }
+        let bit_depth = header[8];
+        let color_type = header[9];
+        if header[12] != 0 {
+            return Err(ParseError::unsupported(
+                "Interlaced png files are not supported",
+            ));
+        }
+        let samples: usize = match color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            6 => 4,
+            _ => return Err(ParseError::invalid("Invalid png color type")),
+        };
+        if !matches!(bit_depth, 1 | 2 | 4 | 8 | 16) {
+            return Err(ParseError::invalid("Invalid png bit depth"));
+        }
+
+        let bits_per_pixel = samples * bit_depth as usize;
+        let row_size = (width as usize * bits_per_pixel + 7) / 8;
+        let size = (row_size + 1)
+            .checked_mul(height as usize)
+            .filter(|size| *size <= MAX_IMAGE_SIZE)
+            .ok_or_else(|| ParseError::limit_exceeded("Png image exceeds size limit"))?;
+        let filtered = zlib_decompress(&compressed, size)?;
+        if filtered.len() < size {
+            return Err(ParseError::invalid("Png image data ended unexpectedly"));
+        }
+        let rows = unfilter(
+            &filtered,
+            row_size,
+            height as usize,
+            (bits_per_pixel + 7) / 8,
+        )?;
+
+        // expand every pixel to 8 bit samples
+        let sample = |row: &[u8], index: usize| -> u16 {
+            match bit_depth {
+                16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+                8 => row[index] as u16,
+                depth => {
+                    let per_byte = 8 / depth as usize;
+                    let shift = 8 - depth as usize * (index % per_byte + 1);
+                    ((row[index / per_byte] >> shift) & ((1 << depth) - 1)) as u16
+                }
+            }
+        };
+        let scale = |value: u16| -> u8 {
+            match bit_depth {
+                16 => (value >> 8) as u8,
+                depth => (value as u32 * 255 / ((1 << depth) - 1)) as u8,
+            }
+        };
+
+        let channels = match color_type {
+            3 if !transparency.is_empty() => 4,
+            3 => 3,
+            0 if transparency.len() >= 2 => 2,
+            2 if transparency.len() >= 6 => 4,
+            _ => samples as u8,
+        };
+        let mut data = Vec::with_capacity(width as usize * height as usize * channels as usize);
+        for row in rows.chunks(row_size) {
+            for x in 0..width as usize {
+                match color_type {
+                    3 => {
+                        let index = sample(row, x) as usize;
+                        let color = palette
+                            .get(index * 3..index * 3 + 3)
+                            .ok_or_else(|| ParseError::invalid("Png palette index out of range"))?;
+                        data.extend_from_slice(color);
+                        if channels == 4 {
+                            data.push(transparency.get(index).copied().unwrap_or(255));
+                        }
+                    }
+                    _ => {
+                        let values: Vec<u16> =
+                            (0..samples).map(|s| sample(row, x * samples + s)).collect();
+                        data.extend(values.iter().map(|v| scale(*v)));
+                        // a single transparent color given by tRNS
+                        if channels as usize > samples {
+                            let transparent = transparency
+                                .chunks_exact(2)
+                                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                                .take(samples)
+                                .eq(values.iter().copied());
+                            data.push(if transparent { 0 } else { 255 });
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Image {
+            width,
+            height,
+            channels,
+            data,
+        })
+    }
+
+    /// Encode as 8 bit png
+    pub fn to_png(&self) -> Vec<u8> {
+        let color_type = match self.channels {
+            1 => 0,
+            2 => 4,
+            3 => 2,
+            _ => 6,
+        };
+        let mut header = vec![];
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+        let row_size = self.width as usize * self.channels as usize;
+        let pixel_size = self.channels as usize;
+        let mut filtered = Vec::with_capacity((row_size + 1) * self.height as usize);
+        let empty = vec![0; row_size];
+        let mut previous = &empty[..];
+        for row in self.data.chunks(row_size.max(1)) {
+            // the filter with the smallest sum of differences usually compresses best
+            let candidates = (0..5u8).map(|filter| {
+                let mut out = Vec::with_capacity(row_size + 1);
+                out.push(filter);
+                for i in 0..row.len() {
+                    let left = if i >= pixel_size {
+                        row[i - pixel_size]
+                    } else {
+                        0
+                    };
+                    let up = previous[i];
+                    let up_left = if i >= pixel_size {
+                        previous[i - pixel_size]
+                    } else {
+                        0
+                    };
+                    out.push(row[i].wrapping_sub(match filter {
+                        0 => 0,
+                        1 => left,
+                        2 => up,
+                        3 => ((left as u16 + up as u16) / 2) as u8,
+                        _ => paeth(left, up, up_left),
+                    }));
+                }
+                out
+            });
+            let best = candidates
+                .min_by_key(|out| {
+                    out[1..]
+                        .iter()
+                        .map(|b| (*b as i8).unsigned_abs() as u32)
+                        .sum::<u32>()
+                })
+                .unwrap_or_default();
+            filtered.extend(best);
+            previous = row;
+        }
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib_compress(&filtered));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Color and alpha of the pixel at `x`, `y`, `None` outside of the image
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let channels = self.channels as usize;
+        let start = (y as usize * self.width as usize + x as usize) * channels;
+        match self.data.get(start..start + channels)? {
+            [gray] => Some([*gray, *gray, *gray, 255]),
+            [gray, alpha] => Some([*gray, *gray, *gray, *alpha]),
+            [r, g, b] => Some([*r, *g, *b, 255]),
+            [r, g, b, a] => Some([*r, *g, *b, *a]),
+            _ => None,
+        }
+    }
+
+    /// Split into the color channels and, if there is one, the alpha channel
+    pub fn split_alpha(&self) -> (Vec<u8>, Option<Vec<u8>>) {
+        match self.channels {
+            2 | 4 => {
+                let channels = self.channels as usize;
+                let color = self
+                    .data
+                    .chunks(channels)
+                    .flat_map(|p| p[..channels - 1].iter().copied())
+                    .collect();
+                let alpha = self
+                    .data
+                    .chunks(channels)
+                    .map(|p| p[channels - 1])
+                    .collect();
+                (color, Some(alpha))
+            }
+            _ => (self.data.clone(), None),
+        }
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Undo the filter applied to every row of a png
+fn unfilter(
+    data: &[u8],
+    row_size: usize,
+    height: usize,
+    pixel_size: usize,
+) -> Result<Vec<u8>, ParseError> {
+    let mut out = vec![0u8; row_size * height];
+    for y in 0..height {
+        let filter = data[y * (row_size + 1)];
+        let source = &data[y * (row_size + 1) + 1..(y + 1) * (row_size + 1)];
+        let (before, current) = out.split_at_mut(y * row_size);
+        let previous = if y > 0 {
+            &before[(y - 1) * row_size..]
+        } else {
+            &[][..]
+        };
+        let current = &mut current[..row_size];
+        for i in 0..row_size {
+            let left = if i >= pixel_size {
+                current[i - pixel_size]
+            } else {
+                0
+            };
+            let up = previous.get(i).copied().unwrap_or(0);
+            let up_left = if i >= pixel_size {
+                previous.get(i - pixel_size).copied().unwrap_or(0)
+            } else {
+                0
+            };
+            current[i] = source[i].wrapping_add(match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(ParseError::invalid("Invalid png filter")),
+            });
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,196 @@
+//! Drawing parsed pages into other formats
+//!
+//! Every exporter works on a [PageDrawing], the strokes of a page in the coordinates of the
+//! tablet's screen: [PAGE_WIDTH] by [PAGE_HEIGHT] pixels with the origin in the top left corner.
+//! Highlights are in the coordinates of the PDF page they were made on.
+
+use crate::{
+    other::Page,
+    shared::{pen_color::PenColor, tool::Tool},
+    v6::{
+        crdt::CrdtId,
+        scene_item::{glyph_range::GlyphRange, SceneItem},
+        scene_tree::SceneTree,
+    },
+    RemarkableFile,
+};
+
+pub mod image;
+pub mod pdf;
+pub mod raster;
+pub mod template;
+pub mod thumbnail;
+pub mod timeline;
+
+/// Width of the tablet's screen in pixels
+pub const PAGE_WIDTH: f32 = 1404.0;
+/// Height of the tablet's screen in pixels
+pub const PAGE_HEIGHT: f32 = 1872.0;
+/// Resolution of the tablet's screen
+pub const PAGE_DPI: f32 = 226.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokePoint {
+    pub x: f32,
+    pub y: f32,
+    /// Width of the stroke at this point in pixels
+    pub width: f32,
+}
+
+/// Colors given to the authors of a page by [PageDrawing::color_by_author], in order
+pub const AUTHOR_COLORS: [PenColor; 6] = [
+    PenColor::Blue,
+    PenColor::Red,
+    PenColor::Green,
+    PenColor::Magenta,
+    PenColor::Cyan,
+    PenColor::Grey,
+];
+
+/// A line drawn with a pen
+#[derive(Debug, Clone)]
+pub struct Stroke {
+    pub tool: Tool,
+    pub color: PenColor,
+    pub points: Vec<StrokePoint>,
+    /// Uuid of whoever drew the stroke, only known for v6 files
+    pub author: Option<String>,
+    /// Id the v6 line was first drawn as, later strokes have larger ids, see [timeline::timeline]
+    pub id: Option<CrdtId>,
+}
+
+impl Stroke {
+    /// Opacity the stroke is drawn with, highlighters are see-through
+    pub fn opacity(&self) -> f32 {
+        let alpha = self.color.to_rgba()[3] as f32 / 255.0;
+        match self.tool.base() {
+            Tool::Highlighter => alpha * 0.4,
+            _ => alpha,
+        }
+    }
+}
+
+/// Everything drawn on a page
+#[derive(Debug, Clone, Default)]
+pub struct PageDrawing {
+    /// Strokes in the order they are drawn, erasers and selections are left out
+    pub strokes: Vec<Stroke>,
+    /// Highlighted text, one entry per glyph range, only v6 files have highlights
+    ///
+    /// The rectangles are in the coordinates of the shown PDF page with the origin in its lower
+    /// left corner, see [PageDrawing::screen_highlights] for exporters without the PDF page.
+    pub highlights: Vec<GlyphRange>,
+}
+
+impl PageDrawing {
+    pub fn new(file: &RemarkableFile) -> PageDrawing {
+        match file {
+            RemarkableFile::V6 { tree, .. } => PageDrawing::from_scene_tree(tree),
+            RemarkableFile::Other { pages, .. } => {
+                let mut drawing = PageDrawing::default();
+                for page in pages {
+                    drawing.strokes.extend(PageDrawing::from_page(page).strokes);
+                }
+                drawing
+            }
+        }
+    }
+
+    /// Drawing of a v6 page
+    pub fn from_scene_tree(tree: &SceneTree) -> PageDrawing {
+        let mut drawing = PageDrawing::default();
+        // x is centered on the page in v6
+        let offset_x = PAGE_WIDTH / 2.0;
+        for (_, item) in tree.visible_sequence_items() {
+            match &item.value {
+                SceneItem::Line(line) if is_drawn(line.tool) => drawing.strokes.push(Stroke {
+                    tool: line.tool,
+                    color: line.color,
+                    author: tree.author_of(&item.origin_id()).map(str::to_owned),
+                    id: Some(item.origin_id()),
+                    points: line
+                        .points
+                        .iter()
+                        .map(|p| StrokePoint {
+                            x: p.x + offset_x,
+                            y: p.y,
+                            // stored in quarter pixels
+                            width: p.width / 4.0,
+                        })
+                        .collect(),
+                }),
+                SceneItem::GlyphRange(glyph) => drawing.highlights.push(glyph.clone()),
+                _ => (),
+            }
+        }
+        drawing
+    }
+
+    /// Drawing of a page of a v3 to v5 file
+    pub fn from_page(page: &Page) -> PageDrawing {
+        let mut drawing = PageDrawing::default();
+        let lines = page.layers.iter().flat_map(|layer| layer.lines.iter());
+        for line in lines.filter(|line| is_drawn(line.tool)) {
+            drawing.strokes.push(Stroke {
+                tool: line.tool,
+                color: line.color,
+                author: None,
+                id: None,
+                points: line
+                    .points
+                    .iter()
+                    .map(|p| StrokePoint {
+                        x: p.x,
+                        y: p.y,
+                        width: p.width,
+                    })
+                    .collect(),
+            })
+        }
+        drawing
+    }
+
+    /// Draw the strokes of the author at index `i` of `authors` in [AUTHOR_COLORS]`[i]`,
+    /// colors repeat if there are more authors than colors
+    ///
+    /// Strokes without a known author keep their color.
+    pub fn color_by_author(&mut self, authors: &[String]) {
+        for stroke in self.strokes.iter_mut() {
+            let index = stroke
+                .author
+                .as_ref()
+                .and_then(|author| authors.iter().position(|a| a == author));
+            if let Some(index) = index {
+                stroke.color = AUTHOR_COLORS[index % AUTHOR_COLORS.len()];
+            }
+        }
+    }
+
+    /// Color and `[x, y, width, height]` on the tablet's screen of every highlight rectangle
+    ///
+    /// Without the PDF page its size is unknown, the page is taken to be as large as the screen
+    /// with one unit per pixel.
+    pub fn screen_highlights(&self) -> Vec<(PenColor, [f32; 4])> {
+        self.highlights
+            .iter()
+            .flat_map(|highlight| {
+                highlight.rectangles.iter().map(|r| {
+                    let (y, height) = (PAGE_HEIGHT - (r.y + r.h) as f32, r.h as f32);
+                    (highlight.color, [r.x as f32, y, r.w as f32, height])
+                })
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty() && self.highlights.is_empty()
+    }
+}
+
+/// False for tools that don't leave anything on the page
+fn is_drawn(tool: Tool) -> bool {
+    !matches!(
+        tool.base(),
+        Tool::Eraser | Tool::EraseArea | Tool::EraseAll | Tool::SelectionBrush
+    )
+}
@@ -0,0 +1,686 @@
+//! Drawing pages on top of the PDF a document was made from
+//!
+//! For PDF documents the `.rm` files only hold what was written on top of the original pages.
+//! `.content` maps every page of the document to a page of the PDF, pages inserted on the tablet
+//! have no PDF page and become blank pages of the size of the page before them. EPUBs work the
+//! same way, the tablet converts them to a `<uuid>.pdf` stored next to the `.epub` and draws on
+//! top of that.
+//!
+//! The device shows a PDF page scaled to fit its screen, centered horizontally and aligned to
+//! the top, strokes are mapped into the page the same way. Highlighted text is added as
+//! `/Highlight` annotations by default so PDF readers can list it.
+//!
+//! Pages without a PDF page, all pages of a notebook and pages inserted into a PDF, can get their
+//! template drawn under the strokes by setting [PdfOptions::templates].
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    compression::zlib_compress,
+    notebook::Notebook,
+    pdf::{format_real, Dictionary, PdfDocument, PdfObject},
+    v6::scene_item::glyph_range::GlyphRange,
+    ParseError,
+};
+
+use super::{
+    template::{PathSegment, Template, Templates},
+    PageDrawing, PAGE_DPI, PAGE_HEIGHT, PAGE_WIDTH,
+};
+
+/// Options for [annotate_pdf_with_options]
+#[derive(Debug, Clone)]
+pub struct PdfOptions {
+    /// Add highlighted text as `/Highlight` annotations that PDF readers can list, instead of
+    /// drawing it into the page
+    pub highlight_annotations: bool,
+    /// Where to find the templates drawn under pages that have no PDF page, no templates are
+    /// drawn if `None`
+    pub templates: Option<Templates>,
+    /// Draw the strokes of every author in its own color, see [PageDrawing::color_by_author]
+    pub color_by_author: bool,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            highlight_annotations: true,
+            templates: None,
+            color_by_author: false,
+        }
+    }
+}
+
+impl Notebook {
+    /// Draw the pages on top of the document's `<uuid>.pdf`, see [annotate_pdf]
+    pub fn to_annotated_pdf(&self) -> Result<Vec<u8>, ParseError> {
+        self.to_annotated_pdf_with_options(&PdfOptions::default())
+    }
+
+    pub fn to_annotated_pdf_with_options(
+        &self,
+        options: &PdfOptions,
+    ) -> Result<Vec<u8>, ParseError> {
+        let name = format!("{}.pdf", self.id);
+        let source = self
+            .other_files
+            .get(&name)
+            .ok_or_else(|| ParseError::invalid(format!("Document has no '{name}' to draw on")))?;
+        annotate_pdf_with_options(source, self, options)
+    }
+
+    /// Draw the pages into a PDF, on top of the document's `<uuid>.pdf` if it has one and on
+    /// blank pages of the tablet's screen size otherwise
+    pub fn to_pdf(&self) -> Result<Vec<u8>, ParseError> {
+        self.to_pdf_with_options(&PdfOptions::default())
+    }
+
+    pub fn to_pdf_with_options(&self, options: &PdfOptions) -> Result<Vec<u8>, ParseError> {
+        match self.other_files.get(&format!("{}.pdf", self.id)) {
+            Some(source) => annotate_pdf_with_options(source, self, options),
+            None => annotate_document(PdfDocument::new(), self, options),
+        }
+    }
+}
+
+/// Draw the strokes and highlights of every page of `notebook` on top of the matching page of
+/// `source` and return the new PDF
+///
+/// The pages of the result are the pages of the notebook in order. PDF pages deleted on the
+/// tablet are left out and inserted pages are added as blank pages.
+pub fn annotate_pdf(source: &[u8], notebook: &Notebook) -> Result<Vec<u8>, ParseError> {
+    annotate_pdf_with_options(source, notebook, &PdfOptions::default())
+}
+
+pub fn annotate_pdf_with_options(
+    source: &[u8],
+    notebook: &Notebook,
+    options: &PdfOptions,
+) -> Result<Vec<u8>, ParseError> {
+    annotate_document(PdfDocument::parse(source)?, notebook, options)
+}
+
+fn annotate_document(
+    mut document: PdfDocument,
+    notebook: &Notebook,
+    options: &PdfOptions,
+) -> Result<Vec<u8>, ParseError> {
+    let source_pages = document.pages()?;
+
+    let pages_root = document.add(PdfObject::Null);
+    // shared stream saving the graphics state before the original content
+    let save_state = document.add(PdfObject::Stream(Dictionary::default(), b"q\n".to_vec()));
+
+    let mut kids = vec![];
+    let mut used = HashSet::new();
+    // every template is added once and shared by all pages using it
+    let mut templates: HashMap<String, Option<PdfObject>> = HashMap::new();
+    // authors are numbered over the whole document so they have the same color on every page
+    let authors = options.color_by_author.then(|| notebook.authors());
+    // size of the page as shown, inserted pages get the size of the page before them
+    let mut previous_size = source_pages
+        .first()
+        .map(|(_, page)| PageGeometry::new(&document, page).visible_size)
+        .unwrap_or((
+            PAGE_WIDTH as f64 / PAGE_DPI as f64 * 72.0,
+            PAGE_HEIGHT as f64 / PAGE_DPI as f64 * 72.0,
+        ));
+
+    for (index, (content_page, page)) in notebook
+        .content
+        .pages
+        .iter()
+        .zip(notebook.pages.iter())
+        .enumerate()
+    {
+        let source_page = content_page
+            .redirect
+            .and_then(|index| source_pages.get(index as usize));
+        let (reference, mut dictionary) = match source_page {
+            // a pdf page shown twice needs a copy as a page can only have one parent
+            Some((number, dictionary)) if used.insert(*number) => {
+                (PdfObject::Reference(*number), dictionary.clone())
+            }
+            Some((_, dictionary)) => (document.add(PdfObject::Null), dictionary.clone()),
+            None => {
+                let mut dictionary = Dictionary::default();
+                dictionary.set("Type", PdfObject::name("Page"));
+                let (width, height) = previous_size;
+                dictionary.set("MediaBox", box_object([0.0, 0.0, width, height]));
+                dictionary.set("Resources", PdfObject::Dictionary(Dictionary::default()));
+                (document.add(PdfObject::Null), dictionary)
+            }
+        };
+        let geometry = PageGeometry::new(&document, &dictionary);
+        previous_size = geometry.visible_size;
+        dictionary.set("Parent", pages_root.clone());
+
+        let template = match (&options.templates, source_page) {
+            (Some(directory), None) => match directory.name_for_page(notebook, index) {
+                Some(name) => match templates.get(&name) {
+                    Some(template) => template.clone(),
+                    None => {
+                        let template = directory
+                            .load(&name)?
+                            .map(|t| add_template(&mut document, &t));
+                        templates.insert(name, template.clone());
+                        template
+                    }
+                },
+                None => None,
+            },
+            _ => None,
+        };
+
+        let mut drawing = page.file().map(PageDrawing::new).unwrap_or_default();
+        if let Some(authors) = &authors {
+            drawing.color_by_author(authors);
+        }
+        if options.highlight_annotations {
+            let highlights = std::mem::take(&mut drawing.highlights);
+            add_highlight_annotations(
+                &mut document,
+                &mut dictionary,
+                &reference,
+                &geometry,
+                &highlights,
+            );
+        }
+        if !drawing.is_empty() || template.is_some() {
+            add_drawing(
+                &mut document,
+                &mut dictionary,
+                &geometry,
+                &drawing,
+                template.as_ref(),
+                &save_state,
+            );
+        }
+
+        if let PdfObject::Reference(number) = reference {
+            document
+                .objects
+                .insert(number, PdfObject::Dictionary(dictionary));
+        }
+        kids.push(reference);
+    }
+
+    let mut pages = Dictionary::default();
+    pages.set("Type", PdfObject::name("Pages"));
+    pages.set("Count", PdfObject::Integer(kids.len() as i64));
+    pages.set("Kids", PdfObject::Array(kids));
+    if let PdfObject::Reference(number) = pages_root {
+        document
+            .objects
+            .insert(number, PdfObject::Dictionary(pages));
+    }
+
+    let mut catalog = document.catalog()?.clone();
+    catalog.set("Pages", pages_root);
+    // labels refer to page indices which changed
+    catalog.remove("PageLabels");
+    match document.trailer.get("Root") {
+        Some(PdfObject::Reference(number)) => {
+            let number = *number;
+            document
+                .objects
+                .insert(number, PdfObject::Dictionary(catalog));
+        }
+        _ => {
+            let root = document.add(PdfObject::Dictionary(catalog));
+            document.trailer.set("Root", root);
+        }
+    }
+
+    Ok(document.write())
+}
+
+/// Size and orientation of a page as it is shown
+struct PageGeometry {
+    /// Width and height of the visible area after rotation
+    visible_size: (f64, f64),
+    /// Transformation from the shown page, with the origin in its lower left corner, to the
+    /// page's coordinates
+    matrix: [f64; 6],
+    /// Transformation from the tablet's screen to the shown page
+    screen_matrix: [f64; 6],
+}
+
+impl PageGeometry {
+    fn new(document: &PdfDocument, page: &Dictionary) -> PageGeometry {
+        let [left, bottom, right, top] = page_box(document, page, "CropBox");
+        let (width, height) = (right - left, top - bottom);
+        let rotation = page
+            .get("Rotate")
+            .and_then(|r| document.resolve(r).as_i64())
+            .unwrap_or(0)
+            .rem_euclid(360);
+
+        // pages are shown rotated clockwise by `rotation`
+        let (visible_size, matrix) = match rotation {
+            90 => ((height, width), [0.0, 1.0, -1.0, 0.0, right, bottom]),
+            180 => ((width, height), [-1.0, 0.0, 0.0, -1.0, right, top]),
+            270 => ((height, width), [0.0, -1.0, 1.0, 0.0, left, top]),
+            _ => ((width, height), [1.0, 0.0, 0.0, 1.0, left, bottom]),
+        };
+
+        let (visible_width, visible_height) = visible_size;
+        let scale = (visible_width / PAGE_WIDTH as f64).max(visible_height / PAGE_HEIGHT as f64);
+        let screen_matrix = [
+            scale,
+            0.0,
+            0.0,
+            -scale,
+            visible_width / 2.0 - PAGE_WIDTH as f64 / 2.0 * scale,
+            visible_height,
+        ];
+        PageGeometry {
+            visible_size,
+            matrix,
+            screen_matrix,
+        }
+    }
+}
+
+fn transform(matrix: [f64; 6], x: f64, y: f64) -> (f64, f64) {
+    let [a, b, c, d, e, f] = matrix;
+    (a * x + c * y + e, b * x + d * y + f)
+}
+
+/// Rectangle `key` of `page` as `[left, bottom, right, top]`, falling back to US letter
+fn page_box(document: &PdfDocument, page: &Dictionary, key: &str) -> [f64; 4] {
+    let values: Vec<f64> = page
+        .get(key)
+        .map(|b| document.resolve(b))
+        .and_then(|b| b.as_array())
+        .map(|b| {
+            b.iter()
+                .filter_map(|v| document.resolve(v).as_f64())
+                .collect()
+        })
+        .unwrap_or_default();
+    match values[..] {
+        [x1, y1, x2, y2] if x1 != x2 && y1 != y2 => {
+            [x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)]
+        }
+        _ if key == "CropBox" => page_box(document, page, "MediaBox"),
+        _ => [0.0, 0.0, 612.0, 792.0],
+    }
+}
+
+fn box_object(rectangle: [f64; 4]) -> PdfObject {
+    PdfObject::Array(rectangle.iter().map(|v| PdfObject::Real(*v)).collect())
+}
+
+/// Add a `/Highlight` annotation for every glyph range to `page`
+///
+/// Highlight rectangles are in the coordinates of the shown page, with the origin in its lower
+/// left corner, and are mapped onto the page by its rotation and crop box only. An appearance
+/// stream is included for readers that don't draw highlights themselves.
+fn add_highlight_annotations(
+    document: &mut PdfDocument,
+    page: &mut Dictionary,
+    page_reference: &PdfObject,
+    geometry: &PageGeometry,
+    highlights: &[GlyphRange],
+) {
+    if highlights.is_empty() {
+        return;
+    }
+    let mut annotations = match page.get("Annots").map(|a| document.resolve(a)) {
+        Some(PdfObject::Array(annotations)) => annotations.clone(),
+        _ => vec![],
+    };
+
+    for highlight in highlights {
+        // corners of every rectangle as [left, bottom, right, top] in page coordinates
+        let rectangles: Vec<[f64; 4]> = highlight
+            .rectangles
+            .iter()
+            .map(|r| {
+                let (x1, y1) = transform(geometry.matrix, r.x, r.y);
+                let (x2, y2) = transform(geometry.matrix, r.x + r.w, r.y + r.h);
+                [x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)]
+            })
+            .collect();
+        if rectangles.is_empty() {
+            continue;
+        }
+        let bounds = rectangles.iter().fold(
+            [f64::MAX, f64::MAX, f64::MIN, f64::MIN],
+            |[l, b, r, t], rectangle| {
+                [
+                    l.min(rectangle[0]),
+                    b.min(rectangle[1]),
+                    r.max(rectangle[2]),
+                    t.max(rectangle[3]),
+                ]
+            },
+        );
+
+        // upper left, upper right, lower left and lower right corner of every rectangle
+        let quad_points = rectangles
+            .iter()
+            .flat_map(|[l, b, r, t]| [*l, *t, *r, *t, *l, *b, *r, *b])
+            .map(PdfObject::Real)
+            .collect();
+        let [r, g, b, _] = highlight.color.to_rgba();
+        let color = [r, g, b].map(|c| c as f64 / 255.0);
+
+        let mut appearance_content = format!(
+            "/RmMultiply gs {} {} {} rg\n",
+            format_real(color[0]),
+            format_real(color[1]),
+            format_real(color[2])
+        );
+        for [l, b, r, t] in rectangles.iter() {
+            appearance_content.push_str(&format!(
+                "{} {} {} {} re f\n",
+                format_real(*l),
+                format_real(*b),
+                format_real(r - l),
+                format_real(t - b)
+            ));
+        }
+        let mut multiply = Dictionary::default();
+        multiply.set("Type", PdfObject::name("ExtGState"));
+        multiply.set("BM", PdfObject::name("Multiply"));
+        let mut states = Dictionary::default();
+        states.set("RmMultiply", PdfObject::Dictionary(multiply));
+        let mut resources = Dictionary::default();
+        resources.set("ExtGState", PdfObject::Dictionary(states));
+        let mut appearance = Dictionary::default();
+        appearance.set("Type", PdfObject::name("XObject"));
+        appearance.set("Subtype", PdfObject::name("Form"));
+        appearance.set("BBox", box_object(bounds));
+        appearance.set("Resources", PdfObject::Dictionary(resources));
+        let appearance = document.add(PdfObject::Stream(
+            appearance,
+            appearance_content.into_bytes(),
+        ));
+        let mut appearances = Dictionary::default();
+        appearances.set("N", appearance);
+
+        let mut annotation = Dictionary::default();
+        annotation.set("Type", PdfObject::name("Annot"));
+        annotation.set("Subtype", PdfObject::name("Highlight"));
+        annotation.set("Rect", box_object(bounds));
+        annotation.set("QuadPoints", PdfObject::Array(quad_points));
+        annotation.set(
+            "C",
+            PdfObject::Array(color.iter().map(|c| PdfObject::Real(*c)).collect()),
+        );
+        annotation.set("Contents", PdfObject::String(text_string(&highlight.text)));
+        // printable
+        annotation.set("F", PdfObject::Integer(4));
+        annotation.set("P", page_reference.clone());
+        annotation.set("AP", PdfObject::Dictionary(appearances));
+        annotations.push(document.add(PdfObject::Dictionary(annotation)));
+    }
+
+    page.set("Annots", PdfObject::Array(annotations));
+}
+
+/// Encode `text` as pdf text string, UTF-16 is used for anything that isn't ASCII
+fn text_string(text: &str) -> Vec<u8> {
+    if text.is_ascii() {
+        return text.as_bytes().to_vec();
+    }
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(text.encode_utf16().flat_map(|c| c.to_be_bytes()));
+    bytes
+}
+
+/// Add `template` as form XObject covering the tablet's screen
+fn add_template(document: &mut PdfDocument, template: &Template) -> PdfObject {
+    let mut resources = Dictionary::default();
+    let mut content = String::new();
+    match template {
+        Template::Image(image) => {
+            let (color, alpha) = image.split_alpha();
+            let mut dictionary = Dictionary::default();
+            dictionary.set("Type", PdfObject::name("XObject"));
+            dictionary.set("Subtype", PdfObject::name("Image"));
+            dictionary.set("Width", PdfObject::Integer(image.width as i64));
+            dictionary.set("Height", PdfObject::Integer(image.height as i64));
+            dictionary.set(
+                "ColorSpace",
+                PdfObject::name(match image.channels {
+                    1 | 2 => "DeviceGray",
+                    _ => "DeviceRGB",
+                }),
+            );
+            dictionary.set("BitsPerComponent", PdfObject::Integer(8));
+            dictionary.set("Filter", PdfObject::name("FlateDecode"));
+            if let Some(alpha) = alpha {
+                let mut mask = dictionary.clone();
+                mask.set("ColorSpace", PdfObject::name("DeviceGray"));
+                let mask = document.add(PdfObject::Stream(mask, zlib_compress(&alpha)));
+                dictionary.set("SMask", mask);
+            }
+            let image = document.add(PdfObject::Stream(dictionary, zlib_compress(&color)));
+            let mut objects = Dictionary::default();
+            objects.set("RmImage", image);
+            resources.set("XObject", PdfObject::Dictionary(objects));
+            // images are drawn into the unit square with their first row at the top
+            write_matrix(
+                &mut content,
+                [
+                    PAGE_WIDTH as f64,
+                    0.0,
+                    0.0,
+                    -PAGE_HEIGHT as f64,
+                    0.0,
+                    PAGE_HEIGHT as f64,
+                ],
+            );
+            content.push_str("/RmImage Do\n");
+        }
+        Template::Vector(paths) => {
+            let real = |value: f32| format_real(value as f64);
+            for path in paths {
+                if let Some([r, g, b]) = path.stroke {
+                    content.push_str(&format!(
+                        "{} {} {} RG {} w\n",
+                        format_real(r as f64 / 255.0),
+                        format_real(g as f64 / 255.0),
+                        format_real(b as f64 / 255.0),
+                        real(path.stroke_width)
+                    ));
+                }
+                if let Some([r, g, b]) = path.fill {
+                    content.push_str(&format!(
+                        "{} {} {} rg\n",
+                        format_real(r as f64 / 255.0),
+                        format_real(g as f64 / 255.0),
+                        format_real(b as f64 / 255.0),
+                    ));
+                }
+                for segment in path.segments.iter() {
+                    match *segment {
+                        PathSegment::MoveTo(x, y) => {
+                            content.push_str(&format!("{} {} m\n", real(x), real(y)))
+                        }
+                        PathSegment::LineTo(x, y) => {
+                            content.push_str(&format!("{} {} l\n", real(x), real(y)))
+                        }
+                        PathSegment::CurveTo(x1, y1, x2, y2, x, y) => content.push_str(&format!(
+                            "{} {} {} {} {} {} c\n",
+                            real(x1),
+                            real(y1),
+                            real(x2),
+                            real(y2),
+                            real(x),
+                            real(y)
+                        )),
+                        PathSegment::Close => content.push_str("h\n"),
+                    }
+                }
+                content.push_str(match (path.stroke, path.fill) {
+                    (Some(_), Some(_)) => "B\n",
+                    (Some(_), None) => "S\n",
+                    _ => "f\n",
+                });
+            }
+        }
+    }
+
+    let mut form = Dictionary::default();
+    form.set("Type", PdfObject::name("XObject"));
+    form.set("Subtype", PdfObject::name("Form"));
+    form.set(
+        "BBox",
+        box_object([0.0, 0.0, PAGE_WIDTH as f64, PAGE_HEIGHT as f64]),
+    );
+    form.set("Resources", PdfObject::Dictionary(resources));
+    form.set("Filter", PdfObject::name("FlateDecode"));
+    document.add(PdfObject::Stream(form, zlib_compress(content.as_bytes())))
+}
+
+/// Append a content stream with `drawing` to `page`, drawn on top of `template` if there is one
+fn add_drawing(
+    document: &mut PdfDocument,
+    page: &mut Dictionary,
+    geometry: &PageGeometry,
+    drawing: &PageDrawing,
+    template: Option<&PdfObject>,
+    save_state: &PdfObject,
+) {
+    let mut alphas = BTreeMap::new();
+    let mut content = String::from("Q\nq\n");
+    write_matrix(&mut content, geometry.matrix);
+    if template.is_some() {
+        content.push_str("q\n");
+        write_matrix(&mut content, geometry.screen_matrix);
+        content.push_str("/RmTemplate Do\nQ\n");
+    }
+
+    // highlight rectangles are in the coordinates of the shown page
+    for highlight in drawing.highlights.iter() {
+        let [r, g, b, a] = highlight.color.to_rgba();
+        let opacity = a as f32 / 255.0 * 0.4;
+        content.push_str(&format!(
+            "{} {} {} rg /{} gs\n",
+            format_real(r as f64 / 255.0),
+            format_real(g as f64 / 255.0),
+            format_real(b as f64 / 255.0),
+            alpha_name(&mut alphas, opacity),
+        ));
+        for rectangle in highlight.rectangles.iter() {
+            content.push_str(&format!(
+                "{} {} {} {} re f\n",
+                format_real(rectangle.x),
+                format_real(rectangle.y),
+                format_real(rectangle.w),
+                format_real(rectangle.h),
+            ));
+        }
+    }
+
+    write_matrix(&mut content, geometry.screen_matrix);
+    content.push_str("1 J 1 j\n");
+    for stroke in drawing.strokes.iter() {
+        let (first, rest) = match stroke.points.split_first() {
+            Some(points) => points,
+            None => continue,
+        };
+        let [r, g, b, _] = stroke.color.to_rgba();
+        content.push_str(&format!(
+            "{} {} {} RG /{} gs\n",
+            format_real(r as f64 / 255.0),
+            format_real(g as f64 / 255.0),
+            format_real(b as f64 / 255.0),
+            alpha_name(&mut alphas, stroke.opacity()),
+        ));
+
+        // segments with the same width are drawn as one path
+        let point = |p: &super::StrokePoint| {
+            format!("{} {}", format_real(p.x as f64), format_real(p.y as f64))
+        };
+        let mut width = first.width;
+        content.push_str(&format!(
+            "{} w {} m",
+            format_real(width as f64),
+            point(first)
+        ));
+        let mut previous = first;
+        for current in rest {
+            if (current.width - width).abs() > 0.05 {
+                width = current.width;
+                content.push_str(&format!(
+                    " S\n{} w {} m",
+                    format_real(width as f64),
+                    point(previous)
+                ));
+            }
+            content.push_str(&format!(" {} l", point(current)));
+            previous = current;
+        }
+        if rest.is_empty() {
+            // a single point is drawn as a dot
+            content.push_str(&format!(" {} l", point(first)));
+        }
+        content.push_str(" S\n");
+    }
+    content.push_str("Q\n");
+
+    let mut resources = page
+        .get("Resources")
+        .and_then(|r| document.resolve(r).as_dictionary())
+        .cloned()
+        .unwrap_or_default();
+    let mut states = resources
+        .get("ExtGState")
+        .and_then(|s| document.resolve(s).as_dictionary())
+        .cloned()
+        .unwrap_or_default();
+    for (name, opacity) in alphas {
+        let mut state = Dictionary::default();
+        state.set("Type", PdfObject::name("ExtGState"));
+        state.set("CA", PdfObject::Real(opacity as f64 / 100.0));
+        state.set("ca", PdfObject::Real(opacity as f64 / 100.0));
+        states.set(&name, PdfObject::Dictionary(state));
+    }
+    resources.set("ExtGState", PdfObject::Dictionary(states));
+    if let Some(template) = template {
+        let mut objects = resources
+            .get("XObject")
+            .and_then(|o| document.resolve(o).as_dictionary())
+            .cloned()
+            .unwrap_or_default();
+        objects.set("RmTemplate", template.clone());
+        resources.set("XObject", PdfObject::Dictionary(objects));
+    }
+    page.set("Resources", PdfObject::Dictionary(resources));
+
+    let drawing = document.add(PdfObject::Stream(
+        Dictionary::default(),
+        content.into_bytes(),
+    ));
+    // original content is wrapped in q/Q so changes it makes to the graphics state don't
+    // affect the drawing
+    let mut contents = vec![save_state.clone()];
+    match page.get("Contents").map(|c| (c, document.resolve(c))) {
+        Some((_, PdfObject::Array(streams))) => contents.extend(streams.iter().cloned()),
+        Some((reference, PdfObject::Stream(..))) => contents.push(reference.clone()),
+        _ => (),
+    }
+    contents.push(drawing);
+    page.set("Contents", PdfObject::Array(contents));
+}
+
+fn write_matrix(content: &mut String, matrix: [f64; 6]) {
+    for value in matrix {
+        content.push_str(&format_real(value));
+        content.push(' ');
+    }
+    content.push_str("cm\n");
+}
+
+/// Name of the graphics state for `opacity`, opacities are rounded to whole percents
+fn alpha_name(alphas: &mut BTreeMap<String, u32>, opacity: f32) -> String {
+    let percent = (opacity.clamp(0.0, 1.0) * 100.0).round() as u32;
+    let name = format!("RmAlpha{percent}");
+    alphas.insert(name.clone(), percent);
+    name
+}
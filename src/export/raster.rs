@@ -0,0 +1,355 @@
+//! Drawing pages into images
+//!
+//! Strokes are drawn as round capped lines and filled shapes are sampled at four rows per pixel,
+//! which is plenty for previews. Transparent strokes are blended once per stroke so highlighters
+//! don't get darker where the line overlaps itself.
+
+use super::{
+    image::Image,
+    template::{PathSegment, Template},
+    PageDrawing, PAGE_HEIGHT, PAGE_WIDTH,
+};
+
+/// Rows sampled per pixel when filling shapes
+const SUBSAMPLES: usize = 4;
+/// Lines used for every bezier curve
+const CURVE_STEPS: usize = 16;
+
+/// Draw `drawing` on top of `template` into a white image of `width` by `height` pixels
+pub fn render(
+    drawing: &PageDrawing,
+    template: Option<&Template>,
+    width: u32,
+    height: u32,
+) -> Image {
+    let mut canvas = Canvas::new(width, height);
+    let scale = (width as f32 / PAGE_WIDTH, height as f32 / PAGE_HEIGHT);
+    let point = |x: f32, y: f32| (x * scale.0, y * scale.1);
+    let line_scale = (scale.0 * scale.1).sqrt();
+
+    match template {
+        Some(Template::Image(image)) => canvas.draw_image(image),
+        Some(Template::Vector(paths)) => {
+            for path in paths {
+                let polygons: Vec<Vec<(f32, f32)>> = flatten(&path.segments)
+                    .into_iter()
+                    .map(|(polygon, closed)| {
+                        let mut polygon: Vec<(f32, f32)> =
+                            polygon.into_iter().map(|(x, y)| point(x, y)).collect();
+                        if closed {
+                            polygon.extend(polygon.first().copied());
+                        }
+                        polygon
+                    })
+                    .collect();
+                if let Some(color) = path.fill {
+                    canvas.fill(&polygons, color, 1.0);
+                }
+                if let Some(color) = path.stroke {
+                    let width = path.stroke_width * line_scale;
+                    let lines: Vec<Vec<(f32, f32, f32)>> = polygons
+                        .iter()
+                        .map(|p| p.iter().map(|(x, y)| (*x, *y, width)).collect())
+                        .collect();
+                    canvas.stroke(&lines, color, 1.0);
+                }
+            }
+        }
+        None => (),
+    }
+
+    for (color, [x, y, width, height]) in drawing.screen_highlights() {
+        let [r, g, b, a] = color.to_rgba();
+        let (left, top) = point(x, y);
+        let (right, bottom) = point(x + width, y + height);
+        let polygon = vec![
+            (left, top),
+            (right, top),
+            (right, bottom),
+            (left, bottom),
+            (left, top),
+        ];
+        canvas.fill(&[polygon], [r, g, b], a as f32 / 255.0 * 0.4);
+    }
+
+    for stroke in drawing.strokes.iter() {
+        let [r, g, b, _] = stroke.color.to_rgba();
+        let line: Vec<(f32, f32, f32)> = stroke
+            .points
+            .iter()
+            .map(|p| {
+                let (x, y) = point(p.x, p.y);
+                (x, y, p.width * line_scale)
+            })
+            .collect();
+        canvas.stroke(&[line], [r, g, b], stroke.opacity());
+    }
+
+    canvas.image
+}
+
+/// Split a path into its sub paths made out of straight lines and whether they are closed
+fn flatten(segments: &[PathSegment]) -> Vec<(Vec<(f32, f32)>, bool)> {
+    let mut paths: Vec<(Vec<(f32, f32)>, bool)> = vec![];
+    let mut current = (0.0, 0.0);
+    for segment in segments {
+        match *segment {
+            PathSegment::MoveTo(x, y) => {
+                paths.push((vec![(x, y)], false));
+                current = (x, y);
+            }
+            PathSegment::LineTo(x, y) => {
+                if paths.is_empty() {
+                    paths.push((vec![current], false));
+                }
+                if let Some((path, _)) = paths.last_mut() {
+                    path.push((x, y));
+                }
+                current = (x, y);
+            }
+            PathSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                if paths.is_empty() {
+                    paths.push((vec![current], false));
+                }
+                let (x0, y0) = current;
+                if let Some((path, _)) = paths.last_mut() {
+                    for step in 1..=CURVE_STEPS {
+                        let t = step as f32 / CURVE_STEPS as f32;
+                        let u = 1.0 - t;
+                        let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                        path.push((
+                            a * x0 + b * x1 + c * x2 + d * x,
+                            a * y0 + b * y1 + c * y2 + d * y,
+                        ));
+                    }
+                }
+                current = (x, y);
+            }
+            PathSegment::Close => {
+                if let Some((path, closed)) = paths.last_mut() {
+                    *closed = true;
+                    current = path[0];
+                }
+            }
+        }
+    }
+    paths
+}
+
+struct Canvas {
+    image: Image,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Canvas {
+        Canvas {
+            image: Image {
+                width,
+                height,
+                channels: 3,
+                data: vec![255; width as usize * height as usize * 3],
+            },
+        }
+    }
+
+    fn blend(&mut self, x: usize, y: usize, color: [u8; 3], alpha: f32) {
+        let start = (y * self.image.width as usize + x) * 3;
+        for (channel, value) in self.image.data[start..start + 3].iter_mut().zip(color) {
+            *channel = (*channel as f32 * (1.0 - alpha) + value as f32 * alpha).round() as u8;
+        }
+    }
+
+    /// Blend `color` by the coverage in `mask`, which covers the pixels of `bounds`
+    fn apply_mask(&mut self, mask: &[f32], bounds: Bounds, color: [u8; 3], opacity: f32) {
+        let width = bounds.right - bounds.left;
+        for (i, coverage) in mask.iter().enumerate() {
+            if *coverage > 0.0 {
+                let alpha = coverage.min(1.0) * opacity;
+                self.blend(
+                    bounds.left + i % width,
+                    bounds.top + i / width,
+                    color,
+                    alpha,
+                );
+            }
+        }
+    }
+
+    /// Pixels covered by the points grown by `margin`, `None` if that's outside of the image
+    fn bounds(&self, points: impl Iterator<Item = (f32, f32)>, margin: f32) -> Option<Bounds> {
+        let (mut left, mut top, mut right, mut bottom) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for (x, y) in points {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+        let clamp = |value: f32, max: u32| value.max(0.0).min(max as f32) as usize;
+        let bounds = Bounds {
+            left: clamp((left - margin).floor(), self.image.width),
+            top: clamp((top - margin).floor(), self.image.height),
+            right: clamp((right + margin).ceil(), self.image.width),
+            bottom: clamp((bottom + margin).ceil(), self.image.height),
+        };
+        if bounds.left >= bounds.right || bounds.top >= bounds.bottom {
+            return None;
+        }
+        Some(bounds)
+    }
+
+    /// Draw connected lines with a width at every point
+    fn stroke(&mut self, lines: &[Vec<(f32, f32, f32)>], color: [u8; 3], opacity: f32) {
+        let points = lines.iter().flatten();
+        let margin = points.clone().map(|p| p.2).fold(0.0f32, f32::max) / 2.0 + 1.0;
+        let bounds = match self.bounds(points.map(|p| (p.0, p.1)), margin) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let width = bounds.right - bounds.left;
+        let mut mask = vec![0.0f32; width * (bounds.bottom - bounds.top)];
+
+        for line in lines {
+            // a single point is drawn as a dot
+            let segments: Vec<_> = match line[..] {
+                [point] => vec![(point, point)],
+                _ => line.windows(2).map(|w| (w[0], w[1])).collect(),
+            };
+            for (start, end) in segments {
+                // lines thinner than a pixel are drawn lighter instead
+                let half_width = ((start.2 + end.2) / 4.0).max(0.5);
+                let segment_bounds = Bounds {
+                    left: ((start.0.min(end.0) - half_width - 1.0).floor().max(0.0) as usize)
+                        .max(bounds.left),
+                    top: ((start.1.min(end.1) - half_width - 1.0).floor().max(0.0) as usize)
+                        .max(bounds.top),
+                    right: ((start.0.max(end.0) + half_width + 1.0).ceil().max(0.0) as usize)
+                        .min(bounds.right),
+                    bottom: ((start.1.max(end.1) + half_width + 1.0).ceil().max(0.0) as usize)
+                        .min(bounds.bottom),
+                };
+                let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+                let length_squared = dx * dx + dy * dy;
+                for y in segment_bounds.top..segment_bounds.bottom {
+                    for x in segment_bounds.left..segment_bounds.right {
+                        let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                        let t = if length_squared > 0.0 {
+                            (((px - start.0) * dx + (py - start.1) * dy) / length_squared)
+                                .clamp(0.0, 1.0)
+                        } else {
+                            0.0
+                        };
+                        let distance = ((px - start.0 - t * dx).powi(2)
+                            + (py - start.1 - t * dy).powi(2))
+                        .sqrt();
+                        let coverage = (half_width + 0.5 - distance).clamp(0.0, 1.0);
+                        let index = (y - bounds.top) * width + x - bounds.left;
+                        mask[index] = mask[index].max(coverage);
+                    }
+                }
+            }
+        }
+        self.apply_mask(&mask, bounds, color, opacity);
+    }
+
+    /// Fill the inside of closed polygons using the non-zero winding rule
+    fn fill(&mut self, polygons: &[Vec<(f32, f32)>], color: [u8; 3], opacity: f32) {
+        let bounds = match self.bounds(polygons.iter().flatten().copied(), 1.0) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let width = bounds.right - bounds.left;
+        let mut mask = vec![0.0f32; width * (bounds.bottom - bounds.top)];
+
+        let edges: Vec<((f32, f32), (f32, f32))> = polygons
+            .iter()
+            .flat_map(|polygon| {
+                let closing = match (polygon.first(), polygon.last()) {
+                    (Some(first), Some(last)) if first != last => Some((*last, *first)),
+                    _ => None,
+                };
+                polygon.windows(2).map(|w| (w[0], w[1])).chain(closing)
+            })
+            .filter(|(a, b)| a.1 != b.1)
+            .collect();
+
+        let mut crossings = vec![];
+        for y in bounds.top..bounds.bottom {
+            for sample in 0..SUBSAMPLES {
+                let sample_y = y as f32 + (sample as f32 + 0.5) / SUBSAMPLES as f32;
+                crossings.clear();
+                for (a, b) in edges.iter() {
+                    if (a.1 <= sample_y) != (b.1 <= sample_y) {
+                        let x = a.0 + (sample_y - a.1) / (b.1 - a.1) * (b.0 - a.0);
+                        crossings.push((x, if b.1 > a.1 { 1 } else { -1 }));
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let mut winding = 0;
+                for pair in crossings.windows(2) {
+                    winding += pair[0].1;
+                    if winding == 0 {
+                        continue;
+                    }
+                    let start = pair[0].0.max(bounds.left as f32);
+                    let end = pair[1].0.min(bounds.right as f32);
+                    let row = (y - bounds.top) * width;
+                    // add the covered part of every pixel in the span
+                    let mut x = start;
+                    while x < end {
+                        let pixel = x.floor();
+                        let next = (pixel + 1.0).min(end);
+                        let index = row + pixel as usize - bounds.left;
+                        mask[index] += (next - x) / SUBSAMPLES as f32;
+                        x = next;
+                    }
+                }
+            }
+        }
+        self.apply_mask(&mask, bounds, color, opacity);
+    }
+
+    /// Draw `image` stretched over the whole canvas, averaging the pixels under each pixel
+    fn draw_image(&mut self, image: &Image) {
+        let (width, height) = (self.image.width as usize, self.image.height as usize);
+        let scale_x = image.width as f32 / width as f32;
+        let scale_y = image.height as f32 / height as f32;
+        for y in 0..height {
+            let top = (y as f32 * scale_y) as u32;
+            let bottom = (((y + 1) as f32 * scale_y).ceil() as u32)
+                .max(top + 1)
+                .min(image.height);
+            for x in 0..width {
+                let left = (x as f32 * scale_x) as u32;
+                let right = (((x + 1) as f32 * scale_x).ceil() as u32)
+                    .max(left + 1)
+                    .min(image.width);
+                let mut sum = [0.0f32; 4];
+                let mut count = 0.0;
+                for source_y in top..bottom {
+                    for source_x in left..right {
+                        let [r, g, b, a] = image.pixel(source_x, source_y).unwrap_or_default();
+                        let alpha = a as f32 / 255.0;
+                        sum[0] += r as f32 * alpha;
+                        sum[1] += g as f32 * alpha;
+                        sum[2] += b as f32 * alpha;
+                        sum[3] += alpha;
+                        count += 1.0;
+                    }
+                }
+                if sum[3] > 0.0 {
+                    let color = [sum[0], sum[1], sum[2]].map(|c| (c / sum[3]).round() as u8);
+                    self.blend(x, y, color, sum[3] / count);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
@@ -0,0 +1,750 @@
+//! Backgrounds drawn under the strokes of a page
+//!
+//! The tablet keeps its templates in `/usr/share/remarkable/templates` as `<name>.png` and
+//! `<name>.svg`. Which one a page uses is listed in the document's `.pagedata`, newer documents
+//! also store it in `.content`, see [Notebook::page_templates]. [Templates] loads them from a
+//! local copy of that directory.
+//!
+//! SVG files are read as far as the tablet's templates need it: shapes and paths with solid
+//! strokes and fills, groups and transforms. Gradients, text and clipping are ignored and arcs
+//! are drawn as straight lines.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{notebook::Notebook, ParseError, ParseErrorKind};
+
+use super::{image::Image, PAGE_HEIGHT, PAGE_WIDTH};
+
+/// Name the tablet uses for pages without a template
+pub const BLANK_TEMPLATE: &str = "Blank";
+
+/// Template as loaded from a `.png` or `.svg` file, stretched to cover the tablet's screen
+#[derive(Debug, Clone)]
+pub enum Template {
+    Image(Image),
+    /// Paths in the coordinates of the tablet's screen, see [super::PageDrawing]
+    Vector(Vec<TemplatePath>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    /// Cubic bezier curve with two control points and the end point
+    CurveTo(f32, f32, f32, f32, f32, f32),
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplatePath {
+    pub segments: Vec<PathSegment>,
+    /// Color of the outline, `None` if it isn't stroked
+    pub stroke: Option<[u8; 3]>,
+    /// Width of the outline in pixels
+    pub stroke_width: f32,
+    /// Color of the inside, `None` if it isn't filled
+    pub fill: Option<[u8; 3]>,
+}
+
+impl Template {
+    pub fn from_png(png: &[u8]) -> Result<Template, ParseError> {
+        Ok(Template::Image(Image::from_png(png)?))
+    }
+
+    pub fn from_svg(svg: &str) -> Result<Template, ParseError> {
+        let mut paths = vec![];
+        let mut styles = vec![Style::default()];
+        let mut root = true;
+
+        for tag in tags(svg)? {
+            let (name, attributes, self_closing) = match tag {
+                Tag::Close => {
+                    if styles.len() > 1 {
+                        styles.pop();
+                    }
+                    continue;
+                }
+                Tag::Open(name, attributes, self_closing) => (name, attributes, self_closing),
+            };
+
+            let mut style = styles.last().cloned().unwrap_or_default();
+            if root && name == "svg" {
+                style.matrix = view_box_matrix(&attributes);
+                root = false;
+            }
+            style.apply(&attributes)?;
+            // elements inside of definitions are only drawn when referenced
+            if matches!(
+                name,
+                "defs" | "symbol" | "clipPath" | "mask" | "pattern" | "marker"
+            ) {
+                style.hidden = true;
+            }
+
+            if !style.hidden {
+                let segments = shape(name, &attributes)?;
+                if !segments.is_empty() && (style.stroke.is_some() || style.fill.is_some()) {
+                    paths.push(style.path(segments));
+                }
+            }
+            if !self_closing {
+                styles.push(style);
+            }
+        }
+
+        Ok(Template::Vector(paths))
+    }
+}
+
+/// Templates from a local copy of the tablet's template directory
+#[derive(Debug, Clone)]
+pub struct Templates {
+    pub directory: PathBuf,
+    /// Template used for every page instead of the one chosen on the tablet
+    pub override_name: Option<String>,
+}
+
+impl Templates {
+    pub fn new(directory: impl AsRef<Path>) -> Templates {
+        Templates {
+            directory: directory.as_ref().to_owned(),
+            override_name: None,
+        }
+    }
+
+    /// Name of the template shown under page `index` of `notebook`
+    pub fn name_for_page(&self, notebook: &Notebook, index: usize) -> Option<String> {
+        match &self.override_name {
+            Some(name) => Some(name.clone()),
+            None => notebook.page_templates().into_iter().nth(index).flatten(),
+        }
+    }
+
+    /// Load template `name`, `None` for [BLANK_TEMPLATE] and templates that aren't in the
+    /// directory
+    ///
+    /// The `.svg` file is used if there is one as it stays sharp when zoomed in.
+    pub fn load(&self, name: &str) -> Result<Option<Template>, ParseError> {
+        // names come from the document and must not point outside of the directory
+        if name.is_empty() || name == BLANK_TEMPLATE || name.contains(['/', '\\']) || name == ".." {
+            return Ok(None);
+        }
+        if let Some(svg) = read_template(&self.directory.join(format!("{name}.svg")))? {
+            return Template::from_svg(&String::from_utf8(svg)?).map(Some);
+        }
+        if let Some(png) = read_template(&self.directory.join(format!("{name}.png")))? {
+            return Template::from_png(&png).map(Some);
+        }
+        Ok(None)
+    }
+}
+
+fn read_template(path: &Path) -> Result<Option<Vec<u8>>, ParseError> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(ParseError::new(
+            format!("Failed to read '{}': {e}", path.display()),
+            ParseErrorKind::Io,
+        )),
+    }
+}
+
+enum Tag<'a> {
+    /// Name, attributes and whether it closes itself
+    Open(&'a str, HashMap<&'a str, String>, bool),
+    Close,
+}
+
+/// Split an xml document into its tags, text is skipped
+fn tags(xml: &str) -> Result<Vec<Tag<'_>>, ParseError> {
+    let mut tags = vec![];
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let skip_to = |rest: &str, end: &str| -> Result<usize, ParseError> {
+            rest.find(end)
+                .map(|i| i + end.len())
+                .ok_or_else(|| ParseError::invalid("Svg ended inside of a tag"))
+        };
+        if rest.starts_with("<!--") {
+            rest = &rest[skip_to(rest, "-->")?..];
+            continue;
+        }
+        if rest.starts_with("<![CDATA[") {
+            rest = &rest[skip_to(rest, "]]>")?..];
+            continue;
+        }
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = &rest[skip_to(rest, ">")?..];
+            continue;
+        }
+        if rest.starts_with("</") {
+            rest = &rest[skip_to(rest, ">")?..];
+            tags.push(Tag::Close);
+            continue;
+        }
+
+        let end = tag_end(rest).ok_or_else(|| ParseError::invalid("Svg ended inside of a tag"))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        let (tag, self_closing) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let name_end = tag.find(|c: char| c.is_whitespace()).unwrap_or(tag.len());
+        // namespaces are ignored, `svg:path` is a path
+        let name = tag[..name_end].rsplit(':').next().unwrap_or_default();
+        tags.push(Tag::Open(name, attributes(&tag[name_end..]), self_closing));
+    }
+    Ok(tags)
+}
+
+/// Index of the `>` closing the tag at the start of `xml`, skipping quoted values
+fn tag_end(xml: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in xml.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+
+fn attributes(mut input: &str) -> HashMap<&str, String> {
+    let mut attributes = HashMap::new();
+    while let Some(equals) = input.find('=') {
+        let name = input[..equals].trim();
+        let value = input[equals + 1..].trim_start();
+        let quote = match value.chars().next() {
+            Some(q @ ('"' | '\'')) => q,
+            _ => break,
+        };
+        let end = match value[1..].find(quote) {
+            Some(end) => end + 1,
+            None => break,
+        };
+        let name = name.rsplit(':').next().unwrap_or_default();
+        attributes.insert(name, unescape(&value[1..end]));
+        input = &value[end + 1..];
+    }
+    attributes
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Style inherited from the parent elements
+#[derive(Debug, Clone)]
+struct Style {
+    /// Transformation to the tablet's screen
+    matrix: [f32; 6],
+    stroke: Option<[u8; 3]>,
+    stroke_width: f32,
+    fill: Option<[u8; 3]>,
+    /// Set by `display: none` and inside of definitions
+    hidden: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            matrix: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+            stroke: None,
+            stroke_width: 1.0,
+            fill: Some([0, 0, 0]),
+            hidden: false,
+        }
+    }
+}
+
+impl Style {
+    fn apply(&mut self, attributes: &HashMap<&str, String>) -> Result<(), ParseError> {
+        if let Some(transform) = attributes.get("transform") {
+            self.matrix = multiply(self.matrix, parse_transform(transform)?);
+        }
+        let mut properties: Vec<(&str, &str)> = attributes
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        // style takes precedence over presentation attributes
+        if let Some(style) = attributes.get("style") {
+            properties.extend(style.split(';').filter_map(|declaration| {
+                let (name, value) = declaration.split_once(':')?;
+                Some((name.trim(), value.trim()))
+            }));
+        }
+        for (name, value) in properties {
+            match name {
+                "stroke" => self.stroke = parse_color(value),
+                "fill" => self.fill = parse_color(value),
+                "stroke-width" => {
+                    self.stroke_width = parse_numbers(value)?.first().copied().unwrap_or(1.0)
+                }
+                "display" if value == "none" => self.hidden = true,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, segments: Vec<PathSegment>) -> TemplatePath {
+        let [a, b, c, d, e, f] = self.matrix;
+        let point = |x: f32, y: f32| (a * x + c * y + e, b * x + d * y + f);
+        let segments = segments
+            .into_iter()
+            .map(|segment| match segment {
+                PathSegment::MoveTo(x, y) => {
+                    let (x, y) = point(x, y);
+                    PathSegment::MoveTo(x, y)
+                }
+                PathSegment::LineTo(x, y) => {
+                    let (x, y) = point(x, y);
+                    PathSegment::LineTo(x, y)
+                }
+                PathSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                    let (x1, y1) = point(x1, y1);
+                    let (x2, y2) = point(x2, y2);
+                    let (x, y) = point(x, y);
+                    PathSegment::CurveTo(x1, y1, x2, y2, x, y)
+                }
+                PathSegment::Close => PathSegment::Close,
+            })
+            .collect();
+        TemplatePath {
+            segments,
+            stroke: self.stroke,
+            // widths scale with the average of both axes
+            stroke_width: self.stroke_width * (a * d - b * c).abs().sqrt(),
+            fill: self.fill,
+        }
+    }
+}
+
+/// Transformation stretching the svg's view box over the tablet's screen
+fn view_box_matrix(attributes: &HashMap<&str, String>) -> [f32; 6] {
+    let length = |name: &str| {
+        attributes
+            .get(name)
+            .and_then(|v| v.trim().trim_end_matches("px").parse::<f32>().ok())
+    };
+    let view_box = attributes
+        .get("viewBox")
+        .and_then(|v| parse_numbers(v).ok())
+        .filter(|v| v.len() == 4 && v[2] > 0.0 && v[3] > 0.0);
+    let [x, y, width, height] = match (view_box, length("width"), length("height")) {
+        (Some(v), _, _) => [v[0], v[1], v[2], v[3]],
+        (None, Some(width), Some(height)) if width > 0.0 && height > 0.0 => {
+            [0.0, 0.0, width, height]
+        }
+        _ => [0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT],
+    };
+    let (scale_x, scale_y) = (PAGE_WIDTH / width, PAGE_HEIGHT / height);
+    [scale_x, 0.0, 0.0, scale_y, -x * scale_x, -y * scale_y]
+}
+
+/// `first` applied after `second`
+fn multiply(first: [f32; 6], second: [f32; 6]) -> [f32; 6] {
+    let [a1, b1, c1, d1, e1, f1] = first;
+    let [a2, b2, c2, d2, e2, f2] = second;
+    [
+        a1 * a2 + c1 * b2,
+        b1 * a2 + d1 * b2,
+        a1 * c2 + c1 * d2,
+        b1 * c2 + d1 * d2,
+        a1 * e2 + c1 * f2 + e1,
+        b1 * e2 + d1 * f2 + f1,
+    ]
+}
+
+fn parse_transform(transform: &str) -> Result<[f32; 6], ParseError> {
+    let mut matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+    let mut rest = transform;
+    while let Some(open) = rest.find('(') {
+        let name = rest[..open].trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let close = rest[open..]
+            .find(')')
+            .map(|i| i + open)
+            .ok_or_else(|| ParseError::invalid("Unclosed svg transform"))?;
+        let values = parse_numbers(&rest[open + 1..close])?;
+        let value = |i: usize| values.get(i).copied().unwrap_or(0.0);
+        let next = match name {
+            "matrix" if values.len() == 6 => {
+                [value(0), value(1), value(2), value(3), value(4), value(5)]
+            }
+            "translate" => [1.0, 0.0, 0.0, 1.0, value(0), value(1)],
+            "scale" => {
+                let y = values.get(1).copied().unwrap_or(value(0));
+                [value(0), 0.0, 0.0, y, 0.0, 0.0]
+            }
+            "rotate" => {
+                let (sin, cos) = value(0).to_radians().sin_cos();
+                let (x, y) = (value(1), value(2));
+                multiply(
+                    [1.0, 0.0, 0.0, 1.0, x, y],
+                    multiply(
+                        [cos, sin, -sin, cos, 0.0, 0.0],
+                        [1.0, 0.0, 0.0, 1.0, -x, -y],
+                    ),
+                )
+            }
+            "skewX" => [1.0, 0.0, value(0).to_radians().tan(), 1.0, 0.0, 0.0],
+            "skewY" => [1.0, value(0).to_radians().tan(), 0.0, 1.0, 0.0, 0.0],
+            _ => {
+                return Err(ParseError::invalid(format!(
+                    "Invalid svg transform '{name}'"
+                )))
+            }
+        };
+        matrix = multiply(matrix, next);
+        rest = &rest[close + 1..];
+    }
+    Ok(matrix)
+}
+
+/// Colors are given as `#rgb`, `#rrggbb`, `rgb(r, g, b)` or one of the basic names, `None`
+/// means nothing is drawn
+fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<u8>>>()?;
+        return match digits[..] {
+            [r, g, b] => Some([r * 17, g * 17, b * 17]),
+            [r1, r2, g1, g2, b1, b2] => Some([r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2]),
+            _ => None,
+        };
+    }
+    if let Some(channels) = value.strip_prefix("rgb(").and_then(|v| v.strip_suffix(')')) {
+        let channels: Vec<u8> = channels
+            .split(',')
+            .map(|c| {
+                let c = c.trim();
+                match c.strip_suffix('%') {
+                    Some(percent) => percent.parse::<f32>().ok().map(|p| p * 2.55),
+                    None => c.parse::<f32>().ok(),
+                }
+                .map(|c| c.clamp(0.0, 255.0).round() as u8)
+            })
+            .collect::<Option<Vec<u8>>>()?;
+        return match channels[..] {
+            [r, g, b] => Some([r, g, b]),
+            _ => None,
+        };
+    }
+    match value.to_ascii_lowercase().as_str() {
+        "black" => Some([0, 0, 0]),
+        "white" => Some([255, 255, 255]),
+        "gray" | "grey" => Some([128, 128, 128]),
+        "lightgray" | "lightgrey" => Some([211, 211, 211]),
+        "darkgray" | "darkgrey" => Some([169, 169, 169]),
+        "silver" => Some([192, 192, 192]),
+        "red" => Some([255, 0, 0]),
+        "green" => Some([0, 128, 0]),
+        "blue" => Some([0, 0, 255]),
+        "yellow" => Some([255, 255, 0]),
+        _ => None,
+    }
+}
+
+/// All numbers in `input` separated by whitespace, commas or signs
+fn parse_numbers(input: &str) -> Result<Vec<f32>, ParseError> {
+    let mut numbers = vec![];
+    let mut scanner = NumberScanner { input, position: 0 };
+    while let Some(number) = scanner.next_number()? {
+        numbers.push(number);
+    }
+    Ok(numbers)
+}
+
+struct NumberScanner<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> NumberScanner<'a> {
+    fn skip_separators(&mut self) {
+        let rest = &self.input[self.position..];
+        let trimmed = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        self.position += rest.len() - trimmed.len();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn next_number(&mut self) -> Result<Option<f32>, ParseError> {
+        self.skip_separators();
+        let bytes = self.input.as_bytes();
+        let start = self.position;
+        let mut end = start;
+        if matches!(bytes.get(end), Some(b'-' | b'+')) {
+            end += 1;
+        }
+        let mut seen_dot = false;
+        let mut seen_digit = false;
+        while let Some(c) = bytes.get(end) {
+            match c {
+                b'0'..=b'9' => seen_digit = true,
+                // `1.5.5` is two numbers
+                b'.' if !seen_dot => seen_dot = true,
+                _ => break,
+            }
+            end += 1;
+        }
+        if !seen_digit {
+            return match self.peek() {
+                None => Ok(None),
+                Some(_) if end == start => Ok(None),
+                Some(c) => Err(ParseError::invalid(format!(
+                    "Invalid number in svg at '{c}'"
+                ))),
+            };
+        }
+        if matches!(bytes.get(end), Some(b'e' | b'E')) {
+            let mut exponent_end = end + 1;
+            if matches!(bytes.get(exponent_end), Some(b'-' | b'+')) {
+                exponent_end += 1;
+            }
+            if matches!(bytes.get(exponent_end), Some(b'0'..=b'9')) {
+                while matches!(bytes.get(exponent_end), Some(b'0'..=b'9')) {
+                    exponent_end += 1;
+                }
+                end = exponent_end;
+            }
+        }
+        self.position = end;
+        self.input[start..end]
+            .parse::<f32>()
+            .map(Some)
+            .map_err(|_| ParseError::invalid("Invalid number in svg"))
+    }
+}
+
+/// Outline of a basic shape or path element, empty for all other elements
+fn shape(name: &str, attributes: &HashMap<&str, String>) -> Result<Vec<PathSegment>, ParseError> {
+    let number = |name: &str| -> Result<f32, ParseError> {
+        match attributes.get(name) {
+            Some(value) => Ok(parse_numbers(value.trim_end_matches("px"))?
+                .first()
+                .copied()
+                .unwrap_or(0.0)),
+            None => Ok(0.0),
+        }
+    };
+    let segments = match name {
+        "path" => match attributes.get("d") {
+            Some(d) => parse_path(d)?,
+            None => vec![],
+        },
+        "line" => vec![
+            PathSegment::MoveTo(number("x1")?, number("y1")?),
+            PathSegment::LineTo(number("x2")?, number("y2")?),
+        ],
+        "rect" => {
+            let (x, y) = (number("x")?, number("y")?);
+            let (width, height) = (number("width")?, number("height")?);
+            if width <= 0.0 || height <= 0.0 {
+                return Ok(vec![]);
+            }
+            vec![
+                PathSegment::MoveTo(x, y),
+                PathSegment::LineTo(x + width, y),
+                PathSegment::LineTo(x + width, y + height),
+                PathSegment::LineTo(x, y + height),
+                PathSegment::Close,
+            ]
+        }
+        "polyline" | "polygon" => {
+            let points = parse_numbers(attributes.get("points").map_or("", |p| p.as_str()))?;
+            let mut segments: Vec<PathSegment> = points
+                .chunks_exact(2)
+                .enumerate()
+                .map(|(i, p)| match i {
+                    0 => PathSegment::MoveTo(p[0], p[1]),
+                    _ => PathSegment::LineTo(p[0], p[1]),
+                })
+                .collect();
+            if name == "polygon" && !segments.is_empty() {
+                segments.push(PathSegment::Close);
+            }
+            segments
+        }
+        "circle" => {
+            let r = number("r")?;
+            ellipse(number("cx")?, number("cy")?, r, r)
+        }
+        "ellipse" => ellipse(number("cx")?, number("cy")?, number("rx")?, number("ry")?),
+        _ => vec![],
+    };
+    Ok(segments)
+}
+
+/// Ellipse made out of four bezier curves
+fn ellipse(cx: f32, cy: f32, rx: f32, ry: f32) -> Vec<PathSegment> {
+    if rx <= 0.0 || ry <= 0.0 {
+        return vec![];
+    }
+    // distance of the control points for a quarter circle
+    let k = 0.552_284_8;
+    let (kx, ky) = (rx * k, ry * k);
+    vec![
+        PathSegment::MoveTo(cx + rx, cy),
+        PathSegment::CurveTo(cx + rx, cy + ky, cx + kx, cy + ry, cx, cy + ry),
+        PathSegment::CurveTo(cx - kx, cy + ry, cx - rx, cy + ky, cx - rx, cy),
+        PathSegment::CurveTo(cx - rx, cy - ky, cx - kx, cy - ry, cx, cy - ry),
+        PathSegment::CurveTo(cx + kx, cy - ry, cx + rx, cy - ky, cx + rx, cy),
+        PathSegment::Close,
+    ]
+}
+
+/// Parse svg path data into absolute segments, quadratic curves become cubic ones
+fn parse_path(d: &str) -> Result<Vec<PathSegment>, ParseError> {
+    let mut segments = vec![];
+    let mut scanner = NumberScanner {
+        input: d,
+        position: 0,
+    };
+    let (mut x, mut y) = (0.0f32, 0.0f32);
+    let (mut start_x, mut start_y) = (0.0f32, 0.0f32);
+    // reflected control point for the smooth curve commands
+    let mut last_cubic: Option<(f32, f32)> = None;
+    let mut last_quadratic: Option<(f32, f32)> = None;
+    let mut current = None;
+
+    loop {
+        scanner.skip_separators();
+        match scanner.peek() {
+            None => break,
+            Some(c) if c.is_ascii_alphabetic() => {
+                scanner.position += 1;
+                current = Some(c);
+                if matches!(c, 'Z' | 'z') {
+                    segments.push(PathSegment::Close);
+                    x = start_x;
+                    y = start_y;
+                    last_cubic = None;
+                    last_quadratic = None;
+                    continue;
+                }
+            }
+            Some(_) => (),
+        }
+        let command = match current {
+            Some(c) if !matches!(c, 'Z' | 'z') => c,
+            _ => {
+                return Err(ParseError::invalid(
+                    "Svg path data doesn't start with a command",
+                ))
+            }
+        };
+
+        let count = match command.to_ascii_uppercase() {
+            'M' | 'L' | 'T' => 2,
+            'H' | 'V' => 1,
+            'S' | 'Q' => 4,
+            'C' => 6,
+            'A' => 7,
+            _ => {
+                return Err(ParseError::invalid(format!(
+                    "Invalid svg path command '{command}'"
+                )))
+            }
+        };
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            match scanner.next_number()? {
+                Some(value) => values.push(value),
+                None => return Err(ParseError::invalid("Svg path command is missing values")),
+            }
+        }
+        let relative = command.is_ascii_lowercase();
+        let (offset_x, offset_y) = if relative { (x, y) } else { (0.0, 0.0) };
+        let point = |i: usize| (values[i] + offset_x, values[i + 1] + offset_y);
+
+        let mut cubic = None;
+        let mut quadratic = None;
+        match command.to_ascii_uppercase() {
+            'M' => {
+                (x, y) = point(0);
+                (start_x, start_y) = (x, y);
+                segments.push(PathSegment::MoveTo(x, y));
+                // further coordinate pairs are lines
+                current = Some(if relative { 'l' } else { 'L' });
+            }
+            'L' => {
+                (x, y) = point(0);
+                segments.push(PathSegment::LineTo(x, y));
+            }
+            'H' => {
+                x = values[0] + offset_x;
+                segments.push(PathSegment::LineTo(x, y));
+            }
+            'V' => {
+                y = values[0] + offset_y;
+                segments.push(PathSegment::LineTo(x, y));
+            }
+            'C' | 'S' => {
+                let (x1, y1) = match command.to_ascii_uppercase() {
+                    'C' => point(0),
+                    _ => last_cubic.map_or((x, y), |(cx, cy)| (2.0 * x - cx, 2.0 * y - cy)),
+                };
+                let rest = if command.to_ascii_uppercase() == 'C' {
+                    2
+                } else {
+                    0
+                };
+                let (x2, y2) = point(rest);
+                let (end_x, end_y) = point(rest + 2);
+                segments.push(PathSegment::CurveTo(x1, y1, x2, y2, end_x, end_y));
+                (x, y) = (end_x, end_y);
+                cubic = Some((x2, y2));
+            }
+            'Q' | 'T' => {
+                let (qx, qy) = match command.to_ascii_uppercase() {
+                    'Q' => point(0),
+                    _ => last_quadratic.map_or((x, y), |(cx, cy)| (2.0 * x - cx, 2.0 * y - cy)),
+                };
+                let end = if command.to_ascii_uppercase() == 'Q' {
+                    2
+                } else {
+                    0
+                };
+                let (end_x, end_y) = point(end);
+                segments.push(PathSegment::CurveTo(
+                    x + 2.0 / 3.0 * (qx - x),
+                    y + 2.0 / 3.0 * (qy - y),
+                    end_x + 2.0 / 3.0 * (qx - end_x),
+                    end_y + 2.0 / 3.0 * (qy - end_y),
+                    end_x,
+                    end_y,
+                ));
+                (x, y) = (end_x, end_y);
+                quadratic = Some((qx, qy));
+            }
+            // arcs only appear in decorations, their end point is enough
+            _ => {
+                (x, y) = point(5);
+                segments.push(PathSegment::LineTo(x, y));
+            }
+        }
+        last_cubic = cubic;
+        last_quadratic = quadratic;
+    }
+    Ok(segments)
+}
@@ -0,0 +1,70 @@
+//! Previews shown in the tablet's document overview
+//!
+//! The tablet keeps a `<uuid>.thumbnails/<page-uuid>.png` for every page of a document. Only the
+//! template and what was written are drawn, pages of a PDF or EPUB show a white background as
+//! the original pages can't be rendered here.
+
+use std::collections::HashMap;
+
+use crate::{notebook::Notebook, ParseError};
+
+use super::{
+    image::Image,
+    raster::render,
+    template::{Template, Templates},
+    PageDrawing,
+};
+
+/// Width of the thumbnails the tablet stores
+pub const THUMBNAIL_WIDTH: u32 = 280;
+/// Height of the thumbnails the tablet stores
+pub const THUMBNAIL_HEIGHT: u32 = 374;
+
+/// Draw a thumbnail of a page
+pub fn thumbnail(drawing: &PageDrawing, template: Option<&Template>) -> Image {
+    render(drawing, template, THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)
+}
+
+/// Path of the thumbnail of page `page_id`, relative to the data directory
+pub fn thumbnail_path(document_id: &str, page_id: &str) -> String {
+    format!("{document_id}.thumbnails/{page_id}.png")
+}
+
+impl Notebook {
+    /// Draw a thumbnail of every page and add it to [Notebook::other_files] under
+    /// [thumbnail_path], replacing thumbnails that are already there
+    ///
+    /// Templates are only drawn if `templates` is given, pages with a PDF page get none.
+    pub fn add_thumbnails(&mut self, templates: Option<&Templates>) -> Result<(), ParseError> {
+        let mut loaded: HashMap<String, Option<Template>> = HashMap::new();
+        let mut thumbnails = vec![];
+        for (index, page) in self.pages.iter().enumerate() {
+            let has_source = self
+                .content
+                .pages
+                .get(index)
+                .map_or(false, |p| p.redirect.is_some());
+            let name = match templates {
+                Some(templates) if !has_source => templates.name_for_page(self, index),
+                _ => None,
+            };
+            let template = match (templates, name) {
+                (Some(templates), Some(name)) => {
+                    if !loaded.contains_key(&name) {
+                        loaded.insert(name.clone(), templates.load(&name)?);
+                    }
+                    loaded.get(&name).and_then(|t| t.as_ref())
+                }
+                _ => None,
+            };
+
+            let drawing = page.file().map(PageDrawing::new).unwrap_or_default();
+            thumbnails.push((
+                thumbnail_path(&self.id, &page.id),
+                thumbnail(&drawing, template).to_png(),
+            ));
+        }
+        self.other_files.extend(thumbnails);
+        Ok(())
+    }
+}
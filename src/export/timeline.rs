@@ -0,0 +1,206 @@
+//! Replaying how a page was drawn
+//!
+//! The id of a v6 line grows with every change its author makes, so sorting the strokes by it
+//! gives the order they were drawn in. Lines moved to another layer keep the id they were drawn
+//! as. v3 to v5 files store their lines in drawing order. Strokes
+//! are replayed one after another at a constant speed along the line, the tablet doesn't store
+//! how long drawing a line actually took.
+//!
+//! [animated_svg] writes the replay as an SVG animated with SMIL, which browsers play directly.
+//! [frames] draws it into images, e.g. to be encoded into a video. Highlights have no time they
+//! were made at and are shown from the start.
+
+use crate::pdf::format_real;
+
+use super::{
+    image::Image, raster::render, template::Template, PageDrawing, Stroke, PAGE_HEIGHT, PAGE_WIDTH,
+};
+
+/// Shortest time a stroke takes to draw, so dots still show up for a moment
+const MIN_DURATION: f32 = 0.05;
+
+/// Timing of a replay
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+    /// Speed strokes are drawn with along the line, in pixels of the tablet's screen
+    pub pixels_per_second: f32,
+    /// Seconds between the end of a stroke and the start of the next one
+    pub pause: f32,
+    /// Images drawn for every second by [frames]
+    pub frames_per_second: f32,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            pixels_per_second: 1500.0,
+            pause: 0.2,
+            frames_per_second: 30.0,
+        }
+    }
+}
+
+/// A stroke together with when it is drawn in the replay
+#[derive(Debug, Clone)]
+pub struct TimelineStroke<'a> {
+    pub stroke: &'a Stroke,
+    /// Seconds from the start of the replay
+    pub start: f32,
+    /// Seconds it takes to draw the stroke
+    pub duration: f32,
+}
+
+/// Strokes of `drawing` in the order they were drawn, timed by `options`
+pub fn timeline<'a>(drawing: &'a PageDrawing, options: &ReplayOptions) -> Vec<TimelineStroke<'a>> {
+    let mut strokes: Vec<&Stroke> = drawing.strokes.iter().collect();
+    // stable, v3 to v5 strokes have no id and keep their order
+    strokes.sort_by_key(|stroke| stroke.id);
+
+    let speed = options.pixels_per_second.max(1.0);
+    let mut start = 0.0;
+    strokes
+        .into_iter()
+        .map(|stroke| {
+            let duration = (length(stroke) / speed).max(MIN_DURATION);
+            let timed = TimelineStroke {
+                stroke,
+                start,
+                duration,
+            };
+            start += duration + options.pause.max(0.0);
+            timed
+        })
+        .collect()
+}
+
+/// Seconds until the last stroke is finished
+pub fn duration(timeline: &[TimelineStroke]) -> f32 {
+    timeline
+        .last()
+        .map_or(0.0, |stroke| stroke.start + stroke.duration)
+}
+
+/// Replay of `drawing` as an SVG of the tablet's screen size animated with SMIL
+///
+/// Every stroke is hidden until its start and then traced from its first to its last point. The
+/// width of a stroke is the average width of its points.
+pub fn animated_svg(drawing: &PageDrawing, options: &ReplayOptions) -> String {
+    let real = |value: f32| format_real(value as f64);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\">\n<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+        w = real(PAGE_WIDTH),
+        h = real(PAGE_HEIGHT),
+    );
+
+    for (color, [x, y, width, height]) in drawing.screen_highlights() {
+        let [r, g, b, a] = color.to_rgba();
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{r:02x}{g:02x}{b:02x}\" \
+             fill-opacity=\"{}\"/>\n",
+            real(x),
+            real(y),
+            real(width),
+            real(height),
+            real(a as f32 / 255.0 * 0.4),
+        ));
+    }
+
+    for timed in timeline(drawing, options) {
+        let stroke = timed.stroke;
+        if stroke.points.is_empty() {
+            continue;
+        }
+        let mut path = String::new();
+        for (i, point) in stroke.points.iter().enumerate() {
+            let command = if i == 0 { 'M' } else { 'L' };
+            path.push_str(&format!("{command}{} {} ", real(point.x), real(point.y)));
+        }
+        // a single point still needs a segment to get a round cap
+        if stroke.points.len() == 1 {
+            path.push_str(&format!(
+                "L{} {} ",
+                real(stroke.points[0].x),
+                real(stroke.points[0].y)
+            ));
+        }
+        let width = stroke.points.iter().map(|p| p.width).sum::<f32>() / stroke.points.len() as f32;
+        let [r, g, b, _] = stroke.color.to_rgba();
+        let begin = real(timed.start);
+        svg.push_str(&format!(
+            "<path d=\"{}\" fill=\"none\" stroke=\"#{r:02x}{g:02x}{b:02x}\" stroke-opacity=\"{}\" \
+             stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\" \
+             pathLength=\"1\" stroke-dasharray=\"1\" stroke-dashoffset=\"1\" visibility=\"hidden\">\
+             <set attributeName=\"visibility\" to=\"visible\" begin=\"{begin}s\" fill=\"freeze\"/>\
+             <animate attributeName=\"stroke-dashoffset\" from=\"1\" to=\"0\" begin=\"{begin}s\" \
+             dur=\"{}s\" fill=\"freeze\"/></path>\n",
+            path.trim_end(),
+            real(stroke.opacity()),
+            real(width),
+            real(timed.duration),
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Replay of `drawing` as images of `width` by `height` pixels drawn with
+/// [render](super::raster::render), one every `1 / frames_per_second` seconds
+///
+/// The first image shows the empty page, the last one the finished page. Images are drawn as
+/// the iterator is advanced so long replays don't have to be kept in memory.
+pub fn frames<'a>(
+    drawing: &'a PageDrawing,
+    template: Option<&'a Template>,
+    width: u32,
+    height: u32,
+    options: &ReplayOptions,
+) -> impl Iterator<Item = Image> + 'a {
+    let timeline = timeline(drawing, options);
+    let fps = options.frames_per_second.max(1.0);
+    let count = (duration(&timeline) * fps).ceil() as usize + 1;
+    (0..count).map(move |frame| {
+        let time = frame as f32 / fps;
+        let page = PageDrawing {
+            strokes: timeline
+                .iter()
+                .take_while(|timed| timed.start <= time)
+                .map(|timed| drawn_part(timed, time))
+                .collect(),
+            highlights: drawing.highlights.clone(),
+        };
+        render(&page, template, width, height)
+    })
+}
+
+/// Length of the line through the points of `stroke`
+fn length(stroke: &Stroke) -> f32 {
+    stroke
+        .points
+        .windows(2)
+        .map(|p| (p[1].x - p[0].x).hypot(p[1].y - p[0].y))
+        .sum()
+}
+
+/// The part of the stroke drawn `time` seconds into the replay
+fn drawn_part(timed: &TimelineStroke, time: f32) -> Stroke {
+    let stroke = timed.stroke;
+    let progress = (time - timed.start) / timed.duration;
+    if progress >= 1.0 {
+        return stroke.clone();
+    }
+    let drawn = progress * length(stroke);
+    let mut covered = 0.0;
+    let mut count = 1;
+    for p in stroke.points.windows(2) {
+        covered += (p[1].x - p[0].x).hypot(p[1].y - p[0].y);
+        if covered > drawn {
+            break;
+        }
+        count += 1;
+    }
+    Stroke {
+        points: stroke.points.iter().take(count).copied().collect(),
+        ..stroke.clone()
+    }
+}
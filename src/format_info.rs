@@ -0,0 +1,19 @@
+use std::collections::HashMap;
+
+/// Summary of a file found by [crate::RemarkableFile::sniff] without parsing its data
+#[derive(Debug, Default, Clone)]
+pub struct FormatInfo {
+    /// Header text at the start of the file, empty if the input is too short to have one
+    pub header: String,
+    /// Version found in the header, `None` if the header is not recognized
+    pub version: Option<u32>,
+    /// True if [crate::RemarkableFile::read] supports this version
+    pub supported: bool,
+    /// Amount of blocks in a v6 file, `None` for other versions
+    pub block_count: Option<usize>,
+    /// Amount of blocks per block type in a v6 file, using the raw type found in the block header
+    pub block_types: HashMap<u8, usize>,
+    /// True if a v6 block header was invalid or pointed past the end of the input, the block
+    /// count only covers blocks before it
+    pub truncated: bool,
+}
@@ -0,0 +1,292 @@
+//! Changing the layers of a page
+//!
+//! Layers are addressed by their index counted from the bottom, as listed by
+//! [RemarkableFile::layers]. Changes to v6 pages are added as new blocks the way the tablet
+//! would write them, see [SceneBuilder], so they are kept by [RemarkableFile::to_bytes]. Layers
+//! of older versions have no name and can't be hidden, they are named `Layer <n>` in listings.
+
+use std::collections::HashMap;
+
+use crate::{
+    uuid,
+    v6::{
+        block::Block, crdt::CrdtId, scene_builder::SceneBuilder, scene_item::SceneItem,
+        scene_tree::SceneTree,
+    },
+    ParseError, RemarkableFile,
+};
+
+/// A layer of a page as listed by [RemarkableFile::layers]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerInfo {
+    pub name: String,
+    pub visible: bool,
+    /// Amount of lines drawn in the layer
+    pub lines: usize,
+}
+
+/// What [RemarkableFile::split_layer] puts into separate layers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBy {
+    Tool,
+    Color,
+}
+
+impl RemarkableFile {
+    /// Layers of the page from bottom to top
+    pub fn layers(&self) -> Vec<LayerInfo> {
+        match self {
+            RemarkableFile::V6 { tree, .. } => tree
+                .layers()
+                .into_iter()
+                .map(|group| LayerInfo {
+                    name: group.label.value.clone(),
+                    visible: group.visible.value,
+                    lines: group
+                        .children
+                        .items
+                        .values()
+                        .filter(|item| matches!(item.value, SceneItem::Line(_)))
+                        .count(),
+                })
+                .collect(),
+            RemarkableFile::Other { pages, .. } => pages
+                .first()
+                .map(|page| {
+                    page.layers
+                        .iter()
+                        .enumerate()
+                        .map(|(index, layer)| LayerInfo {
+                            name: format!("Layer {}", index + 1),
+                            visible: true,
+                            lines: layer.lines.len(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn check_layer(&self, index: usize) -> Result<(), ParseError> {
+        let count = match self {
+            // the layer has to exist on every page
+            RemarkableFile::Other { pages, .. } => pages
+                .iter()
+                .map(|page| page.layers.len())
+                .min()
+                .unwrap_or(0),
+            RemarkableFile::V6 { .. } => self.layers().len(),
+        };
+        if index >= count {
+            return Err(ParseError::invalid(format!(
+                "Layer {index} does not exist, the page has {count} layers"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rename layer `index`, only v6 layers have a name
+    pub fn rename_layer(&mut self, index: usize, name: &str) -> Result<(), ParseError> {
+        self.check_layer(index)?;
+        match self {
+            RemarkableFile::V6 { .. } => edit_v6(self, |builder| {
+                let layer = layer_id(builder.tree(), index);
+                builder.rename_layer(layer, name)
+            }),
+            RemarkableFile::Other { version, .. } => Err(ParseError::unsupported(format!(
+                "Layers of version {version} have no name"
+            ))),
+        }
+    }
+
+    /// Show or hide layer `index`, only v6 layers can be hidden
+    pub fn set_layer_visible(&mut self, index: usize, visible: bool) -> Result<(), ParseError> {
+        self.check_layer(index)?;
+        match self {
+            RemarkableFile::V6 { .. } => edit_v6(self, |builder| {
+                let layer = layer_id(builder.tree(), index);
+                builder.set_layer_visible(layer, visible)
+            }),
+            RemarkableFile::Other { version, .. } => Err(ParseError::unsupported(format!(
+                "Layers of version {version} can't be hidden"
+            ))),
+        }
+    }
+
+    /// Move layer `from` to position `to`, the layers in between shift by one
+    pub fn move_layer(&mut self, from: usize, to: usize) -> Result<(), ParseError> {
+        self.check_layer(from)?;
+        self.check_layer(to)?;
+        match self {
+            RemarkableFile::V6 { .. } => edit_v6(self, |builder| {
+                let layer = layer_id(builder.tree(), from);
+                builder.move_layer(layer, to)
+            }),
+            RemarkableFile::Other { pages, .. } => {
+                for page in pages.iter_mut() {
+                    let layer = page.layers.remove(from);
+                    page.layers.insert(to, layer);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Move everything in layer `from` on top of layer `into` and delete layer `from`
+    pub fn merge_layers(&mut self, from: usize, into: usize) -> Result<(), ParseError> {
+        self.check_layer(from)?;
+        self.check_layer(into)?;
+        if from == into {
+            return Ok(());
+        }
+        match self {
+            RemarkableFile::V6 { .. } => edit_v6(self, |builder| {
+                let (from, into) = (
+                    layer_id(builder.tree(), from),
+                    layer_id(builder.tree(), into),
+                );
+                let items = builder
+                    .tree()
+                    .node(&from)
+                    .map(|group| group.children.sorted_ids())
+                    .unwrap_or_default();
+                for item in items {
+                    builder.move_item(item, from, into)?;
+                }
+                builder.delete_layer(from)
+            }),
+            RemarkableFile::Other { pages, .. } => {
+                for page in pages.iter_mut() {
+                    let lines = std::mem::take(&mut page.layers[from].lines);
+                    page.layers[into].lines.extend(lines);
+                    page.layers.remove(from);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Move the lines of layer `index` into new layers right above it, one for every tool or
+    /// color. Lines like the first line of the layer stay where they are.
+    ///
+    /// Returns the amount of layers added. New v6 layers are named after the original layer
+    /// followed by the tool or color.
+    pub fn split_layer(&mut self, index: usize, by: SplitBy) -> Result<usize, ParseError> {
+        self.check_layer(index)?;
+        let key = |tool, color| match by {
+            SplitBy::Tool => format!("{tool:?}"),
+            SplitBy::Color => format!("{color:?}"),
+        };
+        match self {
+            RemarkableFile::V6 { .. } => edit_v6(self, |builder| {
+                let layer = layer_id(builder.tree(), index);
+                let group = match builder.tree().node(&layer) {
+                    Some(group) => group,
+                    None => return Ok(0),
+                };
+                let name = group.label.value.clone();
+                let mut keys: Vec<String> = vec![];
+                let mut lines: Vec<(CrdtId, usize)> = vec![];
+                for item in group.children.iter() {
+                    if let SceneItem::Line(line) = &item.value {
+                        let key = key(line.tool, line.color);
+                        let position = match keys.iter().position(|k| *k == key) {
+                            Some(position) => position,
+                            None => {
+                                keys.push(key);
+                                keys.len() - 1
+                            }
+                        };
+                        lines.push((item.item_id, position));
+                    }
+                }
+
+                let mut layers = HashMap::new();
+                for (position, key) in keys.iter().enumerate().skip(1) {
+                    let new_layer = builder.add_layer(&format!("{name} {key}"))?;
+                    builder.move_layer(new_layer, index + position)?;
+                    layers.insert(position, new_layer);
+                }
+                for (item, position) in lines {
+                    if let Some(new_layer) = layers.get(&position) {
+                        builder.move_item(item, layer, *new_layer)?;
+                    }
+                }
+                Ok(layers.len())
+            }),
+            RemarkableFile::Other { pages, .. } => {
+                let mut added = 0;
+                for page in pages.iter_mut() {
+                    let mut split: Vec<(String, Vec<_>)> = vec![];
+                    for line in std::mem::take(&mut page.layers[index].lines) {
+                        let key = key(line.tool, line.color);
+                        match split.iter_mut().find(|(k, _)| *k == key) {
+                            Some((_, lines)) => lines.push(line),
+                            None => split.push((key, vec![line])),
+                        }
+                    }
+                    let mut split = split.into_iter().map(|(_, lines)| lines);
+                    page.layers[index].lines = split.next().unwrap_or_default();
+                    for (offset, lines) in split.enumerate() {
+                        page.layers
+                            .insert(index + 1 + offset, crate::other::layer::Layer { lines });
+                        added = added.max(offset + 1);
+                    }
+                }
+                Ok(added)
+            }
+        }
+    }
+
+    /// Delete layer `index` together with everything drawn in it
+    pub fn delete_layer(&mut self, index: usize) -> Result<(), ParseError> {
+        self.check_layer(index)?;
+        match self {
+            RemarkableFile::V6 { .. } => edit_v6(self, |builder| {
+                let layer = layer_id(builder.tree(), index);
+                builder.delete_layer(layer)
+            }),
+            RemarkableFile::Other { pages, .. } => {
+                for page in pages.iter_mut() {
+                    page.layers.remove(index);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Group of v6 layer `index`, which is known to exist
+fn layer_id(tree: &SceneTree, index: usize) -> CrdtId {
+    tree.layers()[index].node_id
+}
+
+/// Change a v6 page with a [SceneBuilder] writing as the first author of the page
+///
+/// The edit is made on a copy, the page is left as it was if it fails.
+fn edit_v6<T>(
+    file: &mut RemarkableFile,
+    edit: impl FnOnce(&mut SceneBuilder) -> Result<T, ParseError>,
+) -> Result<T, ParseError> {
+    let (author, page) = match file {
+        RemarkableFile::V6 { tree, blocks } => {
+            let author = blocks
+                .iter()
+                .find_map(|block| match block {
+                    Block::AuthorsIds(block) => block.authors.iter().min().map(|(_, a)| a.clone()),
+                    _ => None,
+                })
+                .unwrap_or_else(uuid::new_v4);
+            let page = RemarkableFile::V6 {
+                tree: tree.clone(),
+                blocks: blocks.clone(),
+            };
+            (author, page)
+        }
+        RemarkableFile::Other { .. } => return Err(ParseError::unsupported("Not a v6 page")),
+    };
+    let mut builder = SceneBuilder::from_file(page, &author)?;
+    let result = edit(&mut builder)?;
+    *file = builder.build();
+    Ok(result)
+}
@@ -16,32 +16,97 @@
 //! use std::{fs::read};
 //! use remarkable_lines::{RemarkableFile};
 //!
-//! pub fn main() {
-//!     let test_file = read("./test.rm").unwrap();
-//!     let rm_file = RemarkableFile::read(&test_file[..]).unwrap();
-//!     println!("{rm_file:?}");
-//! }
+//! let test_file = read("./test.rm").unwrap();
+//! let rm_file = RemarkableFile::read(&test_file[..]).unwrap();
+//! println!("{rm_file:?}");
 //! ```
+//!
+//! # Notebooks
+//! Documents made out of multiple pages can be loaded from a local copy of the tablet's data
+//! directory with [notebook::Notebook::load].
+//! `.rmdoc` archives as used by the desktop app are read with [notebook::Notebook::read_rmdoc]
+//! and written with [notebook::Notebook::to_rmdoc].
+//! [notebook::Library::load] reads the whole data directory into its folder hierarchy, which
+//! can be exported as a directory tree of `.rmdoc` archives with [notebook::Folder::export].
+//! Tags are listed with [notebook::Notebook::tags], [notebook::Notebook::pages_tagged] finds the
+//! pages with a tag.
+//! New notebooks are created with [notebook::Notebook::new], filled with
+//! [notebook::Notebook::add_page] and written into a data directory with
+//! [notebook::Notebook::write]. Single pages are serialized with [RemarkableFile::to_bytes].
+//!
+//! # Merging
+//! v6 pages edited on two devices can be combined with [RemarkableFile::merge] instead of
+//! picking one side, given the version both started from.
+//! [diff::diff] lists what changed between two versions of a page.
+//! [RemarkableFile::compact] drops the history of heavily edited pages for a smaller file.
+//! Pages are generated or extended with layers, lines and text using
+//! [v6::scene_builder::SceneBuilder], which also inserts and deletes typed text the way the
+//! tablet does.
+//! Layers of any version are renamed, hidden, reordered, merged, split and deleted with the
+//! methods in [layers], such as [RemarkableFile::merge_layers].
+//!
+//! # Exporting
+//! Strokes and highlights of a page are collected with [export::PageDrawing::new].
+//! For PDF and EPUB documents [notebook::Notebook::to_annotated_pdf] draws them on top of the
+//! original pages, including pages inserted on the tablet. Highlights become `/Highlight`
+//! annotations, see [export::pdf::PdfOptions].
+//! Highlighted text is listed with [v6::scene_tree::SceneTree::highlights] or
+//! [notebook::Notebook::highlights] for all pages.
+//! [notebook::Notebook::to_pdf] also exports notebooks without a PDF. Given a copy of the
+//! tablet's template directory in [export::pdf::PdfOptions::templates], the template of every
+//! page is drawn under its strokes, see [export::template::Templates].
+//! [export::raster::render] draws a page into an image and
+//! [notebook::Notebook::add_thumbnails] adds the previews the tablet shows in its overview.
+//! [export::timeline] replays a page stroke by stroke in the order it was drawn, as an animated
+//! SVG or as images for a video.
+//!
+//! # Untrusted input
+//! Parsing never panics on malformed input, lengths and counts found in a file are checked
+//! against [ParseLimits] before anything is allocated. Use [RemarkableFile::read_with_limits]
+//! or [notebook::Notebook::read_rmdoc_with_limits] to tighten them. Fuzz targets live in `fuzz/` and can be run with `cargo fuzz run read`.
+
+#![allow(clippy::needless_return)]
+
+use std::collections::HashMap;
 
 use bitreader::Bitreader;
 use bitreader::Readable;
-use other::{Page, Parse};
-use v6::block::Block;
+use bitwriter::Bitwriter;
+use other::{Page, Parse, Write};
+use v6::block::{Block, BlockInfo};
+use v6::scene_item::text::TextItem;
 use v6::scene_tree::SceneTree;
 use v6::tagged_bit_reader::TaggedBitreader;
-use v6::TypeParse;
+use v6::tagged_bit_writer::TaggedBitwriter;
+use v6::{TypeParse, TypeWrite};
 
 pub mod bitreader;
+pub mod bitwriter;
+pub(crate) mod compression;
+pub mod diff;
+pub mod export;
+pub mod format_info;
+pub mod json;
+pub mod layers;
+pub mod notebook;
 pub mod other;
 pub mod parse_error;
+pub mod parse_limits;
+pub(crate) mod pdf;
 pub mod shared;
+pub mod uuid;
 pub mod v6;
+pub(crate) mod zip;
 
 pub use crate::parse_error::ParseErrorKind;
+pub use format_info::FormatInfo;
 pub use parse_error::ParseError;
+pub use parse_limits::ParseLimits;
 
 /// Structure that represents the file
 #[derive(Debug)]
+// boxing the tree would make matching on files more awkward for little gain
+#[allow(clippy::large_enum_variant)]
 pub enum RemarkableFile {
     V6 {
         /// SceneTree is made from Block's and is a tree with nodes where each node resembles a seperate item
@@ -57,42 +122,102 @@ pub enum RemarkableFile {
 
 impl RemarkableFile {
     pub fn read(input: impl Readable) -> Result<RemarkableFile, ParseError> {
-        let mut reader = Bitreader::new(input);
+        Self::read_with_limits(input, ParseLimits::default())
+    }
+
+    /// Read a file while enforcing custom `limits`, use this for input from untrusted sources
+    /// that should be bound tighter than the defaults
+    pub fn read_with_limits(
+        input: impl Readable,
+        limits: ParseLimits,
+    ) -> Result<RemarkableFile, ParseError> {
+        let mut reader = Bitreader::with_limits(input, limits);
         return Self::read_impl(&mut reader)
             .map_err(|e| e.with_context_from_bitreader(&mut reader));
     }
 
-    fn read_impl(reader: &mut Bitreader<impl Readable>) -> Result<RemarkableFile, ParseError> {
-        let version_description = reader
+    /// Cheaply inspect `input` without decoding any of its data
+    ///
+    /// Only the header and, for v6, the block headers are read. This never fails, anything
+    /// that is not a reMarkable file is reported as unsupported without a version.
+    pub fn sniff(input: &[u8]) -> FormatInfo {
+        let mut reader = Bitreader::new(input);
+        let header = match Self::read_header(&mut reader) {
+            Ok(header) => header,
+            Err(_) => return FormatInfo::default(),
+        };
+        let version = Self::version_from_header(&header).ok();
+
+        let mut info = FormatInfo {
+            supported: version.map(Self::is_supported_version).unwrap_or(false),
+            header,
+            version,
+            ..Default::default()
+        };
+
+        if version == Some(6) {
+            let mut block_types = HashMap::new();
+            let mut block_count = 0;
+            while let Ok(false) = reader.eof() {
+                match BlockInfo::parse(&mut reader) {
+                    Ok(block) => {
+                        *block_types.entry(block.block_type).or_insert(0) += 1;
+                        block_count += 1;
+                        reader.set_position(block.end_offset());
+                    }
+                    Err(_) => {
+                        info.truncated = true;
+                        break;
+                    }
+                }
+            }
+            info.block_count = Some(block_count);
+            info.block_types = block_types;
+        }
+
+        info
+    }
+
+    /// Read the fixed size header at the start of every file
+    fn read_header(reader: &mut Bitreader<impl Readable>) -> Result<String, ParseError> {
+        let header = reader
             .read_bytes(43)?
             .into_iter()
             .map(|i| i as char)
             .collect::<String>();
+        Ok(header.trim_end().to_owned())
+    }
 
-        let version_description = version_description.trim_end();
-        let version: u32 = {
-            if version_description == "reMarkable lines with selections and layers" {
-                // early version of the format that is not supported
-                return Err(ParseError::unsupported("Unsupported outdated version"));
-            } else if version_description.starts_with("reMarkable .lines file, version=") {
-                match version_description.split("=").nth(1) {
-                    Some(v) => v.parse().map_err(|_| {
-                        ParseError::unsupported(format!(
-                            "Could not find version from: {version_description}"
-                        ))
-                    })?,
-                    None => {
-                        return Err(ParseError::unsupported(format!(
-                            "Unknown version from: {version_description}"
-                        )))
-                    }
-                }
-            } else {
-                return Err(ParseError::unsupported(format!(
+    fn is_supported_version(version: u32) -> bool {
+        (3..=6).contains(&version)
+    }
+
+    /// Get the version from the header text
+    fn version_from_header(version_description: &str) -> Result<u32, ParseError> {
+        if version_description == "reMarkable lines with selections and layers" {
+            // early version of the format that is not supported
+            return Err(ParseError::unsupported("Unsupported outdated version"));
+        } else if version_description.starts_with("reMarkable .lines file, version=") {
+            match version_description.split('=').nth(1) {
+                Some(v) => v.parse().map_err(|_| {
+                    ParseError::unsupported(format!(
+                        "Could not find version from: {version_description}"
+                    ))
+                }),
+                None => Err(ParseError::unsupported(format!(
                     "Unknown version from: {version_description}"
-                )));
+                ))),
             }
-        };
+        } else {
+            Err(ParseError::unsupported(format!(
+                "Unknown version from: {version_description}"
+            )))
+        }
+    }
+
+    fn read_impl(reader: &mut Bitreader<impl Readable>) -> Result<RemarkableFile, ParseError> {
+        let header = Self::read_header(reader)?;
+        let version = Self::version_from_header(&header)?;
 
         if version == 6 {
             let mut blocks = vec![];
@@ -105,13 +230,13 @@ impl RemarkableFile {
                 blocks.push(Block::parse(&mut tagged_bit_reader)?);
             }
 
-            let tree = SceneTree::from_blocks(&blocks)?;
+            let tree = SceneTree::from_blocks_with_limits(&blocks, reader.limits())?;
             return Ok(RemarkableFile::V6 { tree, blocks });
         }
 
-        if 3 > version || version > 6 {
+        if !Self::is_supported_version(version) {
             return Err(ParseError::new(
-                &format!("version '{version}' is not supported"),
+                format!("version '{version}' is not supported"),
                 ParseErrorKind::Unsupported,
             ));
         }
@@ -126,6 +251,80 @@ impl RemarkableFile {
         })
     }
 
+    /// Serialize the file in its own version, reading the result gives an equal file
+    ///
+    /// v6 files are written from [RemarkableFile::V6::blocks], changes to the tree are not
+    /// picked up. Line blocks are written in the current block version, unless they hold unchanged
+    /// version 1 points.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ParseError> {
+        let mut writer = Bitwriter::new();
+        let header = format!("reMarkable .lines file, version={}", self.version());
+        writer.write_string(&format!("{header:<43}"));
+
+        match self {
+            RemarkableFile::V6 { blocks, .. } => {
+                let mut tagged_bit_writer = TaggedBitwriter::new(&mut writer);
+                for block in blocks {
+                    block.write(&mut tagged_bit_writer)?;
+                }
+            }
+            RemarkableFile::Other { version, pages } => {
+                if !Self::is_supported_version(*version) {
+                    return Err(ParseError::unsupported(format!(
+                        "version '{version}' is not supported"
+                    )));
+                }
+                // these versions always hold exactly one page
+                if pages.len() != 1 {
+                    return Err(ParseError::invalid(format!(
+                        "Version {version} files hold one page, got {}",
+                        pages.len()
+                    )));
+                }
+                for page in pages {
+                    page.write(*version, &mut writer)?;
+                }
+            }
+        }
+        Ok(writer.into_bytes())
+    }
+
+    /// Three-way merge of two versions of a v6 page that were both edited starting from `base`
+    ///
+    /// See [v6::merge::merge] for how conflicts are resolved.
+    pub fn merge(
+        base: &RemarkableFile,
+        ours: &RemarkableFile,
+        theirs: &RemarkableFile,
+    ) -> Result<RemarkableFile, ParseError> {
+        v6::merge::merge(base, ours, theirs)
+    }
+
+    /// Drop deleted items and superseded updates from a v6 page, see [v6::compact::compact]
+    pub fn compact(&self) -> Result<RemarkableFile, ParseError> {
+        v6::compact::compact(self)
+    }
+
+    /// True if nothing is drawn, highlighted or typed in this file
+    pub fn is_blank(&self) -> bool {
+        match self {
+            RemarkableFile::V6 { blocks, .. } => {
+                !blocks.iter().any(|block| match block {
+                    Block::SceneLineItem(b) => b.item.value.is_some(),
+                    Block::SceneGlyphItem(b) => b.item.value.is_some(),
+                    Block::SceneTextItem(b) => b.item.value.is_some(),
+                    Block::RootText(b) => b.text.items.items.values().any(
+                        |item| matches!(&item.value, TextItem::Text(text) if !text.is_empty()),
+                    ),
+                    _ => false,
+                })
+            }
+            RemarkableFile::Other { pages, .. } => pages
+                .iter()
+                .all(|page| page.layers.iter().all(|layer| layer.lines.is_empty())),
+        }
+    }
+
     pub fn version(&self) -> u32 {
         match self {
             RemarkableFile::V6 { .. } => 6,
@@ -0,0 +1,153 @@
+use crate::{json::JsonValue, ParseError};
+
+/// A page as listed in a `.content` file
+#[derive(Debug, Clone)]
+pub struct ContentPage {
+    pub id: String,
+    /// Index of the page of the original PDF or EPUB shown under this page, `None` for pages
+    /// inserted on the tablet and for notebooks
+    pub redirect: Option<u32>,
+    /// Name of the page's template, only stored here by format version 2, see
+    /// [super::Notebook::page_templates]
+    pub template: Option<String>,
+    /// Names of the tags given to this page, from `pageTags`
+    pub tags: Vec<String>,
+}
+
+/// Contents of a `<uuid>.content` file
+#[derive(Debug, Clone)]
+pub struct Content {
+    /// `notebook`, `pdf` or `epub`
+    pub file_type: String,
+    pub format_version: u32,
+    /// Pages in the order they are shown on the tablet, deleted pages are left out
+    pub pages: Vec<ContentPage>,
+    /// Names of the tags given to the whole document
+    pub tags: Vec<String>,
+    /// Full json so fields not exposed here are kept when writing back
+    pub raw: JsonValue,
+}
+
+/// Names of the tags in a `tags` or `pageTags` list, `page_id` selects the tags of one page
+fn tag_names(tags: Option<&JsonValue>, page_id: Option<&str>) -> Vec<String> {
+    let tags = match tags.and_then(|t| t.as_array()) {
+        Some(tags) => tags,
+        None => return vec![],
+    };
+    let mut names: Vec<String> = vec![];
+    for tag in tags {
+        if page_id.is_some() && tag.get("pageId").and_then(|p| p.as_str()) != page_id {
+            continue;
+        }
+        // older files list plain strings
+        let name = tag
+            .as_str()
+            .or_else(|| tag.get("name").and_then(|n| n.as_str()));
+        if let Some(name) = name {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_owned());
+            }
+        }
+    }
+    names
+}
+
+impl Content {
+    pub fn parse(input: &str) -> Result<Content, ParseError> {
+        let raw = JsonValue::parse(input)?;
+        let format_version = raw
+            .get("formatVersion")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(1) as u32;
+        let file_type = raw
+            .get("fileType")
+            .and_then(|t| t.as_str())
+            .unwrap_or_default()
+            .to_owned();
+
+        let pages = match raw.get("cPages").and_then(|c| c.get("pages")) {
+            // format version 2, pages are CRDT items ordered by their index
+            Some(pages) => {
+                let mut pages = pages
+                    .as_array()
+                    .ok_or_else(|| ParseError::invalid("Expected cPages.pages to be an array"))?
+                    .iter()
+                    .filter(|p| {
+                        let deleted = p.get("deleted").and_then(|d| d.get("value"));
+                        deleted.and_then(|d| d.as_i64()).unwrap_or(0) == 0
+                    })
+                    .map(|p| {
+                        let id = p.get("id").and_then(|id| id.as_str()).ok_or_else(|| {
+                            ParseError::invalid("Page in cPages is missing an id")
+                        })?;
+                        let index = p
+                            .get("idx")
+                            .and_then(|i| i.get("value"))
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+                        let redirect = p
+                            .get("redir")
+                            .and_then(|r| r.get("value"))
+                            .and_then(|v| v.as_i64())
+                            .and_then(|v| u32::try_from(v).ok());
+                        let template = p
+                            .get("template")
+                            .and_then(|t| t.get("value"))
+                            .and_then(|v| v.as_str())
+                            .map(str::to_owned);
+                        Ok((
+                            index.to_owned(),
+                            ContentPage {
+                                id: id.to_owned(),
+                                redirect,
+                                template,
+                                tags: tag_names(raw.get("pageTags"), Some(id)),
+                            },
+                        ))
+                    })
+                    .collect::<Result<Vec<(String, ContentPage)>, ParseError>>()?;
+                pages.sort_by(|(a, _), (b, _)| a.cmp(b));
+                pages.into_iter().map(|(_, p)| p).collect()
+            }
+            // format version 1, inserted pages are -1 in the redirection map
+            None => {
+                let redirection_map = raw.get("redirectionPageMap").and_then(|m| m.as_array());
+                let has_source = file_type == "pdf" || file_type == "epub";
+                raw.get("pages")
+                    .and_then(|p| p.as_array())
+                    .map(|pages| {
+                        pages
+                            .iter()
+                            .filter_map(|p| p.as_str())
+                            .enumerate()
+                            .map(|(i, id)| {
+                                let redirect = match redirection_map {
+                                    Some(map) => map
+                                        .get(i)
+                                        .and_then(|v| v.as_i64())
+                                        .and_then(|v| u32::try_from(v).ok()),
+                                    None if has_source => u32::try_from(i).ok(),
+                                    None => None,
+                                };
+                                ContentPage {
+                                    id: id.to_owned(),
+                                    redirect,
+                                    template: None,
+                                    tags: tag_names(raw.get("pageTags"), Some(id)),
+                                }
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            }
+        };
+
+        Ok(Content {
+            file_type,
+            format_version,
+            pages,
+            tags: tag_names(raw.get("tags"), None),
+            raw,
+        })
+    }
+}
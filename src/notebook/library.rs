@@ -0,0 +1,207 @@
+//! The folder hierarchy of all documents in a data directory
+//!
+//! Files in the data directory are named by id only, the hierarchy is stored in the `parent` field
+//! of every `.metadata` file.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use crate::{ParseError, ParseErrorKind};
+
+use super::{DocumentType, Metadata, Notebook};
+
+/// Folder in the library, the root and trash folders have no metadata
+#[derive(Debug)]
+pub struct Folder {
+    /// Empty for the root folder, `trash` for the trash
+    pub id: String,
+    pub name: String,
+    pub folders: Vec<Folder>,
+    pub documents: Vec<Notebook>,
+}
+
+impl Folder {
+    fn new(id: &str, name: &str) -> Folder {
+        Folder {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            folders: vec![],
+            documents: vec![],
+        }
+    }
+
+    /// All documents in this folder and its sub folders, with the names of the folders leading
+    /// to them
+    pub fn documents_recursive(&self) -> Vec<(Vec<&str>, &Notebook)> {
+        let mut documents: Vec<(Vec<&str>, &Notebook)> =
+            self.documents.iter().map(|d| (vec![], d)).collect();
+        for folder in &self.folders {
+            for (mut path, document) in folder.documents_recursive() {
+                path.insert(0, &folder.name);
+                documents.push((path, document));
+            }
+        }
+        documents
+    }
+
+    /// Write the folder as a directory tree with every document as an `.rmdoc` archive
+    pub fn export(&self, out_dir: impl AsRef<Path>) -> Result<(), ParseError> {
+        let out_dir = out_dir.as_ref();
+        fs::create_dir_all(out_dir).map_err(|e| io_error(out_dir, e))?;
+
+        let mut used_names = HashSet::new();
+        for folder in &self.folders {
+            let path = out_dir.join(unique_file_name(&folder.name, "", &mut used_names));
+            folder.export(path)?;
+        }
+        for document in &self.documents {
+            let path = out_dir.join(unique_file_name(document.name(), ".rmdoc", &mut used_names));
+            fs::write(&path, document.to_rmdoc()?).map_err(|e| io_error(&path, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// All folders and documents in a data directory
+#[derive(Debug)]
+pub struct Library {
+    pub root: Folder,
+    pub trash: Folder,
+    /// Documents that could not be loaded by their id, the rest of the library is still loaded
+    pub errors: Vec<(String, ParseError)>,
+}
+
+impl Library {
+    /// Read every `.metadata` file in `data_dir` and load all documents
+    ///
+    /// Items marked as deleted are skipped, items whose parent can't be found are put in the root.
+    pub fn load(data_dir: impl AsRef<Path>) -> Result<Library, ParseError> {
+        let data_dir = data_dir.as_ref();
+        let mut items = HashMap::new();
+        let mut errors = vec![];
+
+        let entries = fs::read_dir(data_dir).map_err(|e| io_error(data_dir, e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| io_error(data_dir, e))?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("metadata") {
+                continue;
+            }
+            let id = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(id) => id.to_owned(),
+                None => continue,
+            };
+            let metadata = fs::read_to_string(&path)
+                .map_err(|e| io_error(&path, e))
+                .and_then(|m| Metadata::parse(&m));
+            match metadata {
+                Ok(metadata) if metadata.deleted => {}
+                Ok(metadata) => {
+                    items.insert(id, metadata);
+                }
+                Err(e) => errors.push((id, e)),
+            }
+        }
+
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (id, metadata) in &items {
+            children.entry(&metadata.parent).or_default().push(id);
+        }
+        for ids in children.values_mut() {
+            ids.sort_by_key(|id| (&items[*id].visible_name, *id));
+        }
+
+        let mut builder = TreeBuilder {
+            data_dir,
+            items: &items,
+            children: &children,
+            visited: HashSet::new(),
+            errors,
+        };
+        let mut root = Folder::new("", "");
+        builder.fill(&mut root);
+        let mut trash = Folder::new("trash", "trash");
+        builder.fill(&mut trash);
+
+        // items with an unknown parent or in a cycle of folders
+        let mut orphans: Vec<&String> = items.keys().collect();
+        orphans.sort();
+        for id in orphans {
+            builder.add(&mut root, id);
+        }
+
+        Ok(Library {
+            root,
+            trash,
+            errors: builder.errors,
+        })
+    }
+}
+
+struct TreeBuilder<'a> {
+    data_dir: &'a Path,
+    items: &'a HashMap<String, Metadata>,
+    children: &'a HashMap<&'a str, Vec<&'a str>>,
+    visited: HashSet<String>,
+    errors: Vec<(String, ParseError)>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn fill(&mut self, folder: &mut Folder) {
+        let children = self.children.get(folder.id.as_str()).cloned();
+        for id in children.unwrap_or_default() {
+            self.add(folder, id);
+        }
+    }
+
+    fn add(&mut self, folder: &mut Folder, id: &str) {
+        if !self.visited.insert(id.to_owned()) {
+            return;
+        }
+        let metadata = &self.items[id];
+        match metadata.doc_type {
+            DocumentType::Collection => {
+                let mut child = Folder::new(id, &metadata.visible_name);
+                self.fill(&mut child);
+                folder.folders.push(child);
+            }
+            DocumentType::Document => match Notebook::load(self.data_dir, id) {
+                Ok(notebook) => folder.documents.push(notebook),
+                Err(e) => self.errors.push((id.to_owned(), e)),
+            },
+        }
+    }
+}
+
+/// File name for `name` that is valid on common file systems and not used yet in a directory
+fn unique_file_name(name: &str, extension: &str, used: &mut HashSet<String>) -> String {
+    let mut base: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    base = base.trim().trim_start_matches('.').to_owned();
+    if base.is_empty() {
+        base = String::from("Untitled");
+    }
+
+    let mut file_name = format!("{base}{extension}");
+    let mut i = 2;
+    while !used.insert(file_name.to_lowercase()) {
+        file_name = format!("{base} ({i}){extension}");
+        i += 1;
+    }
+    file_name
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> ParseError {
+    ParseError::new(
+        format!("Failed to access '{}': {error}", path.display()),
+        ParseErrorKind::Io,
+    )
+}
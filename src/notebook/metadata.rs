@@ -0,0 +1,81 @@
+use crate::{json::JsonValue, ParseError};
+
+/// Kind of item stored in the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentType {
+    /// A notebook, pdf or epub (`DocumentType`)
+    Document,
+    /// A folder (`CollectionType`)
+    Collection,
+}
+
+/// Contents of a `<uuid>.metadata` file
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    /// Name shown on the tablet
+    pub visible_name: String,
+    pub doc_type: DocumentType,
+    /// Id of the parent folder, empty for the root and `trash` for items in the trash
+    pub parent: String,
+    /// Item is deleted and only kept until the deletion is synced
+    pub deleted: bool,
+    /// Milliseconds since epoch
+    pub last_modified: Option<i64>,
+    /// Full json so fields not exposed here are kept when writing back
+    pub raw: JsonValue,
+}
+
+impl Metadata {
+    /// True if the item was moved to the trash
+    pub fn in_trash(&self) -> bool {
+        self.parent == "trash"
+    }
+
+    /// Json to write back, [Metadata::raw] with the fields exposed here applied
+    pub fn to_json(&self) -> JsonValue {
+        let mut raw = self.raw.clone();
+        if !matches!(raw, JsonValue::Object(_)) {
+            raw = JsonValue::Object(vec![]);
+        }
+        raw.set("deleted", self.deleted.into());
+        // stored as string by the tablet
+        if let Some(last_modified) = self.last_modified {
+            raw.set("lastModified", last_modified.to_string().into());
+        }
+        raw.set("parent", self.parent.as_str().into());
+        let doc_type = match self.doc_type {
+            DocumentType::Document => "DocumentType",
+            DocumentType::Collection => "CollectionType",
+        };
+        raw.set("type", doc_type.into());
+        raw.set("visibleName", self.visible_name.as_str().into());
+        raw
+    }
+
+    pub fn parse(input: &str) -> Result<Metadata, ParseError> {
+        let raw = JsonValue::parse(input)?;
+        let doc_type = match raw.get("type").and_then(|t| t.as_str()) {
+            Some("CollectionType") => DocumentType::Collection,
+            _ => DocumentType::Document,
+        };
+        Ok(Metadata {
+            visible_name: raw
+                .get("visibleName")
+                .and_then(|n| n.as_str())
+                .unwrap_or_default()
+                .to_owned(),
+            doc_type,
+            parent: raw
+                .get("parent")
+                .and_then(|p| p.as_str())
+                .unwrap_or_default()
+                .to_owned(),
+            deleted: raw
+                .get("deleted")
+                .and_then(|d| d.as_bool())
+                .unwrap_or(false),
+            last_modified: raw.get("lastModified").and_then(|m| m.as_i64()),
+            raw,
+        })
+    }
+}
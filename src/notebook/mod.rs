@@ -0,0 +1,270 @@
+//! Documents as stored by the tablet in its data directory (`~/.local/share/remarkable/xochitl`)
+//!
+//! A document with id `<uuid>` is made up of:
+//! - `<uuid>.metadata` name, parent folder and modification info
+//! - `<uuid>.content` file type and the ordered list of pages
+//! - `<uuid>.pagedata` template name per page
+//! - `<uuid>/<page-uuid>.rm` one file per page, only present for pages with content
+//!
+//! New documents are created with [Notebook::new] and written with [Notebook::write].
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use crate::{v6::scene_item::glyph_range::Highlight, ParseError, ParseErrorKind, RemarkableFile};
+
+pub mod content;
+pub mod library;
+pub mod metadata;
+pub mod rmdoc;
+pub mod write;
+
+pub use content::{Content, ContentPage};
+pub use library::{Folder, Library};
+pub use metadata::{DocumentType, Metadata};
+
+/// State of a single page after loading its `.rm` file
+#[derive(Debug)]
+pub enum PageState {
+    /// Page was parsed and contains lines or text
+    Parsed(RemarkableFile),
+    /// Page was parsed but nothing is drawn or typed on it
+    Blank(RemarkableFile),
+    /// No `.rm` file exists for this page, the tablet doesn't store pages that were never written on
+    Missing,
+    /// The `.rm` file could not be parsed
+    Invalid(ParseError),
+}
+
+#[derive(Debug)]
+pub struct NotebookPage {
+    pub id: String,
+    /// Original `.rm` file, `None` if the page has no file
+    pub data: Option<Vec<u8>>,
+    pub state: PageState,
+}
+
+impl NotebookPage {
+    /// Parse the page from its raw `.rm` file
+    pub fn new(id: String, data: Option<Vec<u8>>) -> NotebookPage {
+        let state = match &data {
+            None => PageState::Missing,
+            Some(data) => match RemarkableFile::read(&data[..]) {
+                Ok(file) if file.is_blank() => PageState::Blank(file),
+                Ok(file) => PageState::Parsed(file),
+                Err(e) => PageState::Invalid(e),
+            },
+        };
+        NotebookPage { id, data, state }
+    }
+
+    /// Parsed file of this page, `None` if it is missing or invalid
+    pub fn file(&self) -> Option<&RemarkableFile> {
+        match &self.state {
+            PageState::Parsed(file) | PageState::Blank(file) => Some(file),
+            PageState::Missing | PageState::Invalid(_) => None,
+        }
+    }
+}
+
+/// Highlighted text together with the page it is on
+#[derive(Debug, Clone)]
+pub struct PageHighlight {
+    /// Index of the page in [Notebook::pages]
+    pub page: usize,
+    /// Page of the original PDF or EPUB, see [ContentPage::redirect]
+    pub source_page: Option<u32>,
+    pub highlight: Highlight,
+}
+
+/// A document made out of multiple pages
+#[derive(Debug)]
+pub struct Notebook {
+    pub id: String,
+    /// `None` if the document has no `.metadata` file
+    pub metadata: Option<Metadata>,
+    pub content: Content,
+    /// Pages in the order of [Content::pages]
+    pub pages: Vec<NotebookPage>,
+    /// Other files belonging to the document, eg. `<uuid>.pagedata` or the original `<uuid>.pdf`,
+    /// keyed by their path relative to the data directory
+    pub other_files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Notebook {
+    /// Load document `id` from a local copy of the tablet's data directory
+    pub fn load(data_dir: impl AsRef<Path>, id: &str) -> Result<Notebook, ParseError> {
+        let data_dir = data_dir.as_ref();
+        let content = read_to_string(&data_dir.join(format!("{id}.content")))?;
+        let metadata = read_optional(&data_dir.join(format!("{id}.metadata")))?
+            .map(String::from_utf8)
+            .transpose()?;
+        let pages_dir = data_dir.join(id);
+
+        let mut notebook = Self::from_files(id, metadata.as_deref(), &content, |page_id| {
+            read_optional(&pages_dir.join(format!("{page_id}.rm")))
+        })?;
+
+        for extension in ["pagedata", "pdf", "epub"] {
+            let name = format!("{id}.{extension}");
+            if let Some(data) = read_optional(&data_dir.join(&name))? {
+                notebook.other_files.insert(name, data);
+            }
+        }
+
+        Ok(notebook)
+    }
+
+    /// Build a document from the contents of its files
+    ///
+    /// `read_page` is called with every page id and returns the page's `.rm` file if it exists
+    pub fn from_files(
+        id: &str,
+        metadata: Option<&str>,
+        content: &str,
+        mut read_page: impl FnMut(&str) -> Result<Option<Vec<u8>>, ParseError>,
+    ) -> Result<Notebook, ParseError> {
+        let metadata = metadata.map(Metadata::parse).transpose()?;
+        let content = Content::parse(content)?;
+        let pages = content
+            .pages
+            .iter()
+            .map(|page| Ok(NotebookPage::new(page.id.clone(), read_page(&page.id)?)))
+            .collect::<Result<Vec<NotebookPage>, ParseError>>()?;
+
+        Ok(Notebook {
+            id: id.to_owned(),
+            metadata,
+            content,
+            pages,
+            other_files: BTreeMap::new(),
+        })
+    }
+
+    /// Name shown on the tablet, falls back to the id without metadata
+    pub fn name(&self) -> &str {
+        match &self.metadata {
+            Some(metadata) => &metadata.visible_name,
+            None => &self.id,
+        }
+    }
+
+    /// Highlighted text of all pages in page order, see [crate::v6::scene_tree::SceneTree::highlights]
+    pub fn highlights(&self) -> Vec<PageHighlight> {
+        let mut highlights = vec![];
+        for (index, page) in self.pages.iter().enumerate() {
+            if let Some(RemarkableFile::V6 { tree, .. }) = page.file() {
+                let source_page = self.content.pages.get(index).and_then(|p| p.redirect);
+                highlights.extend(
+                    tree.highlights()
+                        .into_iter()
+                        .map(|highlight| PageHighlight {
+                            page: index,
+                            source_page,
+                            highlight,
+                        }),
+                );
+            }
+        }
+        highlights
+    }
+
+    /// Uuids of everyone who wrote in this document, in the order they first appear
+    pub fn authors(&self) -> Vec<String> {
+        let mut authors: Vec<String> = vec![];
+        for page in self.pages.iter() {
+            if let Some(RemarkableFile::V6 { tree, .. }) = page.file() {
+                let mut page_authors: Vec<(&u16, &String)> = tree.authors().iter().collect();
+                page_authors.sort();
+                for (_, author) in page_authors {
+                    if !authors.contains(author) {
+                        authors.push(author.clone());
+                    }
+                }
+            }
+        }
+        authors
+    }
+
+    /// Name of the template of every page in page order, `None` if it isn't known
+    ///
+    /// The template stored in `.content` is used if there is one, otherwise the line of the page
+    /// in `<uuid>.pagedata`.
+    pub fn page_templates(&self) -> Vec<Option<String>> {
+        let pagedata = self
+            .other_files
+            .get(&format!("{}.pagedata", self.id))
+            .map(|data| String::from_utf8_lossy(data).into_owned())
+            .unwrap_or_default();
+        let mut lines = pagedata.lines();
+        self.content
+            .pages
+            .iter()
+            .map(|page| {
+                let line = lines.next().map(str::trim).filter(|l| !l.is_empty());
+                page.template.clone().or_else(|| line.map(str::to_owned))
+            })
+            .collect()
+    }
+
+    /// Tags of the whole document
+    pub fn tags(&self) -> &[String] {
+        &self.content.tags
+    }
+
+    /// Pages tagged with `tag` in page order, each with its parsed file
+    ///
+    /// The file is `None` for pages without a `.rm` file or one that could not be parsed.
+    /// Tags of the document don't apply to its pages, see [Notebook::tags].
+    pub fn pages_tagged<'a>(
+        &'a self,
+        tag: &'a str,
+    ) -> impl Iterator<Item = (&'a NotebookPage, Option<&'a RemarkableFile>)> + 'a {
+        self.pages
+            .iter()
+            .zip(self.content.pages.iter())
+            .filter(move |(_, content)| content.tags.iter().any(|t| t == tag))
+            .map(|(page, _)| (page, page.file()))
+    }
+
+    /// Pages without a `.rm` file
+    pub fn missing_pages(&self) -> impl Iterator<Item = &NotebookPage> {
+        self.pages
+            .iter()
+            .filter(|p| matches!(p.state, PageState::Missing))
+    }
+
+    /// Pages with a `.rm` file that contains nothing
+    pub fn blank_pages(&self) -> impl Iterator<Item = &NotebookPage> {
+        self.pages
+            .iter()
+            .filter(|p| matches!(p.state, PageState::Blank(_)))
+    }
+
+    /// Pages whose `.rm` file could not be parsed
+    pub fn invalid_pages(&self) -> impl Iterator<Item = &NotebookPage> {
+        self.pages
+            .iter()
+            .filter(|p| matches!(p.state, PageState::Invalid(_)))
+    }
+}
+
+fn read_to_string(path: &Path) -> Result<String, ParseError> {
+    fs::read_to_string(path).map_err(|e| {
+        ParseError::new(
+            format!("Failed to read '{}': {e}", path.display()),
+            ParseErrorKind::Io,
+        )
+    })
+}
+
+/// Read a file that is allowed to not exist
+fn read_optional(path: &Path) -> Result<Option<Vec<u8>>, ParseError> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(ParseError::new(
+            format!("Failed to read '{}': {e}", path.display()),
+            ParseErrorKind::Io,
+        )),
+    }
+}
@@ -0,0 +1,66 @@
+//! `.rmdoc` archives as exchanged by the desktop app and the tablet's USB web interface
+//!
+//! An archive is a zip file holding the files of a single document with the same layout as the
+//! data directory.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    zip::{read_zip, write_zip, ZipEntry},
+    ParseError, ParseLimits,
+};
+
+use super::Notebook;
+
+impl Notebook {
+    /// Read a document from an `.rmdoc` archive
+    pub fn read_rmdoc(data: &[u8]) -> Result<Notebook, ParseError> {
+        Self::read_rmdoc_with_limits(data, ParseLimits::default())
+    }
+
+    /// Read a document from an `.rmdoc` archive, with the sizes of the files in it bounded by
+    /// `limits`
+    pub fn read_rmdoc_with_limits(
+        data: &[u8],
+        limits: ParseLimits,
+    ) -> Result<Notebook, ParseError> {
+        let mut files: BTreeMap<String, Vec<u8>> = read_zip(data, &limits)?
+            .into_iter()
+            .map(|entry| (entry.name, entry.data))
+            .collect();
+
+        let id = files
+            .keys()
+            .find(|name| !name.contains('/') && name.ends_with(".content"))
+            .map(|name| name.trim_end_matches(".content").to_owned())
+            .ok_or_else(|| ParseError::invalid("No .content file found in rmdoc archive"))?;
+
+        let content = files
+            .remove(&format!("{id}.content"))
+            .map(String::from_utf8)
+            .transpose()?
+            .unwrap_or_default();
+        let metadata = files
+            .remove(&format!("{id}.metadata"))
+            .map(String::from_utf8)
+            .transpose()?;
+
+        let mut notebook = Self::from_files(&id, metadata.as_deref(), &content, |page_id| {
+            Ok(files.remove(&format!("{id}/{page_id}.rm")))
+        })?;
+        notebook.other_files = files;
+
+        Ok(notebook)
+    }
+
+    /// Write this document into an `.rmdoc` archive that can be imported by the tablet
+    pub fn to_rmdoc(&self) -> Result<Vec<u8>, ParseError> {
+        let entries: Vec<ZipEntry> = self
+            .files()
+            .into_iter()
+            .map(|(name, data)| ZipEntry { name, data })
+            .collect();
+
+        write_zip(&entries)
+    }
+}
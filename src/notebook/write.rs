@@ -0,0 +1,377 @@
+//! Creating documents and writing them into the tablet's data directory
+//!
+//! New documents use content format version 2. Authors of v6 pages are registered in
+//! `cPages.uuids` of the `.content` file, pages without an [AuthorsIdsBlock] get the document's
+//! author, the same way the tablet does for pages written on it.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    json::JsonValue,
+    uuid,
+    v6::block::{AuthorsIdsBlock, Block},
+    ParseError, ParseErrorKind, RemarkableFile,
+};
+
+use super::{Content, ContentPage, DocumentType, Metadata, Notebook, NotebookPage};
+
+/// Id the tablet gives the author of a document in `cPages.uuids` and in [AuthorsIdsBlock]
+const DOCUMENT_AUTHOR: u16 = 1;
+
+/// Index of the first page in `cPages`
+const FIRST_PAGE_INDEX: &str = "ba";
+
+fn object(entries: Vec<(&str, JsonValue)>) -> JsonValue {
+    JsonValue::Object(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+    )
+}
+
+/// Value with a CRDT timestamp as used in `cPages`
+fn timestamped(timestamp: &str, value: JsonValue) -> JsonValue {
+    object(vec![("timestamp", timestamp.into()), ("value", value)])
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Index sorting right after `index`, indices are compared as strings
+fn next_page_index(index: &str) -> String {
+    match index.chars().last() {
+        Some(last) if last.is_ascii_lowercase() && last != 'z' => {
+            format!("{}{}", &index[..index.len() - 1], (last as u8 + 1) as char)
+        }
+        Some(_) => format!("{index}{FIRST_PAGE_INDEX}"),
+        None => FIRST_PAGE_INDEX.to_owned(),
+    }
+}
+
+impl Metadata {
+    /// Metadata of a new document named `visible_name` in folder `parent`, empty for the root
+    pub fn new(visible_name: &str, parent: &str) -> Metadata {
+        let now = now_millis().to_string();
+        let raw = object(vec![
+            ("createdTime", now.as_str().into()),
+            ("deleted", false.into()),
+            ("lastModified", now.as_str().into()),
+            ("lastOpened", "0".into()),
+            ("lastOpenedPage", 0i64.into()),
+            ("metadatamodified", false.into()),
+            ("modified", false.into()),
+            ("parent", parent.into()),
+            ("pinned", false.into()),
+            ("synced", false.into()),
+            ("type", "DocumentType".into()),
+            ("version", 0i64.into()),
+            ("visibleName", visible_name.into()),
+        ]);
+        Metadata {
+            visible_name: visible_name.to_owned(),
+            doc_type: DocumentType::Document,
+            parent: parent.to_owned(),
+            deleted: false,
+            last_modified: now.parse().ok(),
+            raw,
+        }
+    }
+}
+
+impl Content {
+    /// Content of an empty notebook written by `author`
+    pub fn new_notebook(author: &str) -> Content {
+        let raw = object(vec![
+            (
+                "cPages",
+                object(vec![
+                    ("lastOpened", timestamped("0:0", "".into())),
+                    ("original", timestamped("0:0", (-1i64).into())),
+                    ("pages", JsonValue::Array(vec![])),
+                    (
+                        "uuids",
+                        JsonValue::Array(vec![object(vec![
+                            ("first", author.into()),
+                            ("second", i64::from(DOCUMENT_AUTHOR).into()),
+                        ])]),
+                    ),
+                ]),
+            ),
+            ("coverPageNumber", 0i64.into()),
+            ("documentMetadata", object(vec![])),
+            ("extraMetadata", object(vec![])),
+            ("fileType", "notebook".into()),
+            ("fontName", "".into()),
+            ("formatVersion", 2i64.into()),
+            ("lineHeight", (-1i64).into()),
+            ("margins", 125i64.into()),
+            ("orientation", "portrait".into()),
+            ("pageCount", 0i64.into()),
+            ("pageTags", JsonValue::Array(vec![])),
+            ("tags", JsonValue::Array(vec![])),
+            ("textAlignment", "justify".into()),
+            ("textScale", 1i64.into()),
+            ("zoomMode", "bestFit".into()),
+        ]);
+        Content {
+            file_type: "notebook".to_owned(),
+            format_version: 2,
+            pages: vec![],
+            tags: vec![],
+            raw,
+        }
+    }
+
+    /// Authors listed in `cPages.uuids` by their id
+    pub fn authors(&self) -> BTreeMap<u16, String> {
+        self.raw
+            .get("cPages")
+            .and_then(|c| c.get("uuids"))
+            .and_then(|u| u.as_array())
+            .map(|uuids| {
+                uuids
+                    .iter()
+                    .filter_map(|entry| {
+                        let uuid = entry.get("first")?.as_str()?;
+                        let id = u16::try_from(entry.get("second")?.as_i64()?).ok()?;
+                        Some((id, uuid.to_owned()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Add `author` to `cPages.uuids` if it isn't listed yet and return its id
+    fn add_author(&mut self, author: &str) -> Result<u16, ParseError> {
+        let authors = self.authors();
+        if let Some((id, _)) = authors.iter().find(|(_, uuid)| uuid.as_str() == author) {
+            return Ok(*id);
+        }
+        let id = authors.keys().max().map_or(DOCUMENT_AUTHOR, |id| id + 1);
+        let c_pages = self.c_pages()?;
+        if c_pages.get("uuids").is_none() {
+            c_pages.set("uuids", JsonValue::Array(vec![]));
+        }
+        if let Some(JsonValue::Array(uuids)) = c_pages.get_mut("uuids") {
+            uuids.push(object(vec![
+                ("first", author.into()),
+                ("second", i64::from(id).into()),
+            ]));
+        }
+        Ok(id)
+    }
+
+    /// The `cPages` object, only format version 2 has one
+    fn c_pages(&mut self) -> Result<&mut JsonValue, ParseError> {
+        if self.format_version < 2 {
+            return Err(ParseError::unsupported(format!(
+                "Adding pages to content format version {} is not supported",
+                self.format_version
+            )));
+        }
+        if self.raw.get("cPages").is_none() {
+            self.raw.set("cPages", object(vec![]));
+        }
+        self.raw
+            .get_mut("cPages")
+            .filter(|c| c.as_object().is_some())
+            .ok_or_else(|| ParseError::invalid("Expected cPages to be an object"))
+    }
+
+    /// Append a page to [Content::pages] and `cPages`
+    fn push_page(&mut self, page: ContentPage) -> Result<(), ParseError> {
+        let c_pages = self.c_pages()?;
+        if c_pages.get("pages").is_none() {
+            c_pages.set("pages", JsonValue::Array(vec![]));
+        }
+        let pages = match c_pages.get_mut("pages") {
+            Some(JsonValue::Array(pages)) => pages,
+            _ => return Err(ParseError::invalid("Expected cPages.pages to be an array")),
+        };
+
+        // deleted pages keep their index so they are included here
+        let last_index = pages
+            .iter()
+            .filter_map(|p| p.get("idx")?.get("value")?.as_str())
+            .max()
+            .unwrap_or_default()
+            .to_owned();
+        let clock = pages
+            .iter()
+            .flat_map(|p| p.as_object().into_iter().flatten())
+            .filter_map(|(_, value)| value.get("timestamp")?.as_str())
+            .filter_map(|timestamp| timestamp.split(':').nth(1)?.parse::<u64>().ok())
+            .max()
+            .unwrap_or_default()
+            + 1;
+        let timestamp = format!("{DOCUMENT_AUTHOR}:{clock}");
+
+        let mut entry = vec![
+            ("id", page.id.as_str().into()),
+            (
+                "idx",
+                timestamped(&timestamp, next_page_index(&last_index).into()),
+            ),
+        ];
+        if let Some(template) = &page.template {
+            entry.push((
+                "template",
+                timestamped(&timestamp, template.as_str().into()),
+            ));
+        }
+        pages.push(object(entry));
+
+        self.pages.push(page);
+        self.raw.set("pageCount", (self.pages.len() as i64).into());
+        Ok(())
+    }
+}
+
+impl Notebook {
+    /// Create an empty notebook named `name` in the root folder
+    ///
+    /// A new id and author are generated, pages are added with [Notebook::add_page]
+    pub fn new(name: &str) -> Notebook {
+        let id = uuid::new_v4();
+        let mut other_files = BTreeMap::new();
+        other_files.insert(format!("{id}.pagedata"), vec![]);
+        Notebook {
+            metadata: Some(Metadata::new(name, "")),
+            content: Content::new_notebook(&uuid::new_v4()),
+            pages: vec![],
+            other_files,
+            id,
+        }
+    }
+
+    /// Append a page with a new id, `None` adds a page nothing was written on yet
+    ///
+    /// v6 files without [AuthorsIdsBlock] are attributed to the author of the document, authors
+    /// of other v6 files are added to the document in the order of their ids in the page. The
+    /// page keeps its own ids, like on the tablet they only name authors within the page since
+    /// every item id of the page starts with them, so `cPages.uuids` can list an author under
+    /// another id. Pages of other versions are written as they are. `template` is stored in `.content` and `.pagedata`, `Blank` if not given.
+    pub fn add_page(
+        &mut self,
+        file: Option<RemarkableFile>,
+        template: Option<&str>,
+    ) -> Result<&NotebookPage, ParseError> {
+        let template = template.unwrap_or("Blank");
+        let id = uuid::new_v4();
+
+        let data = match file {
+            Some(RemarkableFile::V6 { tree, mut blocks }) => {
+                let authors = blocks.iter().find_map(|block| match block {
+                    Block::AuthorsIds(block) => Some(block.authors.clone()),
+                    _ => None,
+                });
+                match authors {
+                    Some(authors) => {
+                        let mut authors: Vec<_> = authors.iter().collect();
+                        authors.sort();
+                        for (_, uuid) in authors {
+                            self.content.add_author(uuid)?;
+                        }
+                    }
+                    None => {
+                        let author = match self.content.authors().get(&DOCUMENT_AUTHOR) {
+                            Some(author) => author.clone(),
+                            None => {
+                                let author = uuid::new_v4();
+                                self.content.add_author(&author)?;
+                                author
+                            }
+                        };
+                        let authors = [(DOCUMENT_AUTHOR, author)].into_iter().collect();
+                        blocks.insert(0, Block::AuthorsIds(AuthorsIdsBlock { authors }));
+                    }
+                }
+                Some(RemarkableFile::V6 { tree, blocks }.to_bytes()?)
+            }
+            Some(file) => Some(file.to_bytes()?),
+            None => None,
+        };
+
+        self.content.push_page(ContentPage {
+            id: id.clone(),
+            redirect: None,
+            template: Some(template.to_owned()),
+            tags: vec![],
+        })?;
+        let pagedata = self
+            .other_files
+            .entry(format!("{}.pagedata", self.id))
+            .or_default();
+        pagedata.extend_from_slice(format!("{template}\n").as_bytes());
+        if let Some(metadata) = &mut self.metadata {
+            metadata.last_modified = Some(now_millis());
+        }
+
+        self.pages.push(NotebookPage::new(id, data));
+        Ok(&self.pages[self.pages.len() - 1])
+    }
+
+    /// All files of this document keyed by their path relative to the data directory
+    pub fn files(&self) -> BTreeMap<String, Vec<u8>> {
+        let id = &self.id;
+        let metadata = match &self.metadata {
+            Some(metadata) => metadata.to_json(),
+            None => object(vec![
+                ("parent", "".into()),
+                ("type", "DocumentType".into()),
+                ("visibleName", id.as_str().into()),
+            ]),
+        };
+
+        let mut files = self.other_files.clone();
+        files.insert(
+            format!("{id}.content"),
+            self.content.raw.to_string_pretty().into_bytes(),
+        );
+        files.insert(
+            format!("{id}.metadata"),
+            metadata.to_string_pretty().into_bytes(),
+        );
+        for page in self.pages.iter() {
+            if let Some(data) = &page.data {
+                files.insert(format!("{id}/{}.rm", page.id), data.clone());
+            }
+        }
+        files
+    }
+
+    /// Write this document into the tablet's data directory `data_dir`, replacing the files of
+    /// a document with the same id
+    pub fn write(&self, data_dir: impl AsRef<Path>) -> Result<(), ParseError> {
+        let data_dir = data_dir.as_ref();
+        for (name, data) in self.files() {
+            // names come from archives as well, don't write outside of the data directory
+            if name.split('/').any(|part| part.is_empty() || part == "..") {
+                return Err(ParseError::invalid(format!("Invalid file name '{name}'")));
+            }
+            let path = data_dir.join(&name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| write_error(parent, e))?;
+            }
+            fs::write(&path, data).map_err(|e| write_error(&path, e))?;
+        }
+        Ok(())
+    }
+}
+
+fn write_error(path: &Path, e: std::io::Error) -> ParseError {
+    ParseError::new(
+        format!("Failed to write '{}': {e}", path.display()),
+        ParseErrorKind::Io,
+    )
+}
@@ -1,6 +1,6 @@
-use crate::{bitreader::Readable, ParseError};
+use crate::{bitreader::Readable, bitwriter::Bitwriter, ParseError};
 
-use super::{line::Line, Parse};
+use super::{line::Line, Parse, Write};
 
 #[derive(Debug)]
 pub struct Layer {
@@ -13,9 +13,21 @@ impl Parse for Layer {
         reader: &mut crate::Bitreader<impl Readable>,
     ) -> Result<Self, crate::ParseError> {
         let amount_lines = reader.read_u32()?;
+        // a line without points is still at least 5 values
+        reader.check_count(amount_lines as u64, 20)?;
         let lines = (0..amount_lines)
             .map(|_| Line::parse(version, reader))
             .collect::<Result<Vec<Line>, ParseError>>()?;
         Ok(Layer { lines })
     }
 }
+
+impl Write for Layer {
+    fn write(&self, version: u32, writer: &mut Bitwriter) -> Result<(), ParseError> {
+        writer.write_u32(u32::try_from(self.lines.len())?);
+        for line in self.lines.iter() {
+            line.write(version, writer)?;
+        }
+        Ok(())
+    }
+}
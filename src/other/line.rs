@@ -1,10 +1,11 @@
 use crate::bitreader::Readable;
+use crate::bitwriter::Bitwriter;
 use crate::shared::pen_color::PenColor;
 use crate::shared::tool::Tool;
 use crate::ParseError;
 
 use super::point::Point;
-use super::Parse;
+use super::{Parse, Write};
 
 #[derive(Debug)]
 pub struct Line {
@@ -12,6 +13,10 @@ pub struct Line {
     pub tool: Tool,
     pub color: PenColor,
     pub brush_size: f32,
+    /// Unknown value stored in front of the brush size, kept to write it back
+    pub unknown_line_attribute: u32,
+    /// Unknown value stored after the brush size by version 5 and up, 0 before that
+    pub unknown_line_attribute_v5: u32,
 }
 
 impl Parse for Line {
@@ -19,19 +24,27 @@ impl Parse for Line {
         version: u32,
         reader: &mut crate::Bitreader<impl Readable>,
     ) -> Result<Self, crate::ParseError> {
-        let tool = Tool::try_from(reader.read_u32()?)?;
-        let color = PenColor::try_from(reader.read_u32()?)?;
-        reader.read_u32()?; // Skip unknown value
+        let tool = Tool::from(reader.read_u32()?);
+        let color = PenColor::from(reader.read_u32()?);
+        let unknown_line_attribute = reader.read_u32()?;
         let brush_size = reader.read_f32()?;
-        if version >= 5 {
-            reader.read_u32()?; // Skip unkown value
-        }
+        let unknown_line_attribute_v5 = if version >= 5 { reader.read_u32()? } else { 0 };
         let amount_points = reader.read_u32()?;
+        let max_points = reader.limits().max_points_per_line;
+        if amount_points > max_points {
+            return Err(ParseError::limit_exceeded(format!(
+                "Line with {amount_points} points exceeds limit of {max_points}"
+            )));
+        }
+        // every point is 6 floats
+        reader.check_count(amount_points as u64, 24)?;
 
         Ok(Line {
             tool,
             color,
             brush_size,
+            unknown_line_attribute,
+            unknown_line_attribute_v5,
             points: (0..amount_points)
                 .map(|_| {
                     Ok(Point {
@@ -47,3 +60,20 @@ impl Parse for Line {
         })
     }
 }
+
+impl Write for Line {
+    fn write(&self, version: u32, writer: &mut Bitwriter) -> Result<(), ParseError> {
+        writer.write_u32(u32::from(self.tool));
+        writer.write_u32(u32::from(self.color));
+        writer.write_u32(self.unknown_line_attribute);
+        writer.write_f32(self.brush_size);
+        if version >= 5 {
+            writer.write_u32(self.unknown_line_attribute_v5);
+        }
+        writer.write_u32(u32::try_from(self.points.len())?);
+        for point in self.points.iter() {
+            point.write(version, writer)?;
+        }
+        Ok(())
+    }
+}
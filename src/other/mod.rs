@@ -1,4 +1,4 @@
-use crate::{bitreader::Readable, Bitreader, ParseError};
+use crate::{bitreader::Readable, bitwriter::Bitwriter, Bitreader, ParseError};
 
 pub mod layer;
 pub mod line;
@@ -12,3 +12,8 @@ pub trait Parse {
     where
         Self: Sized;
 }
+
+/// Counterpart of [Parse], writes the layout of `version`
+pub trait Write {
+    fn write(&self, version: u32, writer: &mut Bitwriter) -> Result<(), ParseError>;
+}
@@ -1,6 +1,6 @@
-use crate::{bitreader::Readable, ParseError};
+use crate::{bitreader::Readable, bitwriter::Bitwriter, ParseError};
 
-use super::{layer::Layer, Parse};
+use super::{layer::Layer, Parse, Write};
 
 #[derive(Debug)]
 pub struct Page {
@@ -13,6 +13,7 @@ impl Parse for Page {
         reader: &mut crate::Bitreader<impl Readable>,
     ) -> Result<Self, crate::ParseError> {
         let amount_layers = reader.read_u32()?;
+        reader.check_count(amount_layers as u64, 4)?;
 
         Ok(Page {
             layers: (0..amount_layers)
@@ -21,3 +22,13 @@ impl Parse for Page {
         })
     }
 }
+
+impl Write for Page {
+    fn write(&self, version: u32, writer: &mut Bitwriter) -> Result<(), ParseError> {
+        writer.write_u32(u32::try_from(self.layers.len())?);
+        for layer in self.layers.iter() {
+            layer.write(version, writer)?;
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
-use crate::bitreader::Readable;
+use crate::{bitreader::Readable, bitwriter::Bitwriter, ParseError};
 
-use super::Parse;
+use super::{Parse, Write};
 
 #[derive(Debug)]
 pub struct Point {
@@ -27,3 +27,15 @@ impl Parse for Point {
         })
     }
 }
+
+impl Write for Point {
+    fn write(&self, _version: u32, writer: &mut Bitwriter) -> Result<(), ParseError> {
+        writer.write_f32(self.x);
+        writer.write_f32(self.y);
+        writer.write_f32(self.speed);
+        writer.write_f32(self.direction);
+        writer.write_f32(self.width);
+        writer.write_f32(self.pressure);
+        Ok(())
+    }
+}
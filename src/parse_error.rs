@@ -14,6 +14,8 @@ pub enum ParseErrorKind {
     Io,
     InvalidInput,
     Unsupported,
+    /// Input exceeded one of the configured [crate::ParseLimits]
+    LimitExceeded,
 }
 
 #[derive(Debug)]
@@ -40,6 +42,10 @@ impl ParseError {
         Self::new(message, ParseErrorKind::Unsupported)
     }
 
+    pub fn limit_exceeded<S: Into<String>>(message: S) -> ParseError {
+        Self::new(message, ParseErrorKind::LimitExceeded)
+    }
+
     /// Add extra context from the bitreader used to parse something
     pub fn with_context_from_bitreader(
         mut self,
@@ -70,9 +76,8 @@ impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Error while parsing remarkable file {}. {}",
-            self.message,
-            format!("\n{}", self.context)
+            "Error while parsing remarkable file {}. \n{}",
+            self.message, self.context
         )
     }
 }
@@ -0,0 +1,33 @@
+/// Upper bounds the parser enforces on values read from a file
+///
+/// Lengths and counts inside a file are never trusted on their own, anything exceeding these
+/// limits results in a [crate::ParseError] with [crate::ParseErrorKind::LimitExceeded] instead
+/// of a large allocation. The defaults are well above anything the tablet produces.
+#[derive(Debug, Clone)]
+pub struct ParseLimits {
+    /// Maximum size in bytes of a single v6 block
+    pub max_block_size: u32,
+    /// Maximum amount of points in a single line
+    pub max_points_per_line: u32,
+    /// Maximum length in bytes of a single string
+    pub max_string_length: u32,
+    /// Maximum depth of nested groups in a v6 scene tree
+    pub max_nesting_depth: u32,
+    /// Maximum size in bytes of a single file inside an `.rmdoc` archive
+    pub max_archive_entry_size: u32,
+    /// Maximum size in bytes of all files inside an `.rmdoc` archive together
+    pub max_archive_size: u64,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self {
+            max_block_size: 64 * 1024 * 1024,
+            max_points_per_line: 1024 * 1024,
+            max_string_length: 16 * 1024 * 1024,
+            max_nesting_depth: 256,
+            max_archive_entry_size: 256 * 1024 * 1024,
+            max_archive_size: 512 * 1024 * 1024,
+        }
+    }
+}
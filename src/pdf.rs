@@ -0,0 +1,1145 @@
+//! Reading and writing of PDF files as needed to draw on top of the documents shown on the tablet
+//!
+//! A file is read into its objects, following cross reference tables and streams, object streams
+//! and incremental updates. Writing always produces a complete new file with a classic cross
+//! reference table that only contains the objects still in use. Encrypted files are not supported.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{compression::zlib_decompress, ParseError, ParseErrorKind};
+
+/// Upper bound for a single decompressed stream
+const MAX_STREAM_SIZE: usize = 256 * 1024 * 1024;
+/// Nesting depth of arrays, dictionaries and page tree nodes after which parsing is stopped
+const MAX_DEPTH: u32 = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PdfObject {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    /// Raw bytes of a literal or hex string
+    String(Vec<u8>),
+    /// Name without the leading `/`, escapes decoded
+    Name(Vec<u8>),
+    Array(Vec<PdfObject>),
+    Dictionary(Dictionary),
+    /// Dictionary and still encoded data of a stream
+    Stream(Dictionary, Vec<u8>),
+    /// Reference to an object by its number, generations are dropped when reading
+    Reference(u32),
+}
+
+impl PdfObject {
+    pub fn name(name: &str) -> PdfObject {
+        PdfObject::Name(name.as_bytes().to_vec())
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            PdfObject::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            PdfObject::Integer(i) => Some(*i as f64),
+            PdfObject::Real(r) => Some(*r),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&[u8]> {
+        match self {
+            PdfObject::Name(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<PdfObject>> {
+        match self {
+            PdfObject::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// Dictionary of a dictionary or stream
+    pub fn as_dictionary(&self) -> Option<&Dictionary> {
+        match self {
+            PdfObject::Dictionary(d) | PdfObject::Stream(d, _) => Some(d),
+            _ => None,
+        }
+    }
+}
+
+/// Dictionary keeping the order of its keys
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Dictionary(pub Vec<(Vec<u8>, PdfObject)>);
+
+impl Dictionary {
+    pub fn get(&self, key: &str) -> Option<&PdfObject> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key.as_bytes())
+            .map(|(_, v)| v)
+    }
+
+    /// Set `key` to `value`, replacing the existing value at the same position
+    pub fn set(&mut self, key: &str, value: PdfObject) {
+        match self.0.iter_mut().find(|(k, _)| k == key.as_bytes()) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((key.as_bytes().to_vec(), value)),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<PdfObject> {
+        let index = self.0.iter().position(|(k, _)| k == key.as_bytes())?;
+        Some(self.0.remove(index).1)
+    }
+
+    /// True if `/Type` is the name `type_name`
+    pub fn has_type(&self, type_name: &str) -> bool {
+        self.get("Type").and_then(|t| t.as_name()) == Some(type_name.as_bytes())
+    }
+}
+
+/// Location of an object as given by a cross reference section
+#[derive(Debug, Clone, Copy)]
+enum XrefEntry {
+    Free,
+    Offset(usize),
+    /// Object number of the object stream and index inside of it
+    Compressed(u32, usize),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PdfDocument {
+    pub objects: BTreeMap<u32, PdfObject>,
+    pub trailer: Dictionary,
+}
+
+impl PdfDocument {
+    /// Document without any pages
+    pub fn new() -> PdfDocument {
+        let mut document = PdfDocument::default();
+        let mut pages = Dictionary::default();
+        pages.set("Type", PdfObject::name("Pages"));
+        pages.set("Kids", PdfObject::Array(vec![]));
+        pages.set("Count", PdfObject::Integer(0));
+        let pages = document.add(PdfObject::Dictionary(pages));
+        let mut catalog = Dictionary::default();
+        catalog.set("Type", PdfObject::name("Catalog"));
+        catalog.set("Pages", pages);
+        let root = document.add(PdfObject::Dictionary(catalog));
+        document.trailer.set("Root", root);
+        document
+    }
+
+    pub fn parse(data: &[u8]) -> Result<PdfDocument, ParseError> {
+        if !data.starts_with(b"%PDF-") {
+            return Err(ParseError::invalid("Not a pdf file"));
+        }
+
+        let document = read_xref_sections(data)
+            .and_then(|(entries, trailer)| Self::load(data, entries, trailer, false));
+        match document {
+            Err(e) if e.kind == ParseErrorKind::Unsupported => Err(e),
+            // damaged cross reference, find the objects by scanning the whole file
+            Err(_) => {
+                let (entries, trailer) = scan_objects(data)?;
+                Self::load(data, entries, trailer, true)
+            }
+            document => document,
+        }
+    }
+
+    /// Read all objects listed in `entries`, objects that can't be read are skipped if `lenient`
+    fn load(
+        data: &[u8],
+        entries: BTreeMap<u32, XrefEntry>,
+        trailer: Dictionary,
+        lenient: bool,
+    ) -> Result<PdfDocument, ParseError> {
+        if trailer.get("Encrypt").is_some() {
+            return Err(ParseError::unsupported(
+                "Encrypted pdf files are not supported",
+            ));
+        }
+
+        let offsets: HashMap<u32, usize> = entries
+            .iter()
+            .filter_map(|(number, entry)| match entry {
+                XrefEntry::Offset(offset) => Some((*number, *offset)),
+                _ => None,
+            })
+            .collect();
+        let mut objects = BTreeMap::new();
+        for (number, offset) in offsets.iter() {
+            match parse_indirect_object(data, *offset, &offsets) {
+                Ok((read_number, object)) if read_number == *number => {
+                    objects.insert(*number, object);
+                }
+                _ if lenient => (),
+                Ok(_) => {
+                    return Err(ParseError::invalid(format!(
+                        "Expected pdf object {number} at offset {offset}"
+                    )))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut object_streams: HashMap<u32, Vec<PdfObject>> = HashMap::new();
+        for (number, entry) in entries.iter() {
+            if let XrefEntry::Compressed(stream_number, index) = entry {
+                if !object_streams.contains_key(stream_number) {
+                    let stream = objects
+                        .get(stream_number)
+                        .ok_or_else(|| {
+                            ParseError::invalid(format!(
+                                "Missing pdf object stream {stream_number}"
+                            ))
+                        })
+                        .and_then(parse_object_stream);
+                    match stream {
+                        Ok(stream) => object_streams.insert(*stream_number, stream),
+                        Err(_) if lenient => object_streams.insert(*stream_number, vec![]),
+                        Err(e) => return Err(e),
+                    };
+                }
+                if let Some(object) = object_streams[stream_number].get(*index) {
+                    objects.insert(*number, object.clone());
+                }
+            }
+        }
+
+        // objects that only held other objects or cross references are rebuilt when writing
+        objects.retain(|_, object| {
+            !matches!(object, PdfObject::Stream(d, _) if d.has_type("ObjStm") || d.has_type("XRef"))
+        });
+
+        Ok(PdfDocument { objects, trailer })
+    }
+
+    /// Follow `object` if it is a reference
+    pub fn resolve<'a>(&'a self, object: &'a PdfObject) -> &'a PdfObject {
+        match object {
+            PdfObject::Reference(number) => self.objects.get(number).unwrap_or(&PdfObject::Null),
+            object => object,
+        }
+    }
+
+    /// Add `object` under a new object number and return a reference to it
+    pub fn add(&mut self, object: PdfObject) -> PdfObject {
+        let number = self.objects.keys().next_back().map(|n| n + 1).unwrap_or(1);
+        self.objects.insert(number, object);
+        PdfObject::Reference(number)
+    }
+
+    /// Document catalog, the object `/Root` of the trailer refers to
+    pub fn catalog(&self) -> Result<&Dictionary, ParseError> {
+        self.trailer
+            .get("Root")
+            .map(|root| self.resolve(root))
+            .and_then(|root| root.as_dictionary())
+            .ok_or_else(|| ParseError::invalid("Pdf file has no document catalog"))
+    }
+
+    /// Object numbers and dictionaries of all pages in order
+    ///
+    /// Attributes pages inherit from the page tree (`Resources`, `MediaBox`, `CropBox` and
+    /// `Rotate`) are copied into the returned dictionaries.
+    pub fn pages(&self) -> Result<Vec<(u32, Dictionary)>, ParseError> {
+        let root = self
+            .catalog()?
+            .get("Pages")
+            .ok_or_else(|| ParseError::invalid("Pdf document catalog has no pages"))?;
+        let mut pages = vec![];
+        let mut visited = HashSet::new();
+        self.collect_pages(root, &Dictionary::default(), &mut pages, &mut visited, 0)?;
+        Ok(pages)
+    }
+
+    fn collect_pages(
+        &self,
+        node: &PdfObject,
+        inherited: &Dictionary,
+        pages: &mut Vec<(u32, Dictionary)>,
+        visited: &mut HashSet<u32>,
+        depth: u32,
+    ) -> Result<(), ParseError> {
+        if depth > MAX_DEPTH {
+            return Err(ParseError::invalid("Pdf page tree nested too deep"));
+        }
+        let number = match node {
+            PdfObject::Reference(number) => *number,
+            _ => return Ok(()),
+        };
+        if !visited.insert(number) {
+            return Ok(());
+        }
+        let dictionary = match self.resolve(node).as_dictionary() {
+            Some(dictionary) => dictionary,
+            None => return Ok(()),
+        };
+
+        let mut attributes = inherited.clone();
+        for key in ["Resources", "MediaBox", "CropBox", "Rotate"] {
+            if let Some(value) = dictionary.get(key) {
+                attributes.set(key, value.clone());
+            }
+        }
+
+        match dictionary.get("Kids").map(|kids| self.resolve(kids)) {
+            Some(PdfObject::Array(kids)) if !dictionary.has_type("Page") => {
+                for kid in kids {
+                    self.collect_pages(kid, &attributes, pages, visited, depth + 1)?;
+                }
+            }
+            _ => {
+                let mut page = dictionary.clone();
+                for (key, value) in attributes.0 {
+                    page.0.retain(|(k, _)| *k != key);
+                    page.0.push((key, value));
+                }
+                pages.push((number, page));
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize all objects reachable from the trailer into a new file
+    pub fn write(&self) -> Vec<u8> {
+        let mut trailer = Dictionary::default();
+        for key in ["Root", "Info", "ID"] {
+            if let Some(value) = self.trailer.get(key) {
+                trailer.set(key, value.clone());
+            }
+        }
+
+        // only write objects that are still referenced
+        let mut reachable = HashSet::new();
+        let mut pending: Vec<&PdfObject> = trailer.0.iter().map(|(_, v)| v).collect();
+        while let Some(object) = pending.pop() {
+            match object {
+                PdfObject::Reference(number) => {
+                    if reachable.insert(*number) {
+                        if let Some(object) = self.objects.get(number) {
+                            pending.push(object);
+                        }
+                    }
+                }
+                PdfObject::Array(items) => pending.extend(items),
+                PdfObject::Dictionary(d) | PdfObject::Stream(d, _) => {
+                    pending.extend(d.0.iter().map(|(_, v)| v))
+                }
+                _ => (),
+            }
+        }
+
+        let mut out = b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = BTreeMap::new();
+        for (number, object) in self.objects.iter() {
+            if !reachable.contains(number) {
+                continue;
+            }
+            offsets.insert(*number, out.len());
+            out.extend_from_slice(format!("{number} 0 obj\n").as_bytes());
+            write_object(&mut out, object);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+
+        let size = self.objects.keys().next_back().map(|n| n + 1).unwrap_or(1);
+        let xref_offset = out.len();
+        out.extend_from_slice(format!("xref\n0 {size}\n").as_bytes());
+        for number in 0..size {
+            match offsets.get(&number) {
+                Some(offset) => {
+                    out.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes())
+                }
+                None => out.extend_from_slice(b"0000000000 65535 f \n"),
+            }
+        }
+        trailer.set("Size", PdfObject::Integer(size as i64));
+        out.extend_from_slice(b"trailer\n");
+        write_object(&mut out, &PdfObject::Dictionary(trailer));
+        out.extend_from_slice(format!("\nstartxref\n{xref_offset}\n%%EOF\n").as_bytes());
+        out
+    }
+}
+
+/// Decode the data of a stream, only `FlateDecode` with optional png predictors is supported
+pub fn decode_stream(dictionary: &Dictionary, data: &[u8]) -> Result<Vec<u8>, ParseError> {
+    let filters = match dictionary.get("Filter") {
+        None => vec![],
+        Some(PdfObject::Name(name)) => vec![name.clone()],
+        Some(PdfObject::Array(names)) => names
+            .iter()
+            .filter_map(|n| n.as_name().map(|n| n.to_vec()))
+            .collect(),
+        Some(_) => return Err(ParseError::invalid("Invalid pdf stream filter")),
+    };
+    let parameters = match dictionary.get("DecodeParms") {
+        Some(PdfObject::Array(parameters)) => parameters.first(),
+        parameters => parameters,
+    }
+    .and_then(|p| p.as_dictionary());
+
+    match &filters[..] {
+        [] => Ok(data.to_vec()),
+        [filter] if filter == b"FlateDecode" => {
+            let data = zlib_decompress(data, MAX_STREAM_SIZE)?;
+            match parameters {
+                Some(parameters) => unpredict(parameters, data),
+                None => Ok(data),
+            }
+        }
+        _ => Err(ParseError::unsupported("Unsupported pdf stream filter")),
+    }
+}
+
+/// Undo png predictors applied before compressing
+fn unpredict(parameters: &Dictionary, data: Vec<u8>) -> Result<Vec<u8>, ParseError> {
+    let get = |key: &str, default: i64| {
+        parameters
+            .get(key)
+            .and_then(|v| v.as_i64())
+            .unwrap_or(default)
+    };
+    let predictor = get("Predictor", 1);
+    if predictor < 10 {
+        if predictor != 1 {
+            return Err(ParseError::unsupported("Unsupported pdf tiff predictor"));
+        }
+        return Ok(data);
+    }
+    let bits_per_pixel = (get("Colors", 1) * get("BitsPerComponent", 8)).max(1) as usize;
+    let pixel_size = ((bits_per_pixel + 7) / 8).max(1);
+    let row_size = (get("Columns", 1).max(1) as usize * bits_per_pixel + 7) / 8;
+
+    let mut out = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_size];
+    for chunk in data.chunks(row_size + 1) {
+        let (filter, row) = chunk.split_first().unwrap_or((&0, &[]));
+        let mut row = row.to_vec();
+        for i in 0..row.len() {
+            let left = if i >= pixel_size {
+                row[i - pixel_size]
+            } else {
+                0
+            };
+            let up = previous[i];
+            let up_left = if i >= pixel_size {
+                previous[i - pixel_size]
+            } else {
+                0
+            };
+            row[i] = row[i].wrapping_add(match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(ParseError::invalid("Invalid png predictor")),
+            });
+        }
+        previous[..row.len()].copy_from_slice(&row);
+        out.extend(row);
+    }
+    Ok(out)
+}
+
+pub(crate) fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_up_left = (estimate - up_left as i16).abs();
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Read the newest cross reference section and all older ones it points to
+fn read_xref_sections(data: &[u8]) -> Result<(BTreeMap<u32, XrefEntry>, Dictionary), ParseError> {
+    let tail_start = data.len().saturating_sub(1024);
+    let start = find_last(&data[tail_start..], b"startxref")
+        .map(|i| tail_start + i + b"startxref".len())
+        .ok_or_else(|| ParseError::invalid("Pdf file has no startxref"))?;
+    let mut lexer = Lexer::new(data, start);
+    let mut offset = lexer
+        .parse_object()?
+        .as_i64()
+        .and_then(|o| usize::try_from(o).ok());
+
+    let mut entries = BTreeMap::new();
+    let mut trailer: Option<Dictionary> = None;
+    let mut visited = HashSet::new();
+    while let Some(current) = offset {
+        if !visited.insert(current) {
+            break;
+        }
+        let section_trailer = read_xref_section(data, current, &mut entries)?;
+        // hybrid files keep the objects of object streams in an additional xref stream
+        if let Some(stream_offset) = section_trailer.get("XRefStm").and_then(|o| o.as_i64()) {
+            if let Ok(stream_offset) = usize::try_from(stream_offset) {
+                read_xref_section(data, stream_offset, &mut entries)?;
+            }
+        }
+        offset = section_trailer
+            .get("Prev")
+            .and_then(|o| o.as_i64())
+            .and_then(|o| usize::try_from(o).ok());
+        if trailer.is_none() {
+            trailer = Some(section_trailer);
+        }
+    }
+
+    let trailer = trailer.ok_or_else(|| ParseError::invalid("Pdf file has no trailer"))?;
+    Ok((entries, trailer))
+}
+
+/// Add the entries of the section at `offset` that are not known yet and return its trailer
+fn read_xref_section(
+    data: &[u8],
+    offset: usize,
+    entries: &mut BTreeMap<u32, XrefEntry>,
+) -> Result<Dictionary, ParseError> {
+    let mut lexer = Lexer::new(data, offset);
+    lexer.skip_whitespace();
+    if !lexer.consume_keyword(b"xref") {
+        // cross reference stream
+        let (_, stream) = parse_indirect_object(data, offset, &HashMap::new())?;
+        return match stream {
+            PdfObject::Stream(dictionary, encoded) if dictionary.has_type("XRef") => {
+                read_xref_stream(&dictionary, &encoded, entries)?;
+                Ok(dictionary)
+            }
+            _ => Err(ParseError::invalid("Invalid pdf cross reference")),
+        };
+    }
+
+    loop {
+        lexer.skip_whitespace();
+        if lexer.consume_keyword(b"trailer") {
+            break;
+        }
+        let first = lexer.parse_unsigned()?;
+        let count = lexer.parse_unsigned()?;
+        for number in first..first.saturating_add(count) {
+            let entry_offset = lexer.parse_unsigned()?;
+            lexer.parse_unsigned()?;
+            lexer.skip_whitespace();
+            let kind = lexer.next_byte();
+            let entry = match kind {
+                Some(b'n') => XrefEntry::Offset(entry_offset as usize),
+                Some(b'f') => XrefEntry::Free,
+                _ => return Err(ParseError::invalid("Invalid pdf cross reference entry")),
+            };
+            entries.entry(number as u32).or_insert(entry);
+        }
+    }
+    match lexer.parse_object()? {
+        PdfObject::Dictionary(trailer) => Ok(trailer),
+        _ => Err(ParseError::invalid("Invalid pdf trailer")),
+    }
+}
+
+fn read_xref_stream(
+    dictionary: &Dictionary,
+    encoded: &[u8],
+    entries: &mut BTreeMap<u32, XrefEntry>,
+) -> Result<(), ParseError> {
+    let data = decode_stream(dictionary, encoded)?;
+    let widths: Vec<usize> = dictionary
+        .get("W")
+        .and_then(|w| w.as_array())
+        .map(|w| {
+            w.iter()
+                .filter_map(|w| w.as_i64())
+                .map(|w| w.max(0) as usize)
+                .collect()
+        })
+        .unwrap_or_default();
+    if widths.len() != 3 || widths.iter().any(|w| *w > 8) {
+        return Err(ParseError::invalid(
+            "Invalid pdf cross reference stream widths",
+        ));
+    }
+    let size = dictionary.get("Size").and_then(|s| s.as_i64()).unwrap_or(0);
+    let index: Vec<i64> = match dictionary.get("Index").and_then(|i| i.as_array()) {
+        Some(index) => index.iter().filter_map(|i| i.as_i64()).collect(),
+        None => vec![0, size],
+    };
+
+    let entry_size: usize = widths.iter().sum();
+    let mut records = data.chunks_exact(entry_size.max(1));
+    let read_field = |record: &[u8], start: usize, width: usize| {
+        record[start..start + width]
+            .iter()
+            .fold(0u64, |value, byte| (value << 8) | *byte as u64)
+    };
+    for range in index.chunks_exact(2) {
+        for number in range[0]..range[0].saturating_add(range[1]) {
+            let record = match records.next() {
+                Some(record) => record,
+                None => return Ok(()),
+            };
+            // a missing type field means type 1
+            let kind = if widths[0] == 0 {
+                1
+            } else {
+                read_field(record, 0, widths[0])
+            };
+            let second = read_field(record, widths[0], widths[1]);
+            let third = read_field(record, widths[0] + widths[1], widths[2]);
+            let entry = match kind {
+                0 => XrefEntry::Free,
+                1 => XrefEntry::Offset(second as usize),
+                2 => XrefEntry::Compressed(second as u32, third as usize),
+                _ => continue,
+            };
+            entries.entry(number as u32).or_insert(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Recover the objects of a file with a damaged cross reference by searching for `obj` keywords
+fn scan_objects(data: &[u8]) -> Result<(BTreeMap<u32, XrefEntry>, Dictionary), ParseError> {
+    let mut entries = BTreeMap::new();
+    let mut trailer = None;
+    let mut position = 0;
+    while let Some(found) = find(&data[position..], b" obj") {
+        let keyword = position + found;
+        position = keyword + 4;
+        // walk back over "<number> <generation>"
+        let mut start = keyword;
+        for _ in 0..2 {
+            while start > 0 && data[start - 1].is_ascii_digit() {
+                start -= 1;
+            }
+            if start > 0 && data[start - 1] == b' ' {
+                start -= 1;
+            }
+        }
+        while start < keyword && data[start] == b' ' {
+            start += 1;
+        }
+        let mut lexer = Lexer::new(data, start);
+        if let (Ok(number), Ok(_)) = (lexer.parse_unsigned(), lexer.parse_unsigned()) {
+            // later definitions replace earlier ones as in incremental updates
+            entries.insert(number as u32, XrefEntry::Offset(start));
+        }
+    }
+
+    if let Some(start) = find_last(data, b"trailer") {
+        if let Ok(PdfObject::Dictionary(d)) = Lexer::new(data, start + 7).parse_object() {
+            trailer = Some(d);
+        }
+    }
+    if trailer.is_none() {
+        // files with cross reference streams keep their trailer in the stream dictionary
+        let offsets: HashMap<u32, usize> = HashMap::new();
+        trailer = entries.iter().rev().find_map(|(_, entry)| match entry {
+            XrefEntry::Offset(offset) => match parse_indirect_object(data, *offset, &offsets) {
+                Ok((_, PdfObject::Stream(d, _))) if d.has_type("XRef") => Some(d),
+                _ => None,
+            },
+            _ => None,
+        });
+    }
+
+    let trailer = trailer.ok_or_else(|| ParseError::invalid("Pdf file has no trailer"))?;
+    Ok((entries, trailer))
+}
+
+fn parse_object_stream(stream: &PdfObject) -> Result<Vec<PdfObject>, ParseError> {
+    let (dictionary, encoded) = match stream {
+        PdfObject::Stream(dictionary, encoded) => (dictionary, encoded),
+        _ => return Err(ParseError::invalid("Pdf object stream is not a stream")),
+    };
+    let data = decode_stream(dictionary, encoded)?;
+    let amount = dictionary
+        .get("N")
+        .and_then(|n| n.as_i64())
+        .unwrap_or(0)
+        .max(0) as usize;
+    let first = dictionary
+        .get("First")
+        .and_then(|f| f.as_i64())
+        .unwrap_or(0)
+        .max(0) as usize;
+
+    let mut header = Lexer::new(&data, 0);
+    let mut offsets = vec![];
+    for _ in 0..amount {
+        header.parse_unsigned()?;
+        offsets.push(header.parse_unsigned()? as usize);
+    }
+    offsets
+        .into_iter()
+        .map(|offset| Lexer::new(&data, first.saturating_add(offset)).parse_object())
+        .collect()
+}
+
+/// Parse `<number> <generation> obj ... endobj` at `offset`
+///
+/// `offsets` is used to find stream lengths given as references.
+fn parse_indirect_object(
+    data: &[u8],
+    offset: usize,
+    offsets: &HashMap<u32, usize>,
+) -> Result<(u32, PdfObject), ParseError> {
+    let mut lexer = Lexer::new(data, offset);
+    let number = lexer.parse_unsigned()? as u32;
+    lexer.parse_unsigned()?;
+    lexer.skip_whitespace();
+    if !lexer.consume_keyword(b"obj") {
+        return Err(ParseError::invalid(format!(
+            "Expected pdf object at offset {offset}"
+        )));
+    }
+    let object = lexer.parse_object()?;
+
+    let dictionary = match object {
+        PdfObject::Dictionary(dictionary) => dictionary,
+        object => return Ok((number, object)),
+    };
+    lexer.skip_whitespace();
+    if !lexer.consume_keyword(b"stream") {
+        return Ok((number, PdfObject::Dictionary(dictionary)));
+    }
+    // the keyword is followed by CRLF or LF
+    if data.get(lexer.position) == Some(&b'\r') {
+        lexer.position += 1;
+    }
+    if data.get(lexer.position) == Some(&b'\n') {
+        lexer.position += 1;
+    }
+    let start = lexer.position;
+
+    let length = match dictionary.get("Length") {
+        Some(PdfObject::Integer(length)) => Some(*length),
+        Some(PdfObject::Reference(length_number)) if *length_number != number => offsets
+            .get(length_number)
+            .and_then(|o| parse_indirect_object(data, *o, &HashMap::new()).ok())
+            .and_then(|(_, length)| length.as_i64()),
+        _ => None,
+    }
+    .and_then(|length| usize::try_from(length).ok())
+    .filter(|length| {
+        // only trust the length if the stream ends there
+        data.get(start.saturating_add(*length)..)
+            .map(|rest| {
+                let mut end = Lexer::new(rest, 0);
+                end.skip_whitespace();
+                end.consume_keyword(b"endstream")
+            })
+            .unwrap_or(false)
+    });
+    let end = match length {
+        Some(length) => start + length,
+        None => {
+            let end = find(&data[start..], b"endstream")
+                .map(|i| start + i)
+                .ok_or_else(|| ParseError::invalid("Unterminated pdf stream"))?;
+            // end of line before the keyword is not part of the data
+            let mut end = end;
+            if end > start && data[end - 1] == b'\n' {
+                end -= 1;
+            }
+            if end > start && data[end - 1] == b'\r' {
+                end -= 1;
+            }
+            end
+        }
+    };
+
+    Ok((
+        number,
+        PdfObject::Stream(dictionary, data[start..end].to_vec()),
+    ))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn find_last(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, 0 | b'\t' | b'\n' | 0x0C | b'\r' | b' ')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn is_regular(byte: u8) -> bool {
+    !is_whitespace(byte) && !is_delimiter(byte)
+}
+
+struct Lexer<'a> {
+    data: &'a [u8],
+    position: usize,
+    depth: u32,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(data: &'a [u8], position: usize) -> Lexer<'a> {
+        Lexer {
+            data,
+            position,
+            depth: 0,
+        }
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        ParseError::invalid(format!("{message} at pdf offset {}", self.position))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(byte) = self.peek() {
+            if is_whitespace(byte) {
+                self.position += 1;
+            } else if byte == b'%' {
+                while !matches!(self.peek(), None | Some(b'\r' | b'\n')) {
+                    self.position += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Read the regular characters at the current position
+    fn token(&mut self) -> &'a [u8] {
+        let start = self.position;
+        while self.peek().map(is_regular).unwrap_or(false) {
+            self.position += 1;
+        }
+        self.data.get(start..self.position).unwrap_or_default()
+    }
+
+    /// Consume `keyword` if it is the next token
+    fn consume_keyword(&mut self, keyword: &[u8]) -> bool {
+        let start = self.position;
+        if self.token() == keyword {
+            return true;
+        }
+        self.position = start;
+        false
+    }
+
+    fn parse_unsigned(&mut self) -> Result<u64, ParseError> {
+        self.skip_whitespace();
+        let token = self.token();
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| self.error("Expected number"))
+    }
+
+    fn parse_object(&mut self) -> Result<PdfObject, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'/') => {
+                self.position += 1;
+                Ok(PdfObject::Name(self.parse_name()))
+            }
+            Some(b'(') => Ok(PdfObject::String(self.parse_literal_string()?)),
+            Some(b'<') if self.data.get(self.position + 1) == Some(&b'<') => {
+                self.nested(Self::parse_dictionary)
+            }
+            Some(b'<') => Ok(PdfObject::String(self.parse_hex_string()?)),
+            Some(b'[') => self.nested(Self::parse_array),
+            Some(b'+' | b'-' | b'.' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => match self.token() {
+                b"true" => Ok(PdfObject::Bool(true)),
+                b"false" => Ok(PdfObject::Bool(false)),
+                b"null" => Ok(PdfObject::Null),
+                _ => Err(self.error("Unexpected pdf token")),
+            },
+            None => Err(self.error("Unexpected end of pdf data")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<PdfObject, ParseError>,
+    ) -> Result<PdfObject, ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("Pdf object nested too deep"));
+        }
+        let object = parse(self);
+        self.depth -= 1;
+        object
+    }
+
+    fn parse_name(&mut self) -> Vec<u8> {
+        let token = self.token();
+        let mut name = Vec::with_capacity(token.len());
+        let mut i = 0;
+        while i < token.len() {
+            let escaped = token
+                .get(i + 1..i + 3)
+                .filter(|_| token[i] == b'#')
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escaped {
+                Some(byte) => {
+                    name.push(byte);
+                    i += 3;
+                }
+                None => {
+                    name.push(token[i]);
+                    i += 1;
+                }
+            }
+        }
+        name
+    }
+
+    fn parse_number(&mut self) -> Result<PdfObject, ParseError> {
+        let token = self.token();
+        let text = std::str::from_utf8(token).map_err(|_| self.error("Invalid number"))?;
+        if text.contains('.') {
+            return text
+                .parse()
+                .map(PdfObject::Real)
+                .map_err(|_| self.error("Invalid number"));
+        }
+        let value: i64 = text.parse().map_err(|_| self.error("Invalid number"))?;
+
+        // an integer might be the start of a reference "<number> <generation> R"
+        if value >= 0 {
+            let start = self.position;
+            self.skip_whitespace();
+            if self.peek().map(|b| b.is_ascii_digit()).unwrap_or(false) {
+                let generation = self.token();
+                self.skip_whitespace();
+                if generation.iter().all(|b| b.is_ascii_digit()) && self.consume_keyword(b"R") {
+                    return Ok(PdfObject::Reference(value as u32));
+                }
+            }
+            self.position = start;
+        }
+        Ok(PdfObject::Integer(value))
+    }
+
+    fn parse_literal_string(&mut self) -> Result<Vec<u8>, ParseError> {
+        self.position += 1;
+        let mut out = vec![];
+        let mut open = 1;
+        loop {
+            let byte = self
+                .next_byte()
+                .ok_or_else(|| self.error("Unterminated pdf string"))?;
+            match byte {
+                b'(' => {
+                    open += 1;
+                    out.push(byte);
+                }
+                b')' => {
+                    open -= 1;
+                    if open == 0 {
+                        return Ok(out);
+                    }
+                    out.push(byte);
+                }
+                b'\\' => {
+                    let escape = self
+                        .next_byte()
+                        .ok_or_else(|| self.error("Unterminated pdf string"))?;
+                    match escape {
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0C),
+                        b'0'..=b'7' => {
+                            let mut value = (escape - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(digit @ b'0'..=b'7') => {
+                                        value = value * 8 + (digit - b'0') as u32;
+                                        self.position += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            out.push(value as u8);
+                        }
+                        // escaped line break continues the string on the next line
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.position += 1;
+                            }
+                        }
+                        b'\n' => (),
+                        other => out.push(other),
+                    }
+                }
+                byte => out.push(byte),
+            }
+        }
+    }
+
+    fn parse_hex_string(&mut self) -> Result<Vec<u8>, ParseError> {
+        self.position += 1;
+        let mut digits = vec![];
+        loop {
+            let byte = self
+                .next_byte()
+                .ok_or_else(|| self.error("Unterminated pdf hex string"))?;
+            match byte {
+                b'>' => break,
+                byte if is_whitespace(byte) => (),
+                byte => {
+                    let digit = (byte as char)
+                        .to_digit(16)
+                        .ok_or_else(|| self.error("Invalid pdf hex string"))?;
+                    digits.push(digit as u8);
+                }
+            }
+        }
+        if digits.len() % 2 == 1 {
+            digits.push(0);
+        }
+        Ok(digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect())
+    }
+
+    fn parse_array(&mut self) -> Result<PdfObject, ParseError> {
+        self.position += 1;
+        let mut items = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(b']') {
+                self.position += 1;
+                return Ok(PdfObject::Array(items));
+            }
+            items.push(self.parse_object()?);
+        }
+    }
+
+    fn parse_dictionary(&mut self) -> Result<PdfObject, ParseError> {
+        self.position += 2;
+        let mut dictionary = Dictionary::default();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b'>') if self.data.get(self.position + 1) == Some(&b'>') => {
+                    self.position += 2;
+                    return Ok(PdfObject::Dictionary(dictionary));
+                }
+                Some(b'/') => {
+                    self.position += 1;
+                    let key = self.parse_name();
+                    let value = self.parse_object()?;
+                    dictionary.0.push((key, value));
+                }
+                _ => return Err(self.error("Expected pdf dictionary key")),
+            }
+        }
+    }
+}
+
+fn write_object(out: &mut Vec<u8>, object: &PdfObject) {
+    match object {
+        PdfObject::Null => out.extend_from_slice(b"null"),
+        PdfObject::Bool(b) => out.extend_from_slice(if *b { b"true" } else { b"false" }),
+        PdfObject::Integer(i) => out.extend_from_slice(i.to_string().as_bytes()),
+        PdfObject::Real(r) => out.extend_from_slice(format_real(*r).as_bytes()),
+        PdfObject::String(s) => {
+            out.push(b'(');
+            for byte in s {
+                match byte {
+                    b'(' | b')' | b'\\' => out.extend_from_slice(&[b'\\', *byte]),
+                    b'\r' => out.extend_from_slice(b"\\r"),
+                    byte => out.push(*byte),
+                }
+            }
+            out.push(b')');
+        }
+        PdfObject::Name(name) => write_name(out, name),
+        PdfObject::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ');
+                }
+                write_object(out, item);
+            }
+            out.push(b']');
+        }
+        PdfObject::Dictionary(dictionary) => write_dictionary(out, dictionary),
+        PdfObject::Stream(dictionary, data) => {
+            let mut dictionary = dictionary.clone();
+            dictionary.set("Length", PdfObject::Integer(data.len() as i64));
+            write_dictionary(out, &dictionary);
+            out.extend_from_slice(b"\nstream\n");
+            out.extend_from_slice(data);
+            out.extend_from_slice(b"\nendstream");
+        }
+        PdfObject::Reference(number) => out.extend_from_slice(format!("{number} 0 R").as_bytes()),
+    }
+}
+
+fn write_dictionary(out: &mut Vec<u8>, dictionary: &Dictionary) {
+    out.extend_from_slice(b"<<");
+    for (i, (key, value)) in dictionary.0.iter().enumerate() {
+        if i > 0 {
+            out.push(b' ');
+        }
+        write_name(out, key);
+        out.push(b' ');
+        write_object(out, value);
+    }
+    out.extend_from_slice(b">>");
+}
+
+fn write_name(out: &mut Vec<u8>, name: &[u8]) {
+    out.push(b'/');
+    for byte in name {
+        if *byte == b'#' || !(0x21..0x7F).contains(byte) || is_delimiter(*byte) {
+            out.extend_from_slice(format!("#{byte:02X}").as_bytes());
+        } else {
+            out.push(*byte);
+        }
+    }
+}
+
+/// Format a number without exponent as required by pdf
+pub fn format_real(value: f64) -> String {
+    if !value.is_finite() {
+        return "0".to_owned();
+    }
+    let formatted = format!("{value:.4}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" | "" => "0".to_owned(),
+        trimmed => trimmed.to_owned(),
+    }
+}
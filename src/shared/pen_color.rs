@@ -1,7 +1,5 @@
-use crate::ParseError;
-
 /// Data representation of an exported color in a reMarkable document line
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PenColor {
     Black,
     Grey,
@@ -12,25 +10,101 @@ pub enum PenColor {
     Blue,
     Red,
     GreyOverlap,
+    Highlight,
+    Green2,
+    Cyan,
+    Magenta,
+    Yellow2,
+    /// Arbitrary color stored as `0xAARRGGBB` in newer v6 line blocks, next to the color code
+    /// the file used for it
+    Argb {
+        code: u32,
+        argb: u32,
+    },
+    /// Color code not known by this parser, the original code is preserved
+    Unknown(u32),
 }
 
-impl TryFrom<u32> for PenColor {
-    type Error = ParseError;
+impl PenColor {
+    /// Color value as `[r, g, b, a]`
+    ///
+    /// The values for named colors are approximations of how the tablet displays them. Unknown
+    /// colors are returned as black.
+    pub fn to_rgba(&self) -> [u8; 4] {
+        match self {
+            PenColor::Black => [0, 0, 0, 255],
+            PenColor::Grey => [144, 144, 144, 255],
+            PenColor::White => [255, 255, 255, 255],
+            PenColor::Yellow => [251, 247, 25, 255],
+            PenColor::Green => [0, 255, 0, 255],
+            PenColor::Pink => [255, 192, 203, 255],
+            PenColor::Blue => [78, 105, 201, 255],
+            PenColor::Red => [179, 62, 57, 255],
+            PenColor::GreyOverlap => [125, 125, 125, 255],
+            PenColor::Highlight => [255, 237, 117, 255],
+            PenColor::Green2 => [161, 216, 125, 255],
+            PenColor::Cyan => [139, 208, 229, 255],
+            PenColor::Magenta => [183, 130, 205, 255],
+            PenColor::Yellow2 => [247, 232, 81, 255],
+            PenColor::Argb { argb, .. } => {
+                let [a, r, g, b] = argb.to_be_bytes();
+                [r, g, b, a]
+            }
+            PenColor::Unknown(_) => [0, 0, 0, 255],
+        }
+    }
 
-    fn try_from(value: u32) -> Result<Self, Self::Error> {
+    /// Color with the custom `argb` value applied, the color code is kept for writing back
+    pub fn with_argb(self, argb: u32) -> PenColor {
+        PenColor::Argb {
+            code: u32::from(self),
+            argb,
+        }
+    }
+}
+
+impl From<u32> for PenColor {
+    /// Maps a [u32] value to a `PenColor`, unmapped values become [PenColor::Unknown]
+    fn from(value: u32) -> Self {
         match value {
-            0x00 => Ok(PenColor::Black),
-            0x01 => Ok(PenColor::Grey),
-            0x02 => Ok(PenColor::White),
-            0x03 => Ok(PenColor::Yellow),
-            0x04 => Ok(PenColor::Green),
-            0x05 => Ok(PenColor::Pink),
-            0x06 => Ok(PenColor::Blue),
-            0x07 => Ok(PenColor::Red),
-            0x08 => Ok(PenColor::GreyOverlap),
-            _ => Err(ParseError::invalid(format!(
-                "Invalid color with value '{value}'"
-            ))),
+            0x00 => PenColor::Black,
+            0x01 => PenColor::Grey,
+            0x02 => PenColor::White,
+            0x03 => PenColor::Yellow,
+            0x04 => PenColor::Green,
+            0x05 => PenColor::Pink,
+            0x06 => PenColor::Blue,
+            0x07 => PenColor::Red,
+            0x08 => PenColor::GreyOverlap,
+            0x09 => PenColor::Highlight,
+            0x0a => PenColor::Green2,
+            0x0b => PenColor::Cyan,
+            0x0c => PenColor::Magenta,
+            0x0d => PenColor::Yellow2,
+            value => PenColor::Unknown(value),
+        }
+    }
+}
+
+impl From<PenColor> for u32 {
+    fn from(color: PenColor) -> Self {
+        match color {
+            PenColor::Black => 0x00,
+            PenColor::Grey => 0x01,
+            PenColor::White => 0x02,
+            PenColor::Yellow => 0x03,
+            PenColor::Green => 0x04,
+            PenColor::Pink => 0x05,
+            PenColor::Blue => 0x06,
+            PenColor::Red => 0x07,
+            PenColor::GreyOverlap => 0x08,
+            PenColor::Highlight => 0x09,
+            PenColor::Green2 => 0x0a,
+            PenColor::Cyan => 0x0b,
+            PenColor::Magenta => 0x0c,
+            PenColor::Yellow2 => 0x0d,
+            PenColor::Argb { code, .. } => code,
+            PenColor::Unknown(value) => value,
         }
     }
 }
@@ -1,6 +1,8 @@
-use crate::ParseError;
-
-#[derive(Debug, Clone)]
+/// Tool used to draw a line
+///
+/// Newer firmware stores a second generation of most pens under a different code (suffixed with
+/// `2` here). Both are kept apart so the original code can be written back unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Tool {
     Brush,
     Pencil,
@@ -13,31 +15,92 @@ pub enum Tool {
     EraseArea,
     EraseAll,
     SelectionBrush,
+    SelectionBrush2,
+    Brush2,
+    MechanicalPencil2,
+    Pencil2,
+    BallPoint2,
+    Marker2,
+    FineLiner2,
+    Highlighter2,
     Calligraphy,
+    Shader,
+    /// Tool code not known by this parser, the original code is preserved
+    Unknown(u32),
 }
 
-impl TryFrom<u32> for Tool {
-    /// Used to represent a [u32] that does not map to a known `Tool`
-    type Error = ParseError;
+impl Tool {
+    /// Tool ignoring the generation it was drawn with, eg. [Tool::Pencil2] becomes [Tool::Pencil]
+    pub fn base(&self) -> Tool {
+        match self {
+            Tool::Brush2 => Tool::Brush,
+            Tool::MechanicalPencil2 => Tool::MechanicalPencil,
+            Tool::Pencil2 => Tool::Pencil,
+            Tool::BallPoint2 => Tool::BallPoint,
+            Tool::Marker2 => Tool::Marker,
+            Tool::FineLiner2 => Tool::FineLiner,
+            Tool::Highlighter2 => Tool::Highlighter,
+            Tool::SelectionBrush2 => Tool::SelectionBrush,
+            tool => *tool,
+        }
+    }
+}
 
-    /// Attempts to map a [u32] value to a known and supported `Tool`
-    fn try_from(value: u32) -> Result<Self, Self::Error> {
+impl From<u32> for Tool {
+    /// Maps a [u32] value to a `Tool`, unmapped values become [Tool::Unknown]
+    fn from(value: u32) -> Self {
         match value {
-            0x00 | 0x0c => Ok(Tool::Brush),
-            0x01 | 0x0e => Ok(Tool::Pencil),
-            0x02 | 0x0f => Ok(Tool::BallPoint),
-            0x03 | 0x10 => Ok(Tool::Marker),
-            0x04 | 0x11 => Ok(Tool::FineLiner),
-            0x05 | 0x12 => Ok(Tool::Highlighter),
-            0x06 => Ok(Tool::Eraser),
-            0x07 | 0x0d => Ok(Tool::MechanicalPencil),
-            0x08 => Ok(Tool::EraseArea),
-            0x09 => Ok(Tool::EraseAll),
-            0x0a | 0x0b => Ok(Tool::SelectionBrush),
-            0x15 => Ok(Tool::Calligraphy),
-            _ => Err(ParseError::invalid(format!(
-                "Invalid tool with value '{value}'"
-            ))),
+            0x00 => Tool::Brush,
+            0x01 => Tool::Pencil,
+            0x02 => Tool::BallPoint,
+            0x03 => Tool::Marker,
+            0x04 => Tool::FineLiner,
+            0x05 => Tool::Highlighter,
+            0x06 => Tool::Eraser,
+            0x07 => Tool::MechanicalPencil,
+            0x08 => Tool::EraseArea,
+            0x09 => Tool::EraseAll,
+            0x0a => Tool::SelectionBrush,
+            0x0b => Tool::SelectionBrush2,
+            0x0c => Tool::Brush2,
+            0x0d => Tool::MechanicalPencil2,
+            0x0e => Tool::Pencil2,
+            0x0f => Tool::BallPoint2,
+            0x10 => Tool::Marker2,
+            0x11 => Tool::FineLiner2,
+            0x12 => Tool::Highlighter2,
+            0x15 => Tool::Calligraphy,
+            0x17 => Tool::Shader,
+            value => Tool::Unknown(value),
+        }
+    }
+}
+
+impl From<Tool> for u32 {
+    fn from(tool: Tool) -> Self {
+        match tool {
+            Tool::Brush => 0x00,
+            Tool::Pencil => 0x01,
+            Tool::BallPoint => 0x02,
+            Tool::Marker => 0x03,
+            Tool::FineLiner => 0x04,
+            Tool::Highlighter => 0x05,
+            Tool::Eraser => 0x06,
+            Tool::MechanicalPencil => 0x07,
+            Tool::EraseArea => 0x08,
+            Tool::EraseAll => 0x09,
+            Tool::SelectionBrush => 0x0a,
+            Tool::SelectionBrush2 => 0x0b,
+            Tool::Brush2 => 0x0c,
+            Tool::MechanicalPencil2 => 0x0d,
+            Tool::Pencil2 => 0x0e,
+            Tool::BallPoint2 => 0x0f,
+            Tool::Marker2 => 0x10,
+            Tool::FineLiner2 => 0x11,
+            Tool::Highlighter2 => 0x12,
+            Tool::Calligraphy => 0x15,
+            Tool::Shader => 0x17,
+            Tool::Unknown(value) => value,
         }
     }
 }
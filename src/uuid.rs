@@ -0,0 +1,72 @@
+//! Random ids as used for documents, pages and authors
+//!
+//! Ids are version 4 UUIDs written as lowercase hex like the tablet does,
+//! eg. `3a7e1b4c-9f0d-4c8e-a2b5-6d1f0e9c7b21`.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Generate a new random uuid
+pub fn new_v4() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    // every RandomState is seeded differently by the standard library
+    let random = |salt: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        hasher.write_u64(count);
+        hasher.write_u64(salt);
+        hasher.finish()
+    };
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&random(0).to_be_bytes());
+    bytes[8..].copy_from_slice(&random(1).to_be_bytes());
+    // version 4, variant 1
+    bytes[6] = (bytes[6] & 0x0F) | 0x40;
+    bytes[8] = (bytes[8] & 0x3F) | 0x80;
+    format(bytes)
+}
+
+/// Format 16 bytes as uuid
+pub fn format(bytes: [u8; 16]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Bytes of a uuid, `None` if `uuid` isn't one
+pub fn to_bytes(uuid: &str) -> Option<[u8; 16]> {
+    let groups: Vec<&str> = uuid.split('-').collect();
+    if groups.iter().map(|g| g.len()).collect::<Vec<usize>>() != [8, 4, 4, 4, 12] {
+        return None;
+    }
+    let hex: String = groups.concat();
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0u8; 16];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// True if `uuid` is formatted as uuid
+pub fn is_valid(uuid: &str) -> bool {
+    to_bytes(uuid).is_some()
+}
@@ -1,12 +1,17 @@
-use crate::{bitreader::Readable, v6::crdt::CrdtId, Bitreader, ParseError};
+use crate::{bitreader::Readable, bitwriter::Bitwriter, v6::crdt::CrdtId, Bitreader, ParseError};
 
 mod blocks;
 pub use blocks::*;
 
 use super::{
-    scene_item::{glyph_range::GlyphRange, line::Line, text::Text},
+    scene_item::{
+        glyph_range::GlyphRange,
+        line::{Line, LINE_WRITE_VERSION},
+        text::Text,
+    },
     tagged_bit_reader::TaggedBitreader,
-    TypeParse,
+    tagged_bit_writer::TaggedBitwriter,
+    TypeParse, TypeWrite,
 };
 
 #[derive(Debug)]
@@ -15,11 +20,79 @@ pub struct BlockInfo {
     pub size: u32,
     pub min_version: u8,
     pub current_version: u8,
+    pub block_type: u8,
 }
 
 impl BlockInfo {
+    /// Read the header in front of every block, leaves the reader at the start of the block data
+    pub fn parse(reader: &mut Bitreader<impl Readable>) -> Result<Self, ParseError> {
+        let size = reader.read_u32()?;
+
+        // unknown value
+        let _ = reader.read_u8()?;
+        let min_version = reader.read_u8()?;
+        let current_version = reader.read_u8()?;
+        let block_type = reader.read_u8()?;
+
+        if current_version < min_version {
+            return Err(ParseError::invalid(
+                "current_version can't be smaller than min_version",
+            ));
+        }
+
+        let max_block_size = reader.limits().max_block_size;
+        if size > max_block_size {
+            return Err(ParseError::limit_exceeded(format!(
+                "Block size {size} exceeds limit of {max_block_size}"
+            )));
+        }
+        if size as u64 > reader.remaining() {
+            return Err(ParseError::invalid(format!(
+                "Block size {size} is larger than the remaining input"
+            )));
+        }
+
+        Ok(BlockInfo {
+            start_offset: reader.position(),
+            size,
+            min_version,
+            current_version,
+            block_type,
+        })
+    }
+
+    /// Write a block header, the size is filled in by [BlockInfo::write_size] once the block
+    /// data is written. Returns the offset to pass to it.
+    pub fn write(
+        writer: &mut Bitwriter,
+        min_version: u8,
+        current_version: u8,
+        block_type: u8,
+    ) -> usize {
+        let size_offset = writer.position();
+        writer.write_u32(0);
+        // unknown value
+        writer.write_u8(0);
+        writer.write_u8(min_version);
+        writer.write_u8(current_version);
+        writer.write_u8(block_type);
+        size_offset
+    }
+
+    /// Fill in the size of a block that started at `size_offset`
+    pub fn write_size(writer: &mut Bitwriter, size_offset: usize) -> Result<(), ParseError> {
+        let size = writer.position() - size_offset - 8;
+        writer.patch_u32(size_offset, u32::try_from(size)?);
+        Ok(())
+    }
+
     pub fn has_bytes_remaining(&self, reader: &Bitreader<impl Readable>) -> bool {
-        self.start_offset + self.size as u64 > reader.position()
+        self.end_offset() > reader.position()
+    }
+
+    /// Offset right after the last byte of this block
+    pub fn end_offset(&self) -> u64 {
+        self.start_offset + self.size as u64
     }
 }
 
@@ -49,34 +122,8 @@ pub trait BlockParse {
 
 impl TypeParse for Block {
     fn parse(reader: &mut TaggedBitreader<impl Readable>) -> Result<Self, ParseError> {
-        let size = reader.bit_reader.read_u32()?;
-
-        // unknown value
-        let _ = reader.bit_reader.read_u8()?;
-        let min_version = reader.bit_reader.read_u8()?;
-        let current_version = reader.bit_reader.read_u8()?;
-        let block_type = reader.bit_reader.read_u8()?;
-
-        if current_version < min_version {
-            return Err(ParseError::invalid(
-                "current_version can't be smaller than min_version",
-            ));
-        }
-
-        let start_offset = reader.bit_reader.position();
-
-        // println!(
-        //     "\nStarting new block at offset {:x} until {:x}",
-        //     reader.bit_reader.position() - 4,
-        //     start_offset + size as u64
-        // );
-
-        let info = BlockInfo {
-            start_offset,
-            size,
-            min_version,
-            current_version,
-        };
+        let info = BlockInfo::parse(reader.bit_reader)?;
+        let block_type = info.block_type;
 
         let block = match block_type {
             0x00 => Block::MigrationInfo(MigrationInfoBlock::parse(&info, reader)?),
@@ -119,7 +166,7 @@ impl TypeParse for Block {
             }
         };
 
-        let expected_offset = start_offset + size as u64;
+        let expected_offset = info.end_offset();
         let end_offset = reader.bit_reader.position();
         if expected_offset != end_offset {
             return Err(ParseError::invalid(format!(
@@ -130,3 +177,66 @@ impl TypeParse for Block {
         return Ok(block);
     }
 }
+
+impl Block {
+    /// Block type and the min and current version written for it
+    fn write_info(&self) -> (u8, u8, u8) {
+        match self {
+            Block::MigrationInfo(_) => (0x00, 1, 1),
+            Block::SceneTree(_) => (0x01, 1, 1),
+            Block::TreeNode(_) => (0x02, 1, 1),
+            Block::SceneGlyphItem(_) => (0x03, 1, 1),
+            Block::SceneGroupItem(_) => (0x04, 1, 1),
+            Block::SceneLineItem(block) => {
+                let version = match &block.item.value {
+                    Some(line) => line.write_version(),
+                    None => LINE_WRITE_VERSION,
+                };
+                (0x05, version, version)
+            }
+            Block::SceneTextItem(_) => (0x06, 1, 1),
+            Block::RootText(_) => (0x07, 1, 1),
+            Block::AuthorsIds(_) => (0x09, 1, 1),
+            Block::PageInfo(_) => (0x0A, 0, 1),
+        }
+    }
+}
+
+impl TypeWrite for Block {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        let (block_type, min_version, current_version) = self.write_info();
+        let size_offset =
+            BlockInfo::write(writer.bit_writer, min_version, current_version, block_type);
+
+        match self {
+            Block::MigrationInfo(block) => block.write(writer)?,
+            Block::PageInfo(block) => block.write(writer)?,
+            Block::TreeNode(block) => block.write(writer)?,
+            Block::SceneTree(block) => block.write(writer)?,
+            Block::SceneGlyphItem(block) => block.write(
+                writer,
+                SceneItemType::SceneGlyphItemBlock,
+                |value, writer| value.write(writer),
+            )?,
+            Block::SceneGroupItem(block) => block.write(
+                writer,
+                SceneItemType::SceneGroupItemBlock,
+                |value, writer| writer.write_id(2, *value),
+            )?,
+            Block::SceneLineItem(block) => block.write(
+                writer,
+                SceneItemType::SceneLineItemBlock,
+                |value, writer| value.write(writer),
+            )?,
+            Block::SceneTextItem(block) => block.write(
+                writer,
+                SceneItemType::SceneTextItemBlock,
+                |value, writer| value.write(writer),
+            )?,
+            Block::AuthorsIds(block) => block.write(writer)?,
+            Block::RootText(block) => block.write(writer)?,
+        }
+
+        BlockInfo::write_size(writer.bit_writer, size_offset)
+    }
+}
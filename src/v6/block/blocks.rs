@@ -6,7 +6,8 @@ use crate::{
         crdt::{CrdtId, CrdtSequenceItem},
         scene_item::{group::Group, text::Text},
         tagged_bit_reader::TaggedBitreader,
-        TypeParse,
+        tagged_bit_writer::TaggedBitwriter,
+        TypeParse, TypeWrite,
     },
     ParseError,
 };
@@ -27,7 +28,7 @@ impl BlockParse for MigrationInfoBlock {
 
         let is_device = reader.read_u8(2)? > 0;
 
-        if info.has_bytes_remaining(&mut reader.bit_reader) {
+        if info.has_bytes_remaining(reader.bit_reader) {
             _ = reader.bit_reader.read_u8();
         }
         Ok(Self {
@@ -37,6 +38,14 @@ impl BlockParse for MigrationInfoBlock {
     }
 }
 
+impl TypeWrite for MigrationInfoBlock {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        writer.write_id(1, self.migration_id)?;
+        writer.write_u8(2, self.is_device as u8);
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AuthorsIdsBlock {
     pub authors: HashMap<u16, String>,
@@ -47,6 +56,7 @@ impl BlockParse for AuthorsIdsBlock {
         reader: &mut TaggedBitreader<impl Readable>,
     ) -> Result<Self, ParseError> {
         let amount_subblocks = reader.bit_reader.read_varuint()?;
+        reader.bit_reader.check_count(amount_subblocks as u64, 1)?;
         let mut authors = HashMap::new();
 
         for _ in 0..amount_subblocks {
@@ -63,6 +73,24 @@ impl BlockParse for AuthorsIdsBlock {
     }
 }
 
+impl TypeWrite for AuthorsIdsBlock {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        let mut authors: Vec<(&u16, &String)> = self.authors.iter().collect();
+        authors.sort();
+        writer
+            .bit_writer
+            .write_varuint(u32::try_from(authors.len())?);
+        for (author_id, uuid) in authors {
+            writer.write_subblock(0, |writer| {
+                writer.bit_writer.write_uuid(uuid)?;
+                writer.bit_writer.write_u16(*author_id);
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PageInfoBlock {
     pub loads_count: u32,
@@ -80,7 +108,7 @@ impl BlockParse for PageInfoBlock {
         let text_chars_count = reader.read_u32(3)?;
         let text_lines_count = reader.read_u32(4)?;
 
-        if info.has_bytes_remaining(&mut reader.bit_reader) {
+        if info.has_bytes_remaining(reader.bit_reader) {
             reader.read_u32(5)?;
         }
 
@@ -93,6 +121,16 @@ impl BlockParse for PageInfoBlock {
     }
 }
 
+impl TypeWrite for PageInfoBlock {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        writer.write_u32(1, self.loads_count);
+        writer.write_u32(2, self.merges_count);
+        writer.write_u32(3, self.text_chars_count);
+        writer.write_u32(4, self.text_lines_count);
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TreeNodeBlock {
     pub group: Group,
@@ -102,12 +140,14 @@ impl BlockParse for TreeNodeBlock {
         info: &BlockInfo,
         reader: &mut TaggedBitreader<impl Readable>,
     ) -> Result<Self, ParseError> {
-        let mut group = Group::default();
-        group.node_id = reader.read_id(1)?;
-        group.label = reader.read_lww_string(2)?;
-        group.visible = reader.read_lww_bool(3)?;
+        let mut group = Group {
+            node_id: reader.read_id(1)?,
+            label: reader.read_lww_string(2)?,
+            visible: reader.read_lww_bool(3)?,
+            ..Default::default()
+        };
 
-        if info.has_bytes_remaining(&reader.bit_reader) {
+        if info.has_bytes_remaining(reader.bit_reader) {
             group.anchor_id = Some(reader.read_lww_id(7)?);
             group.anchor_type = Some(reader.read_lww_u8(8)?);
             group.anchor_threshold = Some(reader.read_lww_f32(9)?);
@@ -118,6 +158,29 @@ impl BlockParse for TreeNodeBlock {
     }
 }
 
+impl TypeWrite for TreeNodeBlock {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        let group = &self.group;
+        writer.write_id(1, group.node_id)?;
+        writer.write_lww_string(2, &group.label)?;
+        writer.write_lww_bool(3, &group.visible)?;
+
+        // the anchor is only read if all of it is there
+        if let (Some(id), Some(anchor_type), Some(threshold), Some(origin_x)) = (
+            &group.anchor_id,
+            &group.anchor_type,
+            &group.anchor_threshold,
+            &group.anchor_origin_x,
+        ) {
+            writer.write_lww_id(7, id)?;
+            writer.write_lww_u8(8, anchor_type)?;
+            writer.write_lww_f32(9, threshold)?;
+            writer.write_lww_f32(10, origin_x)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SceneTreeBlock {
     pub tree_id: CrdtId,
@@ -147,6 +210,15 @@ impl BlockParse for SceneTreeBlock {
     }
 }
 
+impl TypeWrite for SceneTreeBlock {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        writer.write_id(1, self.tree_id)?;
+        writer.write_id(2, self.node_id)?;
+        writer.write_bool(3, self.is_update);
+        writer.write_subblock(4, |writer| writer.write_id(1, self.parent_id))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RootTextBlock {
     pub block_id: CrdtId,
@@ -166,8 +238,15 @@ impl BlockParse for RootTextBlock {
     }
 }
 
+impl TypeWrite for RootTextBlock {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        writer.write_id(1, self.block_id)?;
+        self.text.write(writer)
+    }
+}
+
 #[repr(C)]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum SceneItemType {
     SceneGlyphItemBlock = 1,
     SceneGroupItemBlock = 2,
@@ -240,3 +319,27 @@ impl<N> SceneItemBlock<N> {
         })
     }
 }
+
+impl<N> SceneItemBlock<N> {
+    /// Counterpart of [SceneItemBlock::parse], `write_value` writes the value of the item
+    pub fn write(
+        &self,
+        writer: &mut TaggedBitwriter,
+        scene_item_type: SceneItemType,
+        write_value: fn(&N, &mut TaggedBitwriter) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        writer.write_id(1, self.parent_id)?;
+        writer.write_id(2, self.item.item_id)?;
+        writer.write_id(3, self.item.left_id)?;
+        writer.write_id(4, self.item.right_id)?;
+        writer.write_u32(5, self.item.deleted_length);
+
+        if let Some(value) = &self.item.value {
+            writer.write_subblock(6, |writer| {
+                writer.bit_writer.write_u8(scene_item_type as u8);
+                write_value(value, writer)
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{ParseError, RemarkableFile};
+
+use super::{
+    block::{Block, SceneItemBlock},
+    crdt::{CrdtId, CrdtSequence},
+    merge::fold,
+    scene_item::{
+        text::{Text, TextItem},
+        SceneItem,
+    },
+    scene_tree::SceneTree,
+};
+
+/// Smallest list of blocks that gives the same page as `file`
+///
+/// Deleted items and groups that can no longer be reached from the root are dropped, repeated
+/// `TreeNode` blocks are folded into one with the newest values. The remaining items of every
+/// group are linked to each other in the order they are shown. Deleted characters of the text
+/// are only kept if a group is anchored to them.
+pub fn compact(file: &RemarkableFile) -> Result<RemarkableFile, ParseError> {
+    let (tree, blocks) = match file {
+        RemarkableFile::V6 { tree, blocks } => (tree, blocks),
+        RemarkableFile::Other { version, .. } => {
+            return Err(ParseError::unsupported(format!(
+                "Only v6 pages can be compacted, got version {version}"
+            )))
+        }
+    };
+
+    let reachable = reachable_nodes(tree);
+    let anchors: Vec<CrdtId> = reachable
+        .iter()
+        .filter_map(|id| tree.node(id)?.anchor_id.as_ref())
+        .map(|anchor| anchor.value)
+        .collect();
+
+    // neighbours of every live item in the order the tree shows them
+    let mut links = HashMap::new();
+    for id in reachable.iter() {
+        if let Some(node) = tree.node(id) {
+            links.extend(link(&node.children.sorted_ids()));
+        }
+    }
+
+    let mut compacted = vec![];
+    for block in fold(blocks)? {
+        let keep = match &block {
+            Block::SceneTree(b) => reachable.contains(&b.tree_id),
+            Block::TreeNode(b) => reachable.contains(&b.group.node_id),
+            Block::SceneTextItem(b) => b.item.value.is_some() && reachable.contains(&b.parent_id),
+            Block::SceneGlyphItem(b) => links.contains_key(&b.item.item_id),
+            Block::SceneGroupItem(b) => links.contains_key(&b.item.item_id),
+            Block::SceneLineItem(b) => links.contains_key(&b.item.item_id),
+            _ => true,
+        };
+        if !keep {
+            continue;
+        }
+        let block = match block {
+            Block::SceneGlyphItem(b) => Block::SceneGlyphItem(relink(b, &links)),
+            Block::SceneGroupItem(b) => Block::SceneGroupItem(relink(b, &links)),
+            Block::SceneLineItem(b) => Block::SceneLineItem(relink(b, &links)),
+            Block::RootText(mut b) => {
+                b.text = compact_text(b.text, &anchors);
+                Block::RootText(b)
+            }
+            block => block,
+        };
+        compacted.push(block);
+    }
+
+    let tree = SceneTree::from_blocks(&compacted)?;
+    Ok(RemarkableFile::V6 {
+        tree,
+        blocks: compacted,
+    })
+}
+
+/// Ids of the root and every group in it
+fn reachable_nodes(tree: &SceneTree) -> HashSet<CrdtId> {
+    let mut reachable = HashSet::new();
+    let mut pending = vec![tree.root_id()];
+    while let Some(id) = pending.pop() {
+        if !reachable.insert(id) {
+            continue;
+        }
+        if let Some(node) = tree.node(&id) {
+            for item in node.children.items.values() {
+                if let SceneItem::Group(child) = &item.value {
+                    pending.push(child.node_id);
+                }
+            }
+        }
+    }
+    reachable
+}
+
+/// Left and right neighbour of every id in `ids`, the ends are linked to `0:0`
+fn link(ids: &[CrdtId]) -> HashMap<CrdtId, (CrdtId, CrdtId)> {
+    let end = CrdtId::default();
+    ids.iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let left = i.checked_sub(1).map(|i| ids[i]).unwrap_or(end);
+            let right = ids.get(i + 1).copied().unwrap_or(end);
+            (*id, (left, right))
+        })
+        .collect()
+}
+
+fn relink<N>(
+    mut block: SceneItemBlock<N>,
+    links: &HashMap<CrdtId, (CrdtId, CrdtId)>,
+) -> SceneItemBlock<N> {
+    if let Some((left, right)) = links.get(&block.item.item_id) {
+        block.item.left_id = *left;
+        block.item.right_id = *right;
+    }
+    block
+}
+
+fn compact_text(text: Text, anchors: &[CrdtId]) -> Text {
+    let sequence = text.items;
+    let kept: Vec<CrdtId> = sequence
+        .iter()
+        .filter(|item| {
+            let length = match &item.value {
+                TextItem::Text(text) if item.deleted_length > 0 && text.is_empty() => {
+                    item.deleted_length
+                }
+                // still shown
+                _ => return true,
+            };
+            // every character has its own id following the id of the item
+            let ids = item.item_id.part2..item.item_id.part2.saturating_add(length);
+            anchors
+                .iter()
+                .any(|a| a.part1 == item.item_id.part1 && ids.contains(&a.part2))
+        })
+        .map(|item| item.item_id)
+        .collect();
+
+    let links = link(&kept);
+    let mut items = sequence.items;
+    items.retain(|id, _| links.contains_key(id));
+    for item in items.values_mut() {
+        if let Some((left, right)) = links.get(&item.item_id) {
+            item.left_id = *left;
+            item.right_id = *right;
+        }
+    }
+    Text {
+        items: CrdtSequence::new(items),
+        ..text
+    }
+}
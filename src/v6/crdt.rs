@@ -1,15 +1,33 @@
-use std::collections::HashMap;
+use std::{cmp::Ordering, collections::HashMap};
 
 use crate::bitreader::Readable;
 
-use super::{tagged_bit_reader::TaggedBitreader, TypeParse};
+use super::{
+    tagged_bit_reader::TaggedBitreader, tagged_bit_writer::TaggedBitwriter, TypeParse, TypeWrite,
+};
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+/// Id of an item or a timestamp of a change
+///
+/// `part1` is the author, `part2` the author's clock value.
+#[derive(Debug, Default, Hash, PartialEq, Eq, Clone, Copy)]
 pub struct CrdtId {
     pub part1: u8,
     pub part2: u32,
 }
 
+/// Ordered like the device orders changes, by clock value and then by author
+impl Ord for CrdtId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.part2, self.part1).cmp(&(other.part2, other.part1))
+    }
+}
+
+impl PartialOrd for CrdtId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl TypeParse for CrdtId {
     fn parse(reader: &mut TaggedBitreader<impl Readable>) -> Result<Self, crate::ParseError> {
         Ok(CrdtId {
@@ -19,12 +37,11 @@ impl TypeParse for CrdtId {
     }
 }
 
-impl Default for CrdtId {
-    fn default() -> Self {
-        Self {
-            part1: Default::default(),
-            part2: Default::default(),
-        }
+impl TypeWrite for CrdtId {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), crate::ParseError> {
+        writer.bit_writer.write_u8(self.part1);
+        writer.bit_writer.write_varuint(self.part2);
+        Ok(())
     }
 }
 
@@ -48,7 +65,97 @@ impl<N> CrdtSequence<N> {
     }
 
     pub fn push(&mut self, item: CrdtSequenceItem<N>) -> Option<CrdtSequenceItem<N>> {
-        self.items.insert(item.item_id.clone(), item)
+        self.items.insert(item.item_id, item)
+    }
+
+    /// Ids of all items in sequence order
+    ///
+    /// Every item is placed after its `left_id` and before its `right_id`, where an id of `0:0`
+    /// marks the start or end of the sequence. Items that can be placed at the same time are
+    /// ordered by author and then by clock value. Items in a cycle, which the device never
+    /// produces, are put at the end in the same order.
+    pub fn sorted_ids(&self) -> Vec<CrdtId> {
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        enum Key {
+            Start,
+            End,
+            Item(CrdtId),
+        }
+        // Not the order of `Ord for CrdtId`, which compares changes by when they were made. Items
+        // placed at the same spot were inserted there concurrently on different devices, whose
+        // clock values say nothing about each other. Ordering them by author first keeps what
+        // every author inserted there together.
+        let tie_order = |id: &CrdtId| (id.part1, id.part2);
+        let key = |id: CrdtId, marker: Key| {
+            if id == CrdtId::default() {
+                marker
+            } else {
+                Key::Item(id)
+            }
+        };
+
+        // amount of keys every key still has to come after, and the keys that come after it
+        let mut dependencies: HashMap<Key, usize> = HashMap::new();
+        let mut dependents: HashMap<Key, Vec<Key>> = HashMap::new();
+        let mut add_edge = |before: Key, after: Key| {
+            *dependencies.entry(after).or_default() += 1;
+            dependencies.entry(before).or_default();
+            dependents.entry(before).or_default().push(after);
+        };
+        for item in self.items.values() {
+            let id = Key::Item(item.item_id);
+            add_edge(key(item.left_id, Key::Start), id);
+            add_edge(id, key(item.right_id, Key::End));
+        }
+
+        let mut ready: Vec<Key> = dependencies
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(key, _)| *key)
+            .collect();
+        let mut sorted = vec![];
+        while !ready.is_empty() {
+            let mut ids: Vec<CrdtId> = ready
+                .iter()
+                .filter_map(|k| match k {
+                    Key::Item(id) if self.items.contains_key(id) => Some(*id),
+                    _ => None,
+                })
+                .collect();
+            ids.sort_by_key(tie_order);
+            sorted.extend(ids);
+
+            let mut next = vec![];
+            for key in ready {
+                for dependent in dependents.remove(&key).unwrap_or_default() {
+                    let count = dependencies.entry(dependent).or_default();
+                    *count -= 1;
+                    if *count == 0 {
+                        next.push(dependent);
+                    }
+                }
+            }
+            ready = next;
+        }
+
+        if sorted.len() < self.items.len() {
+            let mut remaining: Vec<CrdtId> = self
+                .items
+                .keys()
+                .filter(|id| !sorted.contains(id))
+                .copied()
+                .collect();
+            remaining.sort_by_key(tie_order);
+            sorted.extend(remaining);
+        }
+        sorted
+    }
+
+    /// Items in sequence order, see [CrdtSequence::sorted_ids]
+    pub fn iter(&self) -> impl Iterator<Item = &CrdtSequenceItem<N>> {
+        self.sorted_ids()
+            .into_iter()
+            .filter_map(|id| self.items.get(&id))
     }
 }
 
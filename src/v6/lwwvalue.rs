@@ -1,7 +1,37 @@
 use super::crdt::CrdtId;
 
+/// Last-writer-wins register, the value written with the newest timestamp is kept
 #[derive(Debug, Clone)]
 pub struct LwwValue<T> {
     pub timestamp: CrdtId,
     pub value: T,
 }
+
+impl<T> LwwValue<T> {
+    /// Set `value` if `timestamp` is newer than the current one, returns if it was set
+    pub fn update(&mut self, timestamp: CrdtId, value: T) -> bool {
+        if timestamp > self.timestamp {
+            self.timestamp = timestamp;
+            self.value = value;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Keep whichever of both values is newer, `self` on equal timestamps
+    pub fn merge(&mut self, other: LwwValue<T>) -> bool {
+        self.update(other.timestamp, other.value)
+    }
+
+    /// [LwwValue::merge] for optional values, a missing value is always replaced
+    pub fn merge_option(value: &mut Option<LwwValue<T>>, other: Option<LwwValue<T>>) {
+        match (value.as_mut(), other) {
+            (Some(value), Some(other)) => {
+                value.merge(other);
+            }
+            (None, other) => *value = other,
+            (Some(_), None) => {}
+        }
+    }
+}
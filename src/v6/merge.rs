@@ -0,0 +1,214 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{ParseError, RemarkableFile};
+
+use super::{
+    block::{Block, PageInfoBlock, SceneItemBlock},
+    crdt::{CrdtId, CrdtSequenceItem},
+    scene_item::text::Text,
+    scene_tree::SceneTree,
+};
+
+/// Identifies the blocks of two versions of a page that describe the same thing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum BlockKey {
+    MigrationInfo,
+    PageInfo,
+    AuthorsIds,
+    SceneTree(CrdtId),
+    TreeNode(CrdtId),
+    /// Sequence items by their item id, which is unique within a page
+    Item(CrdtId),
+    RootText(CrdtId),
+}
+
+impl BlockKey {
+    fn of(block: &Block) -> Self {
+        match block {
+            Block::MigrationInfo(_) => BlockKey::MigrationInfo,
+            Block::PageInfo(_) => BlockKey::PageInfo,
+            Block::AuthorsIds(_) => BlockKey::AuthorsIds,
+            Block::SceneTree(b) => BlockKey::SceneTree(b.tree_id),
+            Block::TreeNode(b) => BlockKey::TreeNode(b.group.node_id),
+            Block::SceneGlyphItem(b) => BlockKey::Item(b.item.item_id),
+            Block::SceneGroupItem(b) => BlockKey::Item(b.item.item_id),
+            Block::SceneLineItem(b) => BlockKey::Item(b.item.item_id),
+            Block::SceneTextItem(b) => BlockKey::Item(b.item.item_id),
+            Block::RootText(b) => BlockKey::RootText(b.block_id),
+        }
+    }
+}
+
+/// Merge two versions of a v6 page that were both edited starting from `base`
+///
+/// Sequence items of both sides are combined, an item deleted on either side stays deleted.
+/// Typed text is combined character by character, as edits split its items differently.
+/// Items that are in `base` but were dropped from one side are left out. Labels, visibility,
+/// anchors and paragraph styles keep the value with the newest timestamp, `ours` wins ties.
+/// Blocks are written in the order of `ours` followed by what only `theirs` has.
+pub fn merge(
+    base: &RemarkableFile,
+    ours: &RemarkableFile,
+    theirs: &RemarkableFile,
+) -> Result<RemarkableFile, ParseError> {
+    let base = index(v6_blocks(base)?)?;
+    let (ours, theirs) = (v6_blocks(ours)?, v6_blocks(theirs)?);
+    let (ours_index, theirs_index) = (index(ours)?, index(theirs)?);
+
+    let mut blocks = vec![];
+    let mut seen = HashSet::new();
+    for key in ours.iter().chain(theirs.iter()).map(BlockKey::of) {
+        if !seen.insert(key) {
+            continue;
+        }
+        match (ours_index.get(&key), theirs_index.get(&key)) {
+            (Some(a), Some(b)) => blocks.push(merge_blocks(a, b)?),
+            (Some(block), None) | (None, Some(block)) => {
+                // removed from the other side without leaving a tombstone
+                if matches!(key, BlockKey::Item(_)) && base.contains_key(&key) {
+                    continue;
+                }
+                blocks.push(block.clone());
+            }
+            (None, None) => {}
+        }
+    }
+
+    for block in blocks.iter_mut() {
+        if let Block::PageInfo(info) = block {
+            info.merges_count += 1;
+        }
+    }
+
+    let tree = SceneTree::from_blocks(&blocks)?;
+    Ok(RemarkableFile::V6 { tree, blocks })
+}
+
+fn v6_blocks(file: &RemarkableFile) -> Result<&[Block], ParseError> {
+    match file {
+        RemarkableFile::V6 { blocks, .. } => Ok(blocks),
+        RemarkableFile::Other { version, .. } => Err(ParseError::unsupported(format!(
+            "Only v6 pages can be merged, got version {version}"
+        ))),
+    }
+}
+
+/// Blocks by key, blocks updating an earlier block of the same file are merged into it
+fn index(blocks: &[Block]) -> Result<HashMap<BlockKey, Block>, ParseError> {
+    let mut index: HashMap<BlockKey, Block> = HashMap::new();
+    for block in blocks {
+        let key = BlockKey::of(block);
+        let merged = match index.get(&key) {
+            Some(existing) => merge_blocks(existing, block)?,
+            None => block.clone(),
+        };
+        index.insert(key, merged);
+    }
+    Ok(index)
+}
+
+/// Every block once in the order it first appears, later blocks for the same thing merged into it
+pub(super) fn fold(blocks: &[Block]) -> Result<Vec<Block>, ParseError> {
+    let index = index(blocks)?;
+    let mut seen = HashSet::new();
+    Ok(blocks
+        .iter()
+        .map(BlockKey::of)
+        .filter(|key| seen.insert(*key))
+        .filter_map(|key| index.get(&key).cloned())
+        .collect())
+}
+
+fn merge_blocks(a: &Block, b: &Block) -> Result<Block, ParseError> {
+    let block = match (a, b) {
+        (Block::PageInfo(a), Block::PageInfo(b)) => Block::PageInfo(PageInfoBlock {
+            loads_count: a.loads_count.max(b.loads_count),
+            merges_count: a.merges_count.max(b.merges_count),
+            text_chars_count: a.text_chars_count,
+            text_lines_count: a.text_lines_count,
+        }),
+        (Block::AuthorsIds(a), Block::AuthorsIds(b)) => {
+            let mut merged = a.clone();
+            for (id, uuid) in b.authors.iter() {
+                merged.authors.entry(*id).or_insert_with(|| uuid.clone());
+            }
+            Block::AuthorsIds(merged)
+        }
+        (Block::TreeNode(a), Block::TreeNode(b)) => {
+            let mut merged = a.clone();
+            merged.group.merge_values(b.group.clone());
+            Block::TreeNode(merged)
+        }
+        (Block::SceneGlyphItem(a), Block::SceneGlyphItem(b)) => {
+            Block::SceneGlyphItem(merge_item_block(a, b))
+        }
+        (Block::SceneGroupItem(a), Block::SceneGroupItem(b)) => {
+            Block::SceneGroupItem(merge_item_block(a, b))
+        }
+        (Block::SceneLineItem(a), Block::SceneLineItem(b)) => {
+            Block::SceneLineItem(merge_item_block(a, b))
+        }
+        (Block::SceneTextItem(a), Block::SceneTextItem(b)) => {
+            Block::SceneTextItem(merge_item_block(a, b))
+        }
+        (Block::RootText(a), Block::RootText(b)) => {
+            let mut merged = a.clone();
+            merged.text = merge_text(&a.text, &b.text);
+            Block::RootText(merged)
+        }
+        (Block::MigrationInfo(_), Block::MigrationInfo(_))
+        | (Block::SceneTree(_), Block::SceneTree(_)) => a.clone(),
+        _ => {
+            return Err(ParseError::invalid(format!(
+                "Can not merge blocks of different types: {:?} and {:?}",
+                BlockKey::of(a),
+                BlockKey::of(b)
+            )))
+        }
+    };
+    Ok(block)
+}
+
+/// The item as it is on both sides, a deleted item is never brought back
+fn merge_item<N: Clone>(
+    a: &CrdtSequenceItem<N>,
+    b: &CrdtSequenceItem<N>,
+    is_deleted: fn(&CrdtSequenceItem<N>) -> bool,
+) -> CrdtSequenceItem<N> {
+    if is_deleted(b) && !is_deleted(a) {
+        b.clone()
+    } else {
+        a.clone()
+    }
+}
+
+fn merge_item_block<N: Clone>(a: &SceneItemBlock<N>, b: &SceneItemBlock<N>) -> SceneItemBlock<N> {
+    SceneItemBlock {
+        parent_id: a.parent_id,
+        item: merge_item(&a.item, &b.item, |item| item.value.is_none()),
+    }
+}
+
+fn merge_text(a: &Text, b: &Text) -> Text {
+    let items = a.merged_items(b);
+
+    let mut styles = a.styles.clone();
+    for (id, style) in b.styles.iter() {
+        match styles.get_mut(id) {
+            Some(existing) => {
+                existing.merge(style.clone());
+            }
+            None => {
+                styles.insert(*id, style.clone());
+            }
+        }
+    }
+
+    Text {
+        items,
+        styles,
+        x: a.x,
+        y: a.y,
+        width: a.width,
+    }
+}
@@ -1,13 +1,17 @@
 pub mod block;
+pub mod compact;
 pub mod crdt;
 pub mod lwwvalue;
+pub mod merge;
+pub mod scene_builder;
 pub mod scene_item;
 pub mod scene_tree;
 pub mod tagged_bit_reader;
+pub mod tagged_bit_writer;
 
 use crate::{bitreader::Readable, ParseError};
 
-use self::tagged_bit_reader::TaggedBitreader;
+use self::{tagged_bit_reader::TaggedBitreader, tagged_bit_writer::TaggedBitwriter};
 
 /// Simplified parsing method only accepting reader
 pub trait TypeParse {
@@ -15,3 +19,8 @@ pub trait TypeParse {
     where
         Self: Sized;
 }
+
+/// Counterpart of [TypeParse], writes the layout the parser reads
+pub trait TypeWrite {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError>;
+}
@@ -0,0 +1,524 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{ParseError, RemarkableFile};
+
+use super::{
+    block::{
+        AuthorsIdsBlock, Block, MigrationInfoBlock, PageInfoBlock, RootTextBlock, SceneItemBlock,
+        SceneTreeBlock, TreeNodeBlock,
+    },
+    crdt::{CrdtId, CrdtSequenceItem},
+    lwwvalue::LwwValue,
+    scene_item::{
+        group::Group,
+        line::Line,
+        text::{ParagraphStyle, Text, TextItem},
+        SceneItem,
+    },
+    scene_tree::SceneTree,
+};
+
+/// Author id of the first author of a page
+const FIRST_AUTHOR: u16 = 1;
+
+/// Adds layers, lines and text to a v6 page
+///
+/// Every change is added as the blocks the tablet would write for it, so the result can be
+/// written with [RemarkableFile::to_bytes]. New ids are taken from the clock of the chosen
+/// author, continuing after the newest id already in the page.
+#[derive(Debug)]
+pub struct SceneBuilder {
+    blocks: Vec<Block>,
+    tree: SceneTree,
+    author: u8,
+    clock: u32,
+}
+
+impl SceneBuilder {
+    /// Start an empty page written by the author with uuid `author`
+    pub fn new(author: &str) -> Result<SceneBuilder, ParseError> {
+        let blocks = vec![
+            Block::AuthorsIds(AuthorsIdsBlock {
+                authors: HashMap::from([(FIRST_AUTHOR, author.to_owned())]),
+            }),
+            Block::MigrationInfo(MigrationInfoBlock {
+                migration_id: CrdtId {
+                    part1: FIRST_AUTHOR as u8,
+                    part2: 1,
+                },
+                is_device: true,
+            }),
+            Block::PageInfo(PageInfoBlock {
+                loads_count: 1,
+                merges_count: 0,
+                text_chars_count: 0,
+                text_lines_count: 0,
+            }),
+            Block::TreeNode(TreeNodeBlock {
+                group: Group::default().node_id(SceneTree::new().root_id()),
+            }),
+        ];
+        let file = RemarkableFile::V6 {
+            tree: SceneTree::from_blocks(&blocks)?,
+            blocks,
+        };
+        Self::from_file(file, author)
+    }
+
+    /// Continue editing `file` as the author with uuid `author`, who is added to the page if
+    /// they didn't write in it yet
+    pub fn from_file(file: RemarkableFile, author: &str) -> Result<SceneBuilder, ParseError> {
+        let (tree, mut blocks) = match file {
+            RemarkableFile::V6 { tree, blocks } => (tree, blocks),
+            RemarkableFile::Other { version, .. } => {
+                return Err(ParseError::unsupported(format!(
+                    "Only v6 pages can be edited, got version {version}"
+                )))
+            }
+        };
+
+        let index = match blocks
+            .iter()
+            .position(|b| matches!(b, Block::AuthorsIds(_)))
+        {
+            Some(index) => index,
+            None => {
+                blocks.insert(
+                    0,
+                    Block::AuthorsIds(AuthorsIdsBlock {
+                        authors: HashMap::new(),
+                    }),
+                );
+                0
+            }
+        };
+        let authors = match &mut blocks[index] {
+            Block::AuthorsIds(block) => &mut block.authors,
+            _ => unreachable!("found by position above"),
+        };
+        let author_id = match authors.iter().find(|(_, uuid)| uuid.as_str() == author) {
+            Some((id, _)) => *id,
+            None => {
+                let id = authors.keys().max().map_or(FIRST_AUTHOR, |id| id + 1);
+                authors.insert(id, author.to_owned());
+                id
+            }
+        };
+        // ids only have room for 255 authors
+        let author_id = u8::try_from(author_id)?;
+
+        let clock = blocks.iter().map(newest_clock).max().unwrap_or(0);
+        let mut builder = SceneBuilder {
+            tree,
+            blocks: vec![],
+            author: author_id,
+            clock,
+        };
+        builder.tree.apply_all(&blocks[index..=index])?;
+        builder.blocks = blocks;
+        Ok(builder)
+    }
+
+    /// Tree of the page as it is now
+    pub fn tree(&self) -> &SceneTree {
+        &self.tree
+    }
+
+    /// The page with everything added so far
+    pub fn build(self) -> RemarkableFile {
+        RemarkableFile::V6 {
+            tree: self.tree,
+            blocks: self.blocks,
+        }
+    }
+
+    /// Id following the newest id of this author, `length` ids are reserved for text
+    fn next_id(&mut self, length: u32) -> Result<CrdtId, ParseError> {
+        let exhausted = || ParseError::invalid("No ids left for the author of this page");
+        let id = CrdtId {
+            part1: self.author,
+            part2: self.clock.checked_add(1).ok_or_else(exhausted)?,
+        };
+        self.clock = self
+            .clock
+            .checked_add(length.max(1))
+            .ok_or_else(exhausted)?;
+        Ok(id)
+    }
+
+    fn push(&mut self, block: Block) -> Result<(), ParseError> {
+        self.tree.apply(&block)?;
+        self.blocks.push(block);
+        Ok(())
+    }
+
+    /// Last item in group `parent`, new items are added after it
+    fn last_child(&self, parent: &CrdtId) -> Result<CrdtId, ParseError> {
+        let node = self
+            .tree
+            .node(parent)
+            .ok_or_else(|| ParseError::invalid(format!("Group does not exist: {parent:?}")))?;
+        Ok(node
+            .children
+            .sorted_ids()
+            .last()
+            .copied()
+            .unwrap_or_default())
+    }
+
+    /// Add a layer named `label` on top of the existing layers, returns the id of its group
+    pub fn add_layer(&mut self, label: &str) -> Result<CrdtId, ParseError> {
+        let root_id = self.tree.root_id();
+        let node_id = self.next_id(1)?;
+        let timestamp = self.next_id(1)?;
+
+        self.push(Block::SceneTree(SceneTreeBlock {
+            tree_id: node_id,
+            node_id: CrdtId::default(),
+            is_update: true,
+            parent_id: root_id,
+        }))?;
+        let group = Group::default().node_id(node_id).label(LwwValue {
+            timestamp,
+            value: label.to_owned(),
+        });
+        self.push(Block::TreeNode(TreeNodeBlock {
+            group: group.clone(),
+        }))?;
+        let left_id = self.last_child(&root_id)?;
+        self.insert_item(root_id, SceneItem::Group(group), left_id, CrdtId::default())?;
+        Ok(node_id)
+    }
+
+    /// Draw `line` on top of everything in `layer`, returns the id of the line
+    pub fn add_line(&mut self, layer: CrdtId, line: Line) -> Result<CrdtId, ParseError> {
+        let left_id = self.last_child(&layer)?;
+        self.insert_item(layer, SceneItem::Line(line), left_id, CrdtId::default())
+    }
+
+    /// Add `value` to group `parent` between the items `left_id` and `right_id`
+    fn insert_item(
+        &mut self,
+        parent_id: CrdtId,
+        value: SceneItem,
+        left_id: CrdtId,
+        right_id: CrdtId,
+    ) -> Result<CrdtId, ParseError> {
+        let item = CrdtSequenceItem {
+            item_id: self.next_id(1)?,
+            left_id,
+            right_id,
+            deleted_length: 0,
+            value: (),
+        };
+        let block = match value {
+            SceneItem::Group(group) => Block::SceneGroupItem(SceneItemBlock {
+                parent_id,
+                item: with_value(&item, Some(group.node_id)),
+            }),
+            SceneItem::Line(line) => Block::SceneLineItem(SceneItemBlock {
+                parent_id,
+                item: with_value(&item, Some(line)),
+            }),
+            SceneItem::GlyphRange(glyph) => Block::SceneGlyphItem(SceneItemBlock {
+                parent_id,
+                item: with_value(&item, Some(glyph)),
+            }),
+            SceneItem::Text(text) => Block::SceneTextItem(SceneItemBlock {
+                parent_id,
+                item: with_value(&item, Some(text)),
+            }),
+        };
+        self.push(block)?;
+        Ok(item.item_id)
+    }
+
+    /// Item `item_id` of group `parent_id`
+    fn item(
+        &self,
+        parent_id: &CrdtId,
+        item_id: &CrdtId,
+    ) -> Result<CrdtSequenceItem<SceneItem>, ParseError> {
+        self.tree
+            .node(parent_id)
+            .and_then(|node| node.children.items.get(item_id))
+            .cloned()
+            .ok_or_else(|| {
+                ParseError::invalid(format!("Item {item_id:?} does not exist in {parent_id:?}"))
+            })
+    }
+
+    /// Delete item `item_id` from group `parent_id`
+    pub fn delete_item(&mut self, parent_id: CrdtId, item_id: CrdtId) -> Result<(), ParseError> {
+        let mut item = self.item(&parent_id, &item_id)?;
+        item.deleted_length = 1;
+        let block = match &item.value {
+            SceneItem::Group(_) => Block::SceneGroupItem(SceneItemBlock {
+                parent_id,
+                item: with_value(&item, None),
+            }),
+            SceneItem::Line(_) => Block::SceneLineItem(SceneItemBlock {
+                parent_id,
+                item: with_value(&item, None),
+            }),
+            SceneItem::GlyphRange(_) => Block::SceneGlyphItem(SceneItemBlock {
+                parent_id,
+                item: with_value(&item, None),
+            }),
+            SceneItem::Text(_) => Block::SceneTextItem(SceneItemBlock {
+                parent_id,
+                item: with_value(&item, None),
+            }),
+        };
+        self.push(block)
+    }
+
+    /// Move item `item_id` of group `from` on top of the items of group `to`, returns its new id
+    ///
+    /// Lines keep the id they were first drawn as, so they stay in their place in the
+    /// [timeline](crate::export::timeline) and with their author.
+    pub fn move_item(
+        &mut self,
+        item_id: CrdtId,
+        from: CrdtId,
+        to: CrdtId,
+    ) -> Result<CrdtId, ParseError> {
+        let item = self.item(&from, &item_id)?;
+        let origin_id = item.origin_id();
+        let value = match item.value {
+            SceneItem::Line(line) => SceneItem::Line(Line {
+                move_id: Some(origin_id),
+                ..line
+            }),
+            value => value,
+        };
+        self.delete_item(from, item_id)?;
+        let left_id = self.last_child(&to)?;
+        self.insert_item(to, value, left_id, CrdtId::default())
+    }
+
+    /// Item in the root holding the layer with group `layer`
+    fn layer_item(&self, layer: &CrdtId) -> Result<CrdtSequenceItem<SceneItem>, ParseError> {
+        self.tree
+            .root()
+            .and_then(|root| {
+                root.children
+                    .iter()
+                    .find(|item| matches!(&item.value, SceneItem::Group(g) if g.node_id == *layer))
+            })
+            .cloned()
+            .ok_or_else(|| ParseError::invalid(format!("Layer does not exist: {layer:?}")))
+    }
+
+    /// Change the values of layer `layer`, `update` is given the timestamp to set
+    fn update_layer(
+        &mut self,
+        layer: CrdtId,
+        update: impl FnOnce(&mut Group, CrdtId),
+    ) -> Result<(), ParseError> {
+        self.layer_item(&layer)?;
+        let mut group = match self.tree.node(&layer) {
+            Some(group) => group.clone(),
+            None => {
+                return Err(ParseError::invalid(format!(
+                    "Layer does not exist: {layer:?}"
+                )))
+            }
+        };
+        group.children = Default::default();
+        let timestamp = self.next_id(1)?;
+        update(&mut group, timestamp);
+        self.push(Block::TreeNode(TreeNodeBlock { group }))
+    }
+
+    pub fn rename_layer(&mut self, layer: CrdtId, label: &str) -> Result<(), ParseError> {
+        self.update_layer(layer, |group, timestamp| {
+            group.label.update(timestamp, label.to_owned());
+        })
+    }
+
+    pub fn set_layer_visible(&mut self, layer: CrdtId, visible: bool) -> Result<(), ParseError> {
+        self.update_layer(layer, |group, timestamp| {
+            group.visible.update(timestamp, visible);
+        })
+    }
+
+    /// Delete the layer with group `layer` together with everything in it
+    pub fn delete_layer(&mut self, layer: CrdtId) -> Result<(), ParseError> {
+        let item = self.layer_item(&layer)?;
+        self.delete_item(self.tree.root_id(), item.item_id)
+    }
+
+    /// Move the layer with group `layer` to position `index` counted from the bottom
+    pub fn move_layer(&mut self, layer: CrdtId, index: usize) -> Result<(), ParseError> {
+        let root_id = self.tree.root_id();
+        let item = self.layer_item(&layer)?;
+        self.delete_item(root_id, item.item_id)?;
+
+        let others: Vec<CrdtId> = match self.tree.root() {
+            Some(root) => root.children.sorted_ids(),
+            None => vec![],
+        };
+        let index = index.min(others.len());
+        let left_id = index.checked_sub(1).map(|i| others[i]).unwrap_or_default();
+        let right_id = others.get(index).copied().unwrap_or_default();
+        self.insert_item(root_id, item.value, left_id, right_id)?;
+        Ok(())
+    }
+
+    /// Add a paragraph after the typed text of the page, returns the id of its first character
+    ///
+    /// Pages without text get a text box at the position the tablet uses.
+    pub fn add_paragraph(
+        &mut self,
+        text: &str,
+        style: ParagraphStyle,
+    ) -> Result<CrdtId, ParseError> {
+        self.edit_text(|builder, root| {
+            // a paragraph after existing text starts with a newline, which its style is keyed by
+            let (value, paragraph) = match root.items.items.is_empty() {
+                true => (text.to_owned(), 0),
+                false => {
+                    let newlines = root.chars().iter().filter(|(_, c)| *c == '\n').count();
+                    (format!("\n{text}"), newlines + 1)
+                }
+            };
+            let length = u32::try_from(value.chars().count())?;
+            let item_id = builder.next_id(length)?;
+            root.insert(root.chars().len(), &value, item_id)?;
+            let timestamp = builder.next_id(1)?;
+            root.set_paragraph_style(paragraph, style, timestamp)?;
+
+            let first = match paragraph {
+                0 => item_id.part2,
+                _ => item_id.part2 + 1,
+            };
+            Ok(CrdtId {
+                part1: item_id.part1,
+                part2: first,
+            })
+        })
+    }
+
+    /// Insert `text` before character `pos` of the page's text, see [Text::insert]
+    ///
+    /// Returns the id of the first inserted character.
+    pub fn insert_text(&mut self, pos: usize, text: &str) -> Result<CrdtId, ParseError> {
+        self.edit_text(|builder, root| {
+            let id = builder.next_id(u32::try_from(text.chars().count())?)?;
+            root.insert(pos, text, id)?;
+            Ok(id)
+        })
+    }
+
+    /// Delete characters `range` of the page's text, see [Text::delete]
+    pub fn delete_text(&mut self, range: Range<usize>) -> Result<(), ParseError> {
+        self.edit_text(|_, root| root.delete(range))
+    }
+
+    /// Set the style of paragraph `paragraph` of the page's text, see
+    /// [Text::set_paragraph_style]
+    pub fn set_paragraph_style(
+        &mut self,
+        paragraph: usize,
+        style: ParagraphStyle,
+    ) -> Result<(), ParseError> {
+        self.edit_text(|builder, root| {
+            let timestamp = builder.next_id(1)?;
+            root.set_paragraph_style(paragraph, style, timestamp)
+        })
+    }
+
+    /// Change the text of the page with `edit`, an empty text is added if the page has none
+    ///
+    /// The page keeps a single `RootTextBlock` which is replaced with the changed text.
+    fn edit_text<T>(
+        &mut self,
+        edit: impl FnOnce(&mut SceneBuilder, &mut Text) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        let index = self
+            .blocks
+            .iter()
+            .position(|b| matches!(b, Block::RootText(_)));
+        let mut root_text = match index.map(|i| &self.blocks[i]) {
+            Some(Block::RootText(block)) => block.clone(),
+            _ => RootTextBlock {
+                block_id: CrdtId::default(),
+                text: Text {
+                    items: Default::default(),
+                    styles: HashMap::new(),
+                    x: -468.0,
+                    y: 234.0,
+                    width: 936.0,
+                },
+            },
+        };
+        let result = edit(self, &mut root_text.text)?;
+
+        let block = Block::RootText(root_text);
+        self.tree.apply(&block)?;
+        match index {
+            Some(index) => self.blocks[index] = block,
+            None => self.blocks.push(block),
+        }
+        Ok(result)
+    }
+}
+
+/// `item` with its value replaced by `value`, as it is stored in a block
+fn with_value<T, N>(item: &CrdtSequenceItem<T>, value: Option<N>) -> CrdtSequenceItem<Option<N>> {
+    CrdtSequenceItem {
+        item_id: item.item_id,
+        left_id: item.left_id,
+        right_id: item.right_id,
+        deleted_length: item.deleted_length,
+        value,
+    }
+}
+
+/// Largest clock value used by any id or timestamp in `block`
+fn newest_clock(block: &Block) -> u32 {
+    match block {
+        Block::MigrationInfo(b) => b.migration_id.part2,
+        Block::SceneTree(b) => b.tree_id.part2,
+        Block::TreeNode(b) => {
+            let group = &b.group;
+            [
+                Some(group.node_id),
+                Some(group.label.timestamp),
+                Some(group.visible.timestamp),
+                group.anchor_id.as_ref().map(|v| v.timestamp),
+                group.anchor_type.as_ref().map(|v| v.timestamp),
+                group.anchor_threshold.as_ref().map(|v| v.timestamp),
+                group.anchor_origin_x.as_ref().map(|v| v.timestamp),
+            ]
+            .into_iter()
+            .flatten()
+            .map(|id| id.part2)
+            .max()
+            .unwrap_or(0)
+        }
+        Block::SceneGlyphItem(b) => b.item.item_id.part2,
+        Block::SceneGroupItem(b) => b.item.item_id.part2,
+        Block::SceneLineItem(b) => b.item.item_id.part2,
+        Block::SceneTextItem(b) => b.item.item_id.part2,
+        Block::RootText(b) => {
+            let items = b.text.items.items.values().map(|i| last_char_id(i).part2);
+            let styles = b.text.styles.values().map(|style| style.timestamp.part2);
+            items.chain(styles).max().unwrap_or(0)
+        }
+        Block::AuthorsIds(_) | Block::PageInfo(_) => 0,
+    }
+}
+
+/// Id of the last character of a text item, each character has its own id following the item's
+fn last_char_id(item: &CrdtSequenceItem<TextItem>) -> CrdtId {
+    let length = match &item.value {
+        TextItem::Text(text) => (text.chars().count() as u32).max(item.deleted_length),
+        TextItem::FormatCode(_) => 1,
+    };
+    CrdtId {
+        part1: item.item_id.part1,
+        part2: item.item_id.part2.saturating_add(length.saturating_sub(1)),
+    }
+}
@@ -1,4 +1,5 @@
 use self::{glyph_range::GlyphRange, group::Group, line::Line, text::Text};
+use super::crdt::{CrdtId, CrdtSequenceItem};
 
 pub mod glyph_range;
 pub mod group;
@@ -13,3 +14,18 @@ pub enum SceneItem {
     Text(Text),
     GlyphRange(GlyphRange),
 }
+
+impl CrdtSequenceItem<SceneItem> {
+    /// Id the item was first created with, which tells who made it and when
+    ///
+    /// Lines moved into another group are added again with a new id, they keep their first id as
+    /// [Line::move_id].
+    pub fn origin_id(&self) -> CrdtId {
+        match &self.value {
+            SceneItem::Line(Line {
+                move_id: Some(id), ..
+            }) => *id,
+            _ => self.item_id,
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{shared::pen_color::PenColor, v6::TypeParse, ParseError};
+use crate::{
+    shared::pen_color::PenColor,
+    v6::{tagged_bit_writer::TaggedBitwriter, TypeParse, TypeWrite},
+    ParseError,
+};
 
 #[derive(Debug, Clone)]
 pub struct Rectangle {
@@ -21,6 +25,16 @@ impl TypeParse for Rectangle {
     }
 }
 
+impl TypeWrite for Rectangle {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        for value in [self.x, self.y, self.w, self.h] {
+            writer.bit_writer.write_f64(value);
+        }
+        Ok(())
+    }
+}
+
+/// Highlighted text, `start` and `length` are in characters of the text the highlight is on
 #[derive(Debug, Clone)]
 pub struct GlyphRange {
     pub start: u32,
@@ -36,12 +50,16 @@ impl TypeParse for GlyphRange {
     ) -> Result<Self, crate::ParseError> {
         let start = reader.read_u32(2)?;
         let length = reader.read_u32(3)?;
-        let color = PenColor::try_from(reader.read_u32(4)?)?;
+        let color = PenColor::from(reader.read_u32(4)?);
         let text = reader.read_string(5)?;
 
         let subblock = reader.read_subblock(6)?;
-        let rectangles = (0..reader.bit_reader.read_varuint()?)
-            .into_iter()
+        let amount_rectangles = reader.bit_reader.read_varuint()?;
+        // every rectangle is 4 doubles
+        reader
+            .bit_reader
+            .check_count(amount_rectangles as u64, 32)?;
+        let rectangles = (0..amount_rectangles)
             .map(|_| Rectangle::parse(reader))
             .collect::<Result<Vec<Rectangle>, ParseError>>()?;
         subblock.validate_size(reader)?;
@@ -55,3 +73,77 @@ impl TypeParse for GlyphRange {
         })
     }
 }
+
+impl TypeWrite for GlyphRange {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        writer.write_u32(2, self.start);
+        writer.write_u32(3, self.length);
+        writer.write_u32(4, u32::from(self.color));
+        writer.write_string(5, &self.text)?;
+        writer.write_subblock(6, |writer| {
+            writer
+                .bit_writer
+                .write_varuint(u32::try_from(self.rectangles.len())?);
+            for rectangle in self.rectangles.iter() {
+                rectangle.write(writer)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Highlighted text of a page, made from one or more [GlyphRange]s of the same color
+#[derive(Debug, Clone)]
+pub struct Highlight {
+    pub start: u32,
+    pub length: u32,
+    pub text: String,
+    pub color: PenColor,
+    pub rectangles: Vec<Rectangle>,
+}
+
+impl Highlight {
+    fn end(&self) -> u32 {
+        self.start.saturating_add(self.length)
+    }
+
+    /// Merge highlights of the same color whose ranges touch or overlap, the result is ordered
+    /// by where the highlights start
+    pub fn merge_ranges(highlights: Vec<Highlight>) -> Vec<Highlight> {
+        let mut highlights = highlights;
+        highlights.sort_by_key(|h| h.start);
+
+        let mut merged: Vec<Highlight> = vec![];
+        for highlight in highlights {
+            // last highlight of the same color, ranges of other colors may lie in between
+            let previous = merged
+                .iter_mut()
+                .rev()
+                .find(|h| h.color == highlight.color)
+                .filter(|h| highlight.start <= h.end());
+            match previous {
+                Some(previous) => {
+                    // only add the text that isn't already part of the previous range
+                    let overlap = (previous.end() - highlight.start) as usize;
+                    previous.text.extend(highlight.text.chars().skip(overlap));
+                    previous.length = previous.end().max(highlight.end()) - previous.start;
+                    previous.rectangles.extend(highlight.rectangles);
+                }
+                None => merged.push(highlight),
+            }
+        }
+        merged
+    }
+}
+
+impl From<&GlyphRange> for Highlight {
+    fn from(glyph: &GlyphRange) -> Self {
+        Highlight {
+            start: glyph.start,
+            length: glyph.length,
+            text: glyph.text.clone(),
+            color: glyph.color,
+            rectangles: glyph.rectangles.clone(),
+        }
+    }
+}
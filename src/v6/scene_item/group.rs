@@ -39,6 +39,16 @@ impl Group {
         self.label = label;
         self
     }
+
+    /// Take the label, visibility and anchor of `other` where they are newer, children are kept
+    pub fn merge_values(&mut self, other: Group) {
+        self.label.merge(other.label);
+        self.visible.merge(other.visible);
+        LwwValue::merge_option(&mut self.anchor_id, other.anchor_id);
+        LwwValue::merge_option(&mut self.anchor_type, other.anchor_type);
+        LwwValue::merge_option(&mut self.anchor_threshold, other.anchor_threshold);
+        LwwValue::merge_option(&mut self.anchor_origin_x, other.anchor_origin_x);
+    }
 }
 
 impl Default for Group {
@@ -2,7 +2,11 @@ use crate::{
     shared::{pen_color::PenColor, tool::Tool},
     v6::{
         block::{BlockInfo, BlockParse},
+        crdt::CrdtId,
         scene_item::point::Point,
+        tagged_bit_reader::TagType,
+        tagged_bit_writer::TaggedBitwriter,
+        TypeWrite,
     },
     ParseError,
 };
@@ -14,17 +18,55 @@ pub struct Line {
     pub points: Vec<Point>,
     pub thickness_scale: f64,
     pub starting_length: f32,
+    /// Id stored after the points, files from the tablet seen so far hold `0:1`
+    ///
+    /// Some files leave it out. When a line was drawn is given by the id of its item, see
+    /// [crate::export::timeline].
+    pub timestamp: Option<CrdtId>,
+    /// Points as stored by a version 1 block
+    ///
+    /// Version 1 points can't be converted back exactly, their data is written back instead of
+    /// the points for as long as it still holds the same points.
+    pub v1_point_data: Option<Vec<u8>>,
+    /// Id of the item the line was first drawn as, set when it is moved into another group
+    ///
+    /// Only stored by newer firmware, see [crate::v6::crdt::CrdtSequenceItem::origin_id].
+    pub move_id: Option<CrdtId>,
+    /// Fields after the known ones, stored by firmware newer than this parser
+    ///
+    /// They are written back unchanged after the known fields.
+    pub unknown_fields: Vec<u8>,
 }
 
+impl Line {
+    /// Line drawn with `tool` and `color` in the tablet's medium thickness
+    pub fn new(tool: Tool, color: PenColor, points: Vec<Point>) -> Line {
+        Line {
+            color,
+            tool,
+            points,
+            thickness_scale: 2.0,
+            starting_length: 0.0,
+            timestamp: Some(CrdtId::default()),
+            v1_point_data: None,
+            move_id: None,
+            unknown_fields: vec![],
+        }
+    }
+}
+
+/// Size of a single serialized point for a line block version
+///
+/// Version 1 stores every value as a float, version 2 and later use the compact layout.
 pub fn point_serialize_size(version: u8) -> Result<u32, ParseError> {
     match version {
-        1 => return Ok(0x18),
-        2 => return Ok(0x0E),
-        _ => {
+        0 => {
             return Err(ParseError::unsupported(format!(
                 "Block unsupported version: {version}"
             )))
         }
+        1 => return Ok(0x18),
+        _ => return Ok(0x0E),
     }
 }
 
@@ -33,27 +75,69 @@ impl BlockParse for Line {
         info: &BlockInfo,
         reader: &mut crate::v6::tagged_bit_reader::TaggedBitreader<impl crate::bitreader::Readable>,
     ) -> Result<Self, crate::ParseError> {
-        let tool = Tool::try_from(reader.read_u32(1)?)?;
-        let color = PenColor::try_from(reader.read_u32(2)?)?;
+        let tool = Tool::from(reader.read_u32(1)?);
+        let color = PenColor::from(reader.read_u32(2)?);
         let thickness_scale = reader.read_f64(3)?;
         let starting_length = reader.read_f32(4)?;
 
         let subblock = reader.read_subblock(5)?;
         let point_size = point_serialize_size(info.current_version)?;
-        if info.size % point_size != 0 {
+        if subblock.size % point_size != 0 {
             return Err(ParseError::invalid(format!(
                 "Invalid point data size. {} is not multiple of {point_size}",
-                info.size
+                subblock.size
+            )));
+        }
+        let amount_points = subblock.size / point_size;
+        let max_points = reader.bit_reader.limits().max_points_per_line;
+        if amount_points > max_points {
+            return Err(ParseError::limit_exceeded(format!(
+                "Line with {amount_points} points exceeds limit of {max_points}"
             )));
         }
-        let points = (0..info.size / point_size)
-            .into_iter()
-            .map(|_| Point::parse(info, reader))
-            .collect::<Result<Vec<Point>, ParseError>>()?;
+        reader
+            .bit_reader
+            .check_count(amount_points as u64, point_size as u64)?;
+        let (points, v1_point_data) = if info.current_version == 1 {
+            let data = reader.bit_reader.read_bytes(subblock.size as usize)?;
+            (v1_points(&data), Some(data))
+        } else {
+            let points = (0..amount_points)
+                .map(|_| Point::parse(info, reader))
+                .collect::<Result<Vec<Point>, ParseError>>()?;
+            (points, None)
+        };
         subblock.validate_size(reader)?;
 
-        // XXX unused
-        let _timestamp = reader.read_id(6);
+        let timestamp =
+            if info.has_bytes_remaining(reader.bit_reader) && reader.has_tag(6, TagType::ID)? {
+                Some(reader.read_id(6)?)
+            } else {
+                None
+            };
+
+        let move_id =
+            if info.has_bytes_remaining(reader.bit_reader) && reader.has_tag(7, TagType::ID)? {
+                Some(reader.read_id(7)?)
+            } else {
+                None
+            };
+
+        // newer firmware stores the actual color of highlighters and shaders as ARGB
+        let color =
+            if info.has_bytes_remaining(reader.bit_reader) && reader.has_tag(8, TagType::Byte4)? {
+                color.with_argb(reader.read_u32(8)?)
+            } else {
+                color
+            };
+
+        // keep fields added by firmware versions newer than this parser
+        let unknown_fields = if info.has_bytes_remaining(reader.bit_reader) {
+            let remaining = info.end_offset() - reader.bit_reader.position();
+            reader.bit_reader.read_bytes(remaining as usize)?
+        } else {
+            vec![]
+        };
 
         return Ok(Line {
             tool,
@@ -61,6 +145,73 @@ impl BlockParse for Line {
             thickness_scale,
             starting_length,
             points,
+            timestamp,
+            v1_point_data,
+            move_id,
+            unknown_fields,
         });
     }
 }
+
+/// Block version lines are written as, unless they hold unchanged version 1 points
+pub const LINE_WRITE_VERSION: u8 = 2;
+
+/// Points of version 1 point data
+fn v1_points(data: &[u8]) -> Vec<Point> {
+    data.chunks_exact(0x18)
+        .map(|point| {
+            let mut values = point
+                .chunks_exact(4)
+                .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]));
+            let mut next = || values.next().unwrap_or_default();
+            Point::from_v1(next(), next(), [next(), next(), next(), next()])
+        })
+        .collect()
+}
+
+impl Line {
+    /// Version 1 point data that still holds the points of the line
+    fn unchanged_v1_point_data(&self) -> Option<&[u8]> {
+        let data = self.v1_point_data.as_deref()?;
+        (v1_points(data) == self.points).then_some(data)
+    }
+
+    /// Block version the line is written as
+    pub fn write_version(&self) -> u8 {
+        match self.unchanged_v1_point_data() {
+            Some(_) => 1,
+            None => LINE_WRITE_VERSION,
+        }
+    }
+}
+
+impl TypeWrite for Line {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        writer.write_u32(1, u32::from(self.tool));
+        writer.write_u32(2, u32::from(self.color));
+        writer.write_f64(3, self.thickness_scale);
+        writer.write_f32(4, self.starting_length);
+        writer.write_subblock(5, |writer| {
+            match self.unchanged_v1_point_data() {
+                Some(data) => writer.bit_writer.write_bytes(data),
+                None => {
+                    for point in self.points.iter() {
+                        point.write(writer);
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        if let Some(timestamp) = self.timestamp {
+            writer.write_id(6, timestamp)?;
+        }
+        if let Some(move_id) = self.move_id {
+            writer.write_id(7, move_id)?;
+        }
+        if let PenColor::Argb { argb, .. } = self.color {
+            writer.write_u32(8, argb);
+        }
+        writer.bit_writer.write_bytes(&self.unknown_fields);
+        Ok(())
+    }
+}
@@ -1,8 +1,8 @@
 use std::f32::consts::PI;
 
-use crate::v6::block::BlockParse;
+use crate::v6::{block::BlockParse, tagged_bit_writer::TaggedBitwriter};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -20,18 +20,11 @@ impl BlockParse for Point {
         let x = reader.bit_reader.read_f32()?;
         let y = reader.bit_reader.read_f32()?;
         if info.current_version == 1 {
-            let speed = reader.bit_reader.read_f32()? * 4.0;
-            let direction = (255.0 * reader.bit_reader.read_f32()?) / (PI * 2.0);
-            let width = reader.bit_reader.read_f32()? * 4.0;
-            let pressure = reader.bit_reader.read_f32()? * 255.0;
-            return Ok(Point {
-                x,
-                y,
-                speed,
-                direction,
-                width,
-                pressure,
-            });
+            let mut values = [0.0; 4];
+            for value in values.iter_mut() {
+                *value = reader.bit_reader.read_f32()?;
+            }
+            return Ok(Point::from_v1(x, y, values));
         } else {
             let speed = f32::from(reader.bit_reader.read_u16()?);
             let width = f32::from(reader.bit_reader.read_u16()?);
@@ -48,3 +41,28 @@ impl BlockParse for Point {
         }
     }
 }
+
+impl Point {
+    /// Point of a line block version 1, which stores speed, direction, width and pressure as
+    /// floats in other units
+    pub(crate) fn from_v1(x: f32, y: f32, [speed, direction, width, pressure]: [f32; 4]) -> Point {
+        Point {
+            x,
+            y,
+            speed: speed * 4.0,
+            direction: (255.0 * direction) / (PI * 2.0),
+            width: width * 4.0,
+            pressure: pressure * 255.0,
+        }
+    }
+
+    /// Write in the compact layout of line block version 2
+    pub fn write(&self, writer: &mut TaggedBitwriter) {
+        writer.bit_writer.write_f32(self.x);
+        writer.bit_writer.write_f32(self.y);
+        writer.bit_writer.write_u16(self.speed.round() as u16);
+        writer.bit_writer.write_u16(self.width.round() as u16);
+        writer.bit_writer.write_u8(self.direction.round() as u8);
+        writer.bit_writer.write_u8(self.pressure.round() as u8);
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Range};
 
 use crate::{
     bitreader::Readable,
@@ -6,14 +6,13 @@ use crate::{
         crdt::{CrdtId, CrdtSequence, CrdtSequenceItem},
         lwwvalue::LwwValue,
         tagged_bit_reader::{TagType, TaggedBitreader},
-        TypeParse,
+        tagged_bit_writer::TaggedBitwriter,
+        TypeParse, TypeWrite,
     },
     ParseError,
 };
 
-
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// Text paragraph style.
 pub enum ParagraphStyle {
     BASIC,
@@ -42,6 +41,19 @@ impl TryFrom<u8> for ParagraphStyle {
     }
 }
 
+impl From<ParagraphStyle> for u8 {
+    fn from(style: ParagraphStyle) -> Self {
+        match style {
+            ParagraphStyle::BASIC => 0x00,
+            ParagraphStyle::PLAIN => 0x01,
+            ParagraphStyle::HEADING => 0x02,
+            ParagraphStyle::BOLD => 0x03,
+            ParagraphStyle::BULLET => 0x04,
+            ParagraphStyle::BULLET2 => 0x05,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum TextItem {
     FormatCode(u32),
@@ -66,8 +78,8 @@ impl TypeParse for Text {
 
         // Text items
         let amount_items = reader.bit_reader.read_varuint()?;
+        reader.bit_reader.check_count(amount_items as u64, 1)?;
         let items = (0..amount_items)
-            .into_iter()
             .map(|_| {
                 let subblock = reader.read_subblock(0)?;
                 let item_id = reader.read_id(2)?;
@@ -115,8 +127,8 @@ impl TypeParse for Text {
 
         // Formatting
         let amount_styles = reader.bit_reader.read_varuint()?;
+        reader.bit_reader.check_count(amount_styles as u64, 1)?;
         let styles = (0..amount_styles)
-            .into_iter()
             .map(|_| {
                 let id = CrdtId::parse(reader)?;
                 let timestamp = reader.read_id(1)?;
@@ -160,3 +172,324 @@ impl TypeParse for Text {
         })
     }
 }
+
+impl TypeWrite for Text {
+    fn write(&self, writer: &mut TaggedBitwriter) -> Result<(), ParseError> {
+        writer.write_subblock(2, |writer| {
+            writer.write_subblock(1, |writer| {
+                writer.write_subblock(1, |writer| {
+                    writer
+                        .bit_writer
+                        .write_varuint(u32::try_from(self.items.items.len())?);
+                    for item in self.items.iter() {
+                        writer.write_subblock(0, |writer| {
+                            writer.write_id(2, item.item_id)?;
+                            writer.write_id(3, item.left_id)?;
+                            writer.write_id(4, item.right_id)?;
+                            writer.write_u32(5, item.deleted_length);
+                            match &item.value {
+                                // deleted characters have no value
+                                TextItem::Text(text)
+                                    if text.is_empty() && item.deleted_length > 0 =>
+                                {
+                                    Ok(())
+                                }
+                                TextItem::Text(text) => writer.write_subblock(6, |writer| {
+                                    writer.write_string_value(text);
+                                    Ok(())
+                                }),
+                                TextItem::FormatCode(code) => writer.write_subblock(6, |writer| {
+                                    writer.write_string_value("");
+                                    writer.write_u32(2, *code);
+                                    Ok(())
+                                }),
+                            }
+                        })?;
+                    }
+                    Ok(())
+                })
+            })?;
+
+            writer.write_subblock(2, |writer| {
+                writer.write_subblock(1, |writer| {
+                    writer
+                        .bit_writer
+                        .write_varuint(u32::try_from(self.styles.len())?);
+                    // sorted so writing the same text gives the same bytes
+                    let mut styles: Vec<(&CrdtId, &LwwValue<ParagraphStyle>)> =
+                        self.styles.iter().collect();
+                    styles.sort_by_key(|(id, _)| (id.part1, id.part2));
+                    for (id, style) in styles {
+                        id.write(writer)?;
+                        writer.write_id(1, style.timestamp)?;
+                        writer.write_subblock(2, |writer| {
+                            // XXX always 17 in files from the device
+                            writer.bit_writer.write_u8(17);
+                            writer.bit_writer.write_u8(u8::from(style.value));
+                            Ok(())
+                        })?;
+                    }
+                    Ok(())
+                })
+            })
+        })?;
+
+        writer.write_subblock(3, |writer| {
+            writer.bit_writer.write_f64(self.x);
+            writer.bit_writer.write_f64(self.y);
+            Ok(())
+        })?;
+        writer.write_f32(4, self.width);
+        Ok(())
+    }
+}
+
+/// One character of a [Text], items hold runs of characters with consecutive ids
+#[derive(Debug, Clone, Copy)]
+enum Char {
+    Text(char),
+    FormatCode(u32),
+    Deleted,
+}
+
+impl Text {
+    /// Characters of the text in order together with their ids, deleted ones left out
+    pub fn chars(&self) -> Vec<(CrdtId, char)> {
+        self.expand()
+            .into_iter()
+            .filter_map(|c| match c.value {
+                Char::Text(value) => Some((c.item_id, value)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Insert `text` before the character at `pos`, counted in characters of [Text::chars]
+    ///
+    /// The characters get the ids starting at `id`, which have to be unused in the page, ids
+    /// already used by the text are an error. Like on the device the new item is linked to the
+    /// characters left and right of it, an item containing `pos` is split.
+    pub fn insert(&mut self, pos: usize, text: &str, id: CrdtId) -> Result<(), ParseError> {
+        let mut chars = self.expand();
+        let index = match pos {
+            0 => 0,
+            pos => char_index(&chars, pos - 1)? + 1,
+        };
+        let left = index
+            .checked_sub(1)
+            .map(|i| chars[i].item_id)
+            .unwrap_or_default();
+        let right = chars.get(index).map(|c| c.item_id).unwrap_or_default();
+
+        let length = u32::try_from(text.chars().count())?;
+        let end = id
+            .part2
+            .checked_add(length)
+            .ok_or_else(|| ParseError::invalid("No ids left for the inserted text"))?;
+        // deleted characters keep their ids
+        if chars
+            .iter()
+            .any(|c| c.item_id.part1 == id.part1 && (id.part2..end).contains(&c.item_id.part2))
+        {
+            return Err(ParseError::invalid(
+                "Ids of the inserted text are already used",
+            ));
+        }
+        let char_id = |i: usize| CrdtId {
+            part1: id.part1,
+            part2: id.part2 + i as u32,
+        };
+        let last = text.chars().count().saturating_sub(1);
+        let inserted = text.chars().enumerate().map(|(i, value)| CrdtSequenceItem {
+            item_id: char_id(i),
+            left_id: if i == 0 { left } else { char_id(i - 1) },
+            right_id: if i == last { right } else { char_id(i + 1) },
+            deleted_length: 0,
+            value: Char::Text(value),
+        });
+        chars.splice(index..index, inserted);
+        self.items = collapse(chars);
+        Ok(())
+    }
+
+    /// Delete the characters in `range`, counted in characters of [Text::chars]
+    ///
+    /// Deleted characters stay in the text as tombstones so changes of other authors that refer
+    /// to them can still be placed.
+    pub fn delete(&mut self, range: Range<usize>) -> Result<(), ParseError> {
+        let mut chars = self.expand();
+        if range.start > range.end {
+            return Err(ParseError::invalid(format!(
+                "Invalid range of characters: {range:?}"
+            )));
+        }
+        if range.is_empty() {
+            return Ok(());
+        }
+        let start = char_index(&chars, range.start)?;
+        let end = char_index(&chars, range.end - 1)?;
+        for c in chars[start..=end].iter_mut() {
+            if let Char::Text(_) = c.value {
+                c.value = Char::Deleted;
+                c.deleted_length = 1;
+            }
+        }
+        self.items = collapse(chars);
+        Ok(())
+    }
+
+    /// Set the style of paragraph `paragraph`, counted from 0 at the start of the text
+    ///
+    /// Styles are keyed by the newline starting the paragraph, the first paragraph by `0:0`.
+    /// Nothing changes if the style was set at a newer `timestamp` already.
+    pub fn set_paragraph_style(
+        &mut self,
+        paragraph: usize,
+        style: ParagraphStyle,
+        timestamp: CrdtId,
+    ) -> Result<(), ParseError> {
+        let id = match paragraph {
+            0 => CrdtId::default(),
+            paragraph => self
+                .chars()
+                .into_iter()
+                .filter(|(_, c)| *c == '\n')
+                .nth(paragraph - 1)
+                .map(|(id, _)| id)
+                .ok_or_else(|| {
+                    ParseError::invalid(format!("Paragraph {paragraph} does not exist"))
+                })?,
+        };
+        match self.styles.get_mut(&id) {
+            Some(current) => {
+                current.update(timestamp, style);
+            }
+            None => {
+                self.styles.insert(
+                    id,
+                    LwwValue {
+                        timestamp,
+                        value: style,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Every character, format code and deleted character in order
+    fn expand(&self) -> Vec<CrdtSequenceItem<Char>> {
+        let chars: CrdtSequence<Char> = self
+            .items
+            .items
+            .values()
+            .flat_map(|item| {
+                let values: Vec<Char> = match &item.value {
+                    TextItem::Text(text) if text.is_empty() => {
+                        vec![Char::Deleted; item.deleted_length as usize]
+                    }
+                    TextItem::Text(text) => text.chars().map(Char::Text).collect(),
+                    TextItem::FormatCode(code) => vec![Char::FormatCode(*code)],
+                };
+                let id = |i: usize| CrdtId {
+                    part1: item.item_id.part1,
+                    part2: item.item_id.part2.saturating_add(i as u32),
+                };
+                let last = values.len().saturating_sub(1);
+                values
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(i, value)| CrdtSequenceItem {
+                        item_id: id(i),
+                        left_id: if i == 0 { item.left_id } else { id(i - 1) },
+                        right_id: if i == last { item.right_id } else { id(i + 1) },
+                        deleted_length: matches!(value, Char::Deleted) as u32,
+                        value,
+                    })
+            })
+            .collect();
+        in_order(chars)
+    }
+
+    /// Items of this text combined with those of `other` character by character
+    ///
+    /// Runs split differently on both sides are combined by the ids of their characters, a
+    /// character deleted on either side stays deleted.
+    pub(crate) fn merged_items(&self, other: &Text) -> CrdtSequence<TextItem> {
+        let mut chars: CrdtSequence<Char> = self.expand().into_iter().collect();
+        for c in other.expand() {
+            match chars.items.get_mut(&c.item_id) {
+                Some(existing) => {
+                    if let Char::Deleted = c.value {
+                        *existing = c;
+                    }
+                }
+                None => {
+                    chars.push(c);
+                }
+            }
+        }
+        collapse(in_order(chars))
+    }
+}
+
+/// Characters of `chars` in sequence order
+fn in_order(chars: CrdtSequence<Char>) -> Vec<CrdtSequenceItem<Char>> {
+    let order = chars.sorted_ids();
+    let mut items = chars.items;
+    order
+        .into_iter()
+        .filter_map(|id| items.remove(&id))
+        .collect()
+}
+
+/// Index in `chars` of the character at `pos` in [Text::chars]
+fn char_index(chars: &[CrdtSequenceItem<Char>], pos: usize) -> Result<usize, ParseError> {
+    chars
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| matches!(c.value, Char::Text(_)))
+        .nth(pos)
+        .map(|(index, _)| index)
+        .ok_or_else(|| ParseError::invalid(format!("Character {pos} is outside of the text")))
+}
+
+/// Join characters that follow each other in order and by id into items again
+fn collapse(chars: Vec<CrdtSequenceItem<Char>>) -> CrdtSequence<TextItem> {
+    let mut items: Vec<CrdtSequenceItem<TextItem>> = vec![];
+    let mut previous: Option<CrdtSequenceItem<Char>> = None;
+    for c in chars {
+        let joins = previous.as_ref().map_or(false, |p| {
+            p.right_id == c.item_id
+                && c.left_id == p.item_id
+                && p.item_id.part1 == c.item_id.part1
+                && p.item_id.part2.checked_add(1) == Some(c.item_id.part2)
+                && matches!(
+                    (p.value, c.value),
+                    (Char::Text(_), Char::Text(_)) | (Char::Deleted, Char::Deleted)
+                )
+        });
+        match items.last_mut() {
+            Some(item) if joins => {
+                match (&mut item.value, c.value) {
+                    (TextItem::Text(text), Char::Text(value)) => text.push(value),
+                    _ => item.deleted_length += 1,
+                }
+                item.right_id = c.right_id;
+            }
+            _ => items.push(CrdtSequenceItem {
+                item_id: c.item_id,
+                left_id: c.left_id,
+                right_id: c.right_id,
+                deleted_length: c.deleted_length,
+                value: match c.value {
+                    Char::Text(value) => TextItem::Text(value.to_string()),
+                    Char::FormatCode(code) => TextItem::FormatCode(code),
+                    Char::Deleted => TextItem::Text(String::new()),
+                },
+            }),
+        }
+        previous = Some(c);
+    }
+    items.into_iter().collect()
+}
@@ -1,39 +1,155 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::ParseError;
+use crate::{ParseError, ParseLimits};
 
 use super::{
     block::Block,
     crdt::{CrdtId, CrdtSequenceItem},
-    scene_item::{group::Group, text::Text, SceneItem},
+    scene_item::{glyph_range::Highlight, group::Group, text::Text, SceneItem},
 };
 
-struct Node {
-    id: CrdtId,
-    children: Vec<Node>,
-    is_layer: bool,
-    layer: i32,
-    // value: *SceneTreeNode
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SceneTree {
     nodes: HashMap<CrdtId, Group>,
     root_id: CrdtId,
     root_text: Option<Text>,
+    /// Uuids of the authors by the id used in [CrdtId::part1]
+    authors: HashMap<u16, String>,
+    /// Depth of every group node, used to bound how deep groups can be nested
+    depths: HashMap<CrdtId, u32>,
+    /// Groups that hold a copy of every group node, kept up to date by [SceneTree::apply]
+    containers: HashMap<CrdtId, HashSet<CrdtId>>,
+    /// Nodes whose values were set by a TreeNodeBlock
+    described: HashSet<CrdtId>,
+    limits: ParseLimits,
+}
+
+impl Default for SceneTree {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SceneTree {
+    /// Add an empty group node, nothing happens if the node already exists
     pub fn add_node(&mut self, id: CrdtId) {
-        let mut group = Group::default();
-        group.node_id = id.clone();
-        self.nodes.insert(id, group);
+        self.nodes
+            .entry(id)
+            .or_insert_with(|| Group::default().node_id(id));
+    }
+
+    pub fn root_id(&self) -> CrdtId {
+        self.root_id
     }
 
     pub fn get_node(&mut self, id: &CrdtId) -> Option<&Group> {
         self.nodes.get(id)
     }
 
+    /// Group node `id`, without needing mutable access
+    pub fn node(&self, id: &CrdtId) -> Option<&Group> {
+        self.nodes.get(id)
+    }
+
+    /// Group at the root of the tree, its children are usually the layers of the page
+    pub fn root(&self) -> Option<&Group> {
+        self.nodes.get(&self.root_id)
+    }
+
+    /// Groups directly under the root from bottom to top, which are the layers of a page
+    pub fn layers(&self) -> Vec<&Group> {
+        let mut layers = vec![];
+        if let Some(root) = self.root() {
+            for item in root.children.iter() {
+                if let SceneItem::Group(group) = &item.value {
+                    layers.extend(self.nodes.get(&group.node_id));
+                }
+            }
+        }
+        layers
+    }
+
+    /// Typed text of the page
+    pub fn root_text(&self) -> Option<&Text> {
+        self.root_text.as_ref()
+    }
+
+    /// Items with a value in drawing order together with the group they are in
+    ///
+    /// Nested groups are descended into instead of being returned, children of hidden groups
+    /// are left out.
+    pub fn visible_items(&self) -> Vec<(&Group, &SceneItem)> {
+        self.visible_sequence_items()
+            .into_iter()
+            .map(|(group, item)| (group, &item.value))
+            .collect()
+    }
+
+    /// [SceneTree::visible_items] together with their ids
+    pub fn visible_sequence_items(&self) -> Vec<(&Group, &CrdtSequenceItem<SceneItem>)> {
+        let mut items = vec![];
+        let mut visited = HashSet::new();
+        if let Some(root) = self.root() {
+            self.collect_visible_items(root, &mut items, &mut visited);
+        }
+        items
+    }
+
+    /// Uuids of the authors that wrote to this page by the id they have in this page
+    pub fn authors(&self) -> &HashMap<u16, String> {
+        &self.authors
+    }
+
+    /// Uuid of the author that created the item, text character or change with `id`
+    pub fn author_of(&self, id: &CrdtId) -> Option<&str> {
+        self.authors.get(&(id.part1 as u16)).map(String::as_str)
+    }
+
+    /// Visible items created by the author with uuid `author`
+    pub fn items_by_author(&self, author: &str) -> Vec<(&Group, &SceneItem)> {
+        self.visible_sequence_items()
+            .into_iter()
+            .filter(|(_, item)| self.author_of(&item.origin_id()) == Some(author))
+            .map(|(group, item)| (group, &item.value))
+            .collect()
+    }
+
+    /// Highlighted text on this page ordered by position in the text
+    ///
+    /// Ranges of the same color that touch or overlap are merged into one highlight.
+    pub fn highlights(&self) -> Vec<Highlight> {
+        let highlights = self
+            .visible_items()
+            .into_iter()
+            .filter_map(|(_, item)| match item {
+                SceneItem::GlyphRange(glyph) => Some(Highlight::from(glyph)),
+                _ => None,
+            })
+            .collect();
+        Highlight::merge_ranges(highlights)
+    }
+
+    fn collect_visible_items<'a>(
+        &'a self,
+        group: &'a Group,
+        items: &mut Vec<(&'a Group, &'a CrdtSequenceItem<SceneItem>)>,
+        visited: &mut HashSet<CrdtId>,
+    ) {
+        if !group.visible.value || !visited.insert(group.node_id) {
+            return;
+        }
+        for item in group.children.iter() {
+            match &item.value {
+                SceneItem::Group(child) => {
+                    if let Some(child) = self.nodes.get(&child.node_id) {
+                        self.collect_visible_items(child, items, visited);
+                    }
+                }
+                _ => items.push((group, item)),
+            }
+        }
+    }
+
     pub fn add_item(
         &mut self,
         item: CrdtSequenceItem<SceneItem>,
@@ -52,95 +168,248 @@ impl SceneTree {
     pub fn new() -> SceneTree {
         let root_id = CrdtId { part1: 0, part2: 1 };
         let mut nodes = HashMap::new();
-        let mut root = Group::default();
-        root.node_id = root_id;
-        nodes.insert(root_id, root);
+        nodes.insert(root_id, Group::default().node_id(root_id));
         SceneTree {
             root_id,
             nodes,
             root_text: None,
+            authors: HashMap::new(),
+            depths: HashMap::from([(root_id, 0)]),
+            containers: HashMap::new(),
+            described: HashSet::new(),
+            limits: ParseLimits::default(),
         }
     }
 
-    pub fn from_blocks(blocks: &Vec<Block>) -> Result<SceneTree, ParseError> {
-        let mut tree = SceneTree::new();
-        for block in blocks.into_iter() {
-            let block = block.clone();
-            match block {
-                Block::SceneTree(b) => {
-                    // XXX check node_id and is_update
-                    // pending_tree_nodes[b.tree_id] = b
-                    tree.add_node(b.tree_id)
-                }
-                Block::TreeNode(b) => {
-                    let node = match tree.nodes.get_mut(&b.group.node_id) {
-                        Some(node) => node,
-                        None => {
-                            return Err(ParseError::invalid(format!(
-                                "Node does not exist for TreeNodeBlock: {:?}",
-                                b.group.node_id
-                            )));
-                        }
-                    };
+    pub fn from_blocks(blocks: &[Block]) -> Result<SceneTree, ParseError> {
+        Self::from_blocks_with_limits(blocks, &ParseLimits::default())
+    }
+
+    pub fn from_blocks_with_limits(
+        blocks: &[Block],
+        limits: &ParseLimits,
+    ) -> Result<SceneTree, ParseError> {
+        let mut tree = SceneTree {
+            limits: limits.clone(),
+            ..SceneTree::new()
+        };
+        tree.apply_all(blocks)?;
+        Ok(tree)
+    }
+
+    /// Apply a block read after the ones this tree was built from
+    ///
+    /// Gives the same tree as [SceneTree::from_blocks] with `block` added to the end of its
+    /// blocks. Only the copies of the changed group in the groups containing it are updated, use
+    /// [SceneTree::apply_all] for many blocks at once.
+    pub fn apply(&mut self, block: &Block) -> Result<(), ParseError> {
+        self.apply_block(block)?;
+        let changed = match block {
+            Block::TreeNode(b) => b.group.node_id,
+            Block::SceneGroupItem(b) => b.parent_id,
+            Block::SceneGlyphItem(b) => b.parent_id,
+            Block::SceneLineItem(b) => b.parent_id,
+            _ => return Ok(()),
+        };
+        self.resolve_containers(changed);
+        Ok(())
+    }
+
+    /// [SceneTree::apply] for every block in order
+    pub fn apply_all(&mut self, blocks: &[Block]) -> Result<(), ParseError> {
+        for block in blocks.iter() {
+            self.apply_block(block)?;
+        }
+        self.resolve_groups();
+        Ok(())
+    }
+
+    fn apply_block(&mut self, block: &Block) -> Result<(), ParseError> {
+        match block.clone() {
+            Block::SceneTree(b) => {
+                // XXX check node_id and is_update
+                // updates of a node that exists keep its children
+                self.add_node(b.tree_id)
+            }
+            Block::TreeNode(b) => {
+                let node = match self.nodes.get_mut(&b.group.node_id) {
+                    Some(node) => node,
+                    None => {
+                        return Err(ParseError::invalid(format!(
+                            "Node does not exist for TreeNodeBlock: {:?}",
+                            b.group.node_id
+                        )));
+                    }
+                };
+                // later blocks for the same node only change what they have a newer value for
+                if self.described.insert(b.group.node_id) {
                     *node = Group {
                         children: node.children.clone(),
                         ..b.group
                     };
+                } else {
+                    node.merge_values(b.group);
                 }
-                Block::SceneGroupItem(b) => {
-                    match &b.item.value {
-                        Some(v) => {
-                            let node = tree.nodes.get(&v).ok_or(ParseError::invalid(format!(
-                                "Node does not exist for ScneGroupItemBlock: {v:?}"
-                            )))?;
-                            let block_item = b.item;
-                            let item = CrdtSequenceItem {
-                                value: SceneItem::Group(node.clone()),
-                                item_id: block_item.item_id,
-                                left_id: block_item.left_id,
-                                right_id: block_item.right_id,
-                                deleted_length: block_item.deleted_length,
-                            };
-                            tree.add_item(item, b.parent_id)?;
-                        }
-                        None => {
-                            return Err(ParseError::invalid(format!("No node id found",)));
-                        }
+            }
+            Block::SceneGroupItem(b) => match &b.item.value {
+                Some(v) => {
+                    let node = self.nodes.get(v).ok_or(ParseError::invalid(format!(
+                        "Node does not exist for ScneGroupItemBlock: {v:?}"
+                    )))?;
+                    let node = node.clone();
+                    let depth = self.depths.get(&b.parent_id).copied().unwrap_or(0) + 1;
+                    self.set_depth(*v, depth)?;
+                    self.containers.entry(*v).or_default().insert(b.parent_id);
+                    let block_item = b.item;
+                    let item = CrdtSequenceItem {
+                        value: SceneItem::Group(node),
+                        item_id: block_item.item_id,
+                        left_id: block_item.left_id,
+                        right_id: block_item.right_id,
+                        deleted_length: block_item.deleted_length,
+                    };
+                    self.add_item(item, b.parent_id)?;
+                }
+                None => self.remove_item(&b.item.item_id, &b.parent_id),
+            },
+            Block::SceneGlyphItem(b) => match b.item.value {
+                Some(glyph) => {
+                    let item = CrdtSequenceItem {
+                        value: SceneItem::GlyphRange(glyph),
+                        item_id: b.item.item_id,
+                        left_id: b.item.left_id,
+                        right_id: b.item.right_id,
+                        deleted_length: b.item.deleted_length,
+                    };
+                    self.add_item(item, b.parent_id)?;
+                }
+                None => self.remove_item(&b.item.item_id, &b.parent_id),
+            },
+            Block::SceneLineItem(b) => match b.item.value {
+                Some(line) => {
+                    let item = CrdtSequenceItem {
+                        value: SceneItem::Line(line),
+                        item_id: b.item.item_id,
+                        left_id: b.item.left_id,
+                        right_id: b.item.right_id,
+                        deleted_length: b.item.deleted_length,
                     };
+                    self.add_item(item, b.parent_id)?;
                 }
-                Block::SceneGlyphItem(b) => {
-                    if let Some(glyph) = b.item.value {
-                        // let mut block_item = b.item;
-                        let item = CrdtSequenceItem {
-                            value: SceneItem::GlyphRange(glyph),
-                            item_id: b.item.item_id,
-                            left_id: b.item.left_id,
-                            right_id: b.item.right_id,
-                            deleted_length: b.item.deleted_length,
-                        };
-
-                        tree.add_item(item, b.parent_id)?;
+                None => self.remove_item(&b.item.item_id, &b.parent_id),
+            },
+            Block::RootText(b) => self.root_text = Some(b.text),
+            Block::AuthorsIds(b) => self.authors.extend(b.authors),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Record that group `id` is nested `depth` levels deep, together with the groups in it
+    ///
+    /// Groups can be added to a parent before the parent itself is placed, so the depths of
+    /// everything below a group are raised when it is added. A group keeps the deepest place it
+    /// was ever added at.
+    fn set_depth(&mut self, id: CrdtId, depth: u32) -> Result<(), ParseError> {
+        let mut pending = vec![(id, depth)];
+        while let Some((id, depth)) = pending.pop() {
+            if depth > self.limits.max_nesting_depth {
+                return Err(ParseError::limit_exceeded(format!(
+                    "Group nesting depth exceeds limit of {}",
+                    self.limits.max_nesting_depth
+                )));
+            }
+            if self
+                .depths
+                .get(&id)
+                .map_or(false, |current| *current >= depth)
+            {
+                continue;
+            }
+            self.depths.insert(id, depth);
+            if let Some(group) = self.nodes.get(&id) {
+                for item in group.children.items.values() {
+                    if let SceneItem::Group(child) = &item.value {
+                        pending.push((child.node_id, depth + 1));
                     }
                 }
-                Block::SceneLineItem(b) => {
-                    if let Some(line) = b.item.value {
-                        // let mut block_item = b.item;
-                        let item = CrdtSequenceItem {
-                            value: SceneItem::Line(line),
-                            item_id: b.item.item_id,
-                            left_id: b.item.left_id,
-                            right_id: b.item.right_id,
-                            deleted_length: b.item.deleted_length,
-                        };
-
-                        tree.add_item(item, b.parent_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove an item that was deleted by a later block for the same id
+    fn remove_item(&mut self, item_id: &CrdtId, parent_id: &CrdtId) {
+        if let Some(parent) = self.nodes.get_mut(parent_id) {
+            parent.children.items.remove(item_id);
+        }
+    }
+
+    /// Replace the group children of every node with the final state of that group
+    ///
+    /// A group is added to its parent when its `SceneGroupItemBlock` is read, items added to it
+    /// afterwards would otherwise be missing from the parent's copy.
+    fn resolve_groups(&mut self) {
+        let ids: Vec<CrdtId> = self.nodes.keys().copied().collect();
+        let resolved: Vec<(CrdtId, Group)> = ids
+            .into_iter()
+            .filter_map(|id| Some((id, self.resolve_group(&id, &mut vec![])?)))
+            .collect();
+        self.nodes.extend(resolved);
+    }
+
+    /// Replace the copies of group `id` in the groups containing it, and theirs in turn, with
+    /// the current state of the group
+    fn resolve_containers(&mut self, id: CrdtId) {
+        let mut changed = HashSet::from([id]);
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            for container in self.containers.get(&id).into_iter().flatten() {
+                if changed.insert(*container) {
+                    pending.push(*container);
+                }
+            }
+        }
+
+        // deepest groups first, so every group copies children that are already up to date
+        let mut containers: Vec<CrdtId> = changed.iter().copied().filter(|c| *c != id).collect();
+        containers.sort_by_key(|c| std::cmp::Reverse(self.depths.get(c).copied().unwrap_or(0)));
+        for container_id in containers {
+            let Some(mut container) = self.nodes.remove(&container_id) else {
+                continue;
+            };
+            for item in container.children.items.values_mut() {
+                if let SceneItem::Group(child) = &mut item.value {
+                    if changed.contains(&child.node_id) {
+                        if let Some(group) = self.nodes.get(&child.node_id) {
+                            *child = group.clone();
+                        }
                     }
                 }
-                Block::RootText(b) => tree.root_text = Some(b.text),
-                _ => (),
             }
+            self.nodes.insert(container_id, container);
         }
+    }
 
-        Ok(tree)
+    fn resolve_group(&self, id: &CrdtId, parents: &mut Vec<CrdtId>) -> Option<Group> {
+        // deeper groups are rejected when they are added, this only guards the recursion
+        if parents.len() > self.limits.max_nesting_depth as usize {
+            return None;
+        }
+        let mut group = self.nodes.get(id)?.clone();
+        parents.push(*id);
+        for item in group.children.items.values_mut() {
+            if let SceneItem::Group(child) = &mut item.value {
+                // a group containing itself is left as it was read
+                if parents.contains(&child.node_id) {
+                    continue;
+                }
+                if let Some(resolved) = self.resolve_group(&child.node_id, parents) {
+                    *child = resolved;
+                }
+            }
+        }
+        parents.pop();
+        Some(group)
     }
 }
@@ -0,0 +1,128 @@
+use crate::{bitwriter::Bitwriter, ParseError};
+
+use super::{crdt::CrdtId, lwwvalue::LwwValue, tagged_bit_reader::TagType, TypeWrite};
+
+/// Writes values with the tags read by [super::tagged_bit_reader::TaggedBitreader]
+pub struct TaggedBitwriter<'n> {
+    pub bit_writer: &'n mut Bitwriter,
+}
+
+impl<'n> TaggedBitwriter<'n> {
+    pub fn new(bit_writer: &'n mut Bitwriter) -> TaggedBitwriter<'n> {
+        TaggedBitwriter { bit_writer }
+    }
+
+    pub fn write_tag(&mut self, index: u32, tag_type: TagType) {
+        let tag_type = match tag_type {
+            TagType::Byte1 => 0x1,
+            TagType::Byte4 => 0x4,
+            TagType::Byte8 => 0x8,
+            TagType::Length4 => 0xC,
+            TagType::ID => 0xF,
+        };
+        self.bit_writer.write_varuint((index << 4) | tag_type);
+    }
+
+    pub fn write_id(&mut self, index: u32, id: CrdtId) -> Result<(), ParseError> {
+        self.write_tag(index, TagType::ID);
+        id.write(self)
+    }
+
+    pub fn write_bool(&mut self, index: u32, value: bool) {
+        self.write_tag(index, TagType::Byte1);
+        self.bit_writer.write_bool(value);
+    }
+
+    pub fn write_u8(&mut self, index: u32, value: u8) {
+        self.write_tag(index, TagType::Byte1);
+        self.bit_writer.write_u8(value);
+    }
+
+    pub fn write_u32(&mut self, index: u32, value: u32) {
+        self.write_tag(index, TagType::Byte4);
+        self.bit_writer.write_u32(value);
+    }
+
+    pub fn write_f32(&mut self, index: u32, value: f32) {
+        self.write_tag(index, TagType::Byte4);
+        self.bit_writer.write_f32(value);
+    }
+
+    pub fn write_f64(&mut self, index: u32, value: f64) {
+        self.write_tag(index, TagType::Byte8);
+        self.bit_writer.write_f64(value);
+    }
+
+    pub fn write_string(&mut self, index: u32, value: &str) -> Result<(), ParseError> {
+        self.write_subblock(index, |writer| {
+            writer.write_string_value(value);
+            Ok(())
+        })
+    }
+
+    /// Length prefixed string without a tag
+    pub fn write_string_value(&mut self, value: &str) {
+        self.bit_writer.write_varuint(value.len() as u32);
+        // XXX always set by the device
+        self.bit_writer.write_bool(true);
+        self.bit_writer.write_string(value);
+    }
+
+    /// Write a subblock with the content written by `content`, its size is filled in afterwards
+    pub fn write_subblock(
+        &mut self,
+        index: u32,
+        content: impl FnOnce(&mut Self) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        self.write_tag(index, TagType::Length4);
+        let size_position = self.bit_writer.position();
+        self.bit_writer.write_u32(0);
+        content(self)?;
+        let size = self.bit_writer.position() - size_position - 4;
+        self.bit_writer
+            .patch_u32(size_position, u32::try_from(size)?);
+        Ok(())
+    }
+
+    pub fn write_lww_u8(&mut self, index: u32, value: &LwwValue<u8>) -> Result<(), ParseError> {
+        self.write_subblock(index, |writer| {
+            writer.write_id(1, value.timestamp)?;
+            writer.write_u8(2, value.value);
+            Ok(())
+        })
+    }
+
+    pub fn write_lww_string(
+        &mut self,
+        index: u32,
+        value: &LwwValue<String>,
+    ) -> Result<(), ParseError> {
+        self.write_subblock(index, |writer| {
+            writer.write_id(1, value.timestamp)?;
+            writer.write_string(2, &value.value)
+        })
+    }
+
+    pub fn write_lww_bool(&mut self, index: u32, value: &LwwValue<bool>) -> Result<(), ParseError> {
+        self.write_subblock(index, |writer| {
+            writer.write_id(1, value.timestamp)?;
+            writer.write_bool(2, value.value);
+            Ok(())
+        })
+    }
+
+    pub fn write_lww_id(&mut self, index: u32, value: &LwwValue<CrdtId>) -> Result<(), ParseError> {
+        self.write_subblock(index, |writer| {
+            writer.write_id(1, value.timestamp)?;
+            writer.write_id(2, value.value)
+        })
+    }
+
+    pub fn write_lww_f32(&mut self, index: u32, value: &LwwValue<f32>) -> Result<(), ParseError> {
+        self.write_subblock(index, |writer| {
+            writer.write_id(1, value.timestamp)?;
+            writer.write_f32(2, value.value);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,187 @@
+//! Reading and writing of zip archives as used by `.rmdoc` files
+//!
+//! Only what the tablet and desktop app produce is supported: stored and deflated entries
+//! without zip64 or encryption.
+
+use crate::{
+    compression::{crc32, inflate},
+    ParseError, ParseLimits,
+};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+
+/// A file inside an archive
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, ParseError> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| ParseError::invalid("Zip archive ended unexpectedly"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, ParseError> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| ParseError::invalid("Zip archive ended unexpectedly"))
+}
+
+fn slice(data: &[u8], offset: usize, length: usize) -> Result<&[u8], ParseError> {
+    data.get(offset..offset.saturating_add(length))
+        .ok_or_else(|| ParseError::invalid("Zip archive ended unexpectedly"))
+}
+
+/// Read all files from a zip archive, directories are skipped
+///
+/// The sizes the archive declares are checked against `limits` before anything is decompressed.
+pub fn read_zip(data: &[u8], limits: &ParseLimits) -> Result<Vec<ZipEntry>, ParseError> {
+    // the end of central directory record is at the end, followed by a comment of at most 64KiB
+    let search_start = data.len().saturating_sub(22 + 0xFFFF);
+    let end = (search_start..data.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(data, i).ok() == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+        .ok_or_else(|| ParseError::invalid("Not a zip archive"))?;
+
+    let amount_entries = u16_at(data, end + 10)?;
+    let mut offset = u32_at(data, end + 16)? as usize;
+
+    let mut entries = vec![];
+    let mut total_size: u64 = 0;
+    for _ in 0..amount_entries {
+        if u32_at(data, offset)? != CENTRAL_HEADER_SIGNATURE {
+            return Err(ParseError::invalid("Invalid zip central directory header"));
+        }
+        let flags = u16_at(data, offset + 8)?;
+        let method = u16_at(data, offset + 10)?;
+        let crc = u32_at(data, offset + 16)?;
+        let compressed_size = u32_at(data, offset + 20)? as usize;
+        let size = u32_at(data, offset + 24)? as usize;
+        let name_length = u16_at(data, offset + 28)? as usize;
+        let extra_length = u16_at(data, offset + 30)? as usize;
+        let comment_length = u16_at(data, offset + 32)? as usize;
+        let local_offset = u32_at(data, offset + 42)? as usize;
+        let name = String::from_utf8_lossy(slice(data, offset + 46, name_length)?).into_owned();
+        offset += 46 + name_length + extra_length + comment_length;
+
+        if flags & 1 == 1 {
+            return Err(ParseError::unsupported(format!(
+                "Encrypted zip entry '{name}'"
+            )));
+        }
+        if compressed_size == 0xFFFFFFFF || size == 0xFFFFFFFF {
+            return Err(ParseError::unsupported("Zip64 archives are not supported"));
+        }
+        if name.ends_with('/') {
+            continue;
+        }
+        if size > limits.max_archive_entry_size as usize {
+            return Err(ParseError::limit_exceeded(format!(
+                "Zip entry '{name}' exceeds limit of {} bytes",
+                limits.max_archive_entry_size
+            )));
+        }
+        total_size += size as u64;
+        if total_size > limits.max_archive_size {
+            return Err(ParseError::limit_exceeded(format!(
+                "Zip archive exceeds limit of {} bytes",
+                limits.max_archive_size
+            )));
+        }
+
+        if u32_at(data, local_offset)? != LOCAL_HEADER_SIGNATURE {
+            return Err(ParseError::invalid("Invalid zip local file header"));
+        }
+        let data_offset = local_offset
+            + 30
+            + u16_at(data, local_offset + 26)? as usize
+            + u16_at(data, local_offset + 28)? as usize;
+        let compressed = slice(data, data_offset, compressed_size)?;
+
+        let content = match method {
+            0 => compressed.to_vec(),
+            8 => inflate(compressed, size)?,
+            _ => {
+                return Err(ParseError::unsupported(format!(
+                    "Unsupported zip compression method {method} for '{name}'"
+                )))
+            }
+        };
+        if content.len() != size || crc32(&content) != crc {
+            return Err(ParseError::invalid(format!(
+                "Zip entry '{name}' is corrupted"
+            )));
+        }
+
+        entries.push(ZipEntry {
+            name,
+            data: content,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Write `entries` into a zip archive, entries are stored without compression
+pub fn write_zip(entries: &[ZipEntry]) -> Result<Vec<u8>, ParseError> {
+    let mut out = vec![];
+    let mut central_directory = vec![];
+
+    if entries.len() > u16::MAX as usize {
+        return Err(ParseError::unsupported("Too many files for a zip archive"));
+    }
+
+    for entry in entries {
+        let offset = u32::try_from(out.len())?;
+        let size = u32::try_from(entry.data.len())?;
+        let name_length = u16::try_from(entry.name.len())?;
+        let crc = crc32(&entry.data);
+
+        // fields shared by the local and central header, starting at "version needed"
+        let mut common = vec![];
+        common.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        common.extend_from_slice(&0x0800u16.to_le_bytes()); // flags, utf-8 names
+        common.extend_from_slice(&0u16.to_le_bytes()); // method, stored
+        common.extend_from_slice(&0u16.to_le_bytes()); // time
+        common.extend_from_slice(&0x0021u16.to_le_bytes()); // date, 1980-01-01
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes()); // compressed size
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&name_length.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes()); // extra length
+
+        out.extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&common);
+        out.extend_from_slice(entry.name.as_bytes());
+        out.extend_from_slice(&entry.data);
+
+        central_directory.extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        central_directory.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        central_directory.extend_from_slice(&common);
+        central_directory.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        central_directory.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        central_directory.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        central_directory.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        central_directory.extend_from_slice(&offset.to_le_bytes());
+        central_directory.extend_from_slice(entry.name.as_bytes());
+    }
+
+    let central_directory_offset = u32::try_from(out.len())?;
+    let central_directory_size = u32::try_from(central_directory.len())?;
+    out.extend(central_directory);
+
+    out.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // disk number
+    out.extend_from_slice(&0u16.to_le_bytes()); // disk with central directory
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&central_directory_size.to_le_bytes());
+    out.extend_from_slice(&central_directory_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // comment length
+
+    Ok(out)
+}
@@ -0,0 +1,26 @@
+// every test file uses its own part of these helpers
+#![allow(dead_code)]
+
+use std::fs::read;
+
+use remarkable_lines::{v6::scene_item::point::Point, RemarkableFile};
+
+/// Read fixture `name` from `tests/fixtures`
+pub fn read_file(name: &str) -> RemarkableFile {
+    let test_page = read(format!("./tests/fixtures/{name}")).unwrap();
+    RemarkableFile::read(&test_page[..]).unwrap()
+}
+
+/// `count` points of a horizontal line drawn with medium pressure
+pub fn points(count: usize) -> Vec<Point> {
+    (0..count)
+        .map(|i| Point {
+            x: i as f32 * 10.0,
+            y: 100.0,
+            speed: 0.0,
+            direction: 0.0,
+            width: 8.0,
+            pressure: 100.0,
+        })
+        .collect()
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use remarkable_lines::{
+    diff::{diff, Change, LayerChange},
+    shared::pen_color::PenColor,
+    v6::{
+        block::{Block, TreeNodeBlock},
+        crdt::{CrdtId, CrdtSequenceItem},
+        lwwvalue::LwwValue,
+        scene_item::{group::Group, text::TextItem},
+    },
+    RemarkableFile,
+};
+
+use common::read_file;
+
+/// Edit the blocks of a v6 file and parse it again so the tree is rebuilt
+fn edit(name: &str, f: impl FnOnce(&mut Vec<Block>)) -> RemarkableFile {
+    let mut file = read_file(name);
+    match &mut file {
+        RemarkableFile::V6 { blocks, .. } => f(blocks),
+        RemarkableFile::Other { .. } => panic!("invalid version"),
+    }
+    RemarkableFile::read(&file.to_bytes().unwrap()[..]).unwrap()
+}
+
+#[test]
+fn test_diff_v6() {
+    let before = read_file("test_v6_single_page_line.rm");
+    assert!(diff(&before, &before).is_empty());
+
+    let after = edit("test_v6_single_page_line.rm", |blocks| {
+        let mut line = None;
+        for block in blocks.iter_mut() {
+            if let Block::SceneLineItem(b) = block {
+                line = Some(Block::SceneLineItem(b.clone()));
+                b.item.value = None;
+                b.item.deleted_length = 1;
+            }
+        }
+        let mut line = line.unwrap();
+        if let Block::SceneLineItem(b) = &mut line {
+            b.item.item_id = CrdtId {
+                part1: 1,
+                part2: 20,
+            };
+            if let Some(value) = &mut b.item.value {
+                value.color = PenColor::Red;
+            }
+        }
+        blocks.push(line);
+        blocks.push(Block::TreeNode(TreeNodeBlock {
+            group: Group {
+                visible: LwwValue {
+                    timestamp: CrdtId {
+                        part1: 1,
+                        part2: 21,
+                    },
+                    value: false,
+                },
+                ..Group::default()
+                    .node_id(CrdtId {
+                        part1: 0,
+                        part2: 11,
+                    })
+                    .label(LwwValue {
+                        timestamp: CrdtId {
+                            part1: 1,
+                            part2: 21,
+                        },
+                        value: "Notes".to_owned(),
+                    })
+            },
+        }));
+    });
+
+    let changes = diff(&before, &after);
+    assert_eq!(
+        changes.layers,
+        vec![
+            LayerChange::Renamed {
+                from: "Layer 1".to_owned(),
+                to: "Notes".to_owned()
+            },
+            LayerChange::Hidden("Notes".to_owned())
+        ]
+    );
+    assert_eq!(changes.strokes.len(), 2);
+    assert!(matches!(&changes.strokes[0], Change::Removed(s) if s.color == PenColor::Black));
+    assert!(matches!(&changes.strokes[1], Change::Added(s) if s.color == PenColor::Red));
+    let summary = changes.to_string();
+    assert!(summary.contains("~ layer 'Layer 1' renamed to 'Notes'"));
+    assert!(summary.contains("+ Red"));
+}
+
+#[test]
+fn test_diff_text() {
+    let before = read_file("test_v6_normal_ab.rm");
+    let after = edit("test_v6_normal_ab.rm", |blocks| {
+        for block in blocks.iter_mut() {
+            if let Block::RootText(b) = block {
+                b.text.items.push(CrdtSequenceItem {
+                    item_id: CrdtId {
+                        part1: 1,
+                        part2: 30,
+                    },
+                    left_id: CrdtId {
+                        part1: 1,
+                        part2: 16,
+                    },
+                    right_id: CrdtId::default(),
+                    deleted_length: 0,
+                    value: TextItem::Text("CD".to_owned()),
+                });
+            }
+        }
+    });
+    let changes = diff(&before, &after);
+    assert_eq!(changes.text, vec![Change::Added("CD".to_owned())]);
+    assert_eq!(
+        diff(&after, &before).text,
+        vec![Change::Removed("CD".to_owned())]
+    );
+}
+
+#[test]
+fn test_diff_v5() {
+    let before = read_file("test_v5_advent_of_code.rm");
+    let mut after = read_file("test_v5_advent_of_code.rm");
+    assert!(diff(&before, &after).is_empty());
+
+    if let RemarkableFile::Other { pages, .. } = &mut after {
+        let lines = &mut pages[0].layers[0].lines;
+        lines.remove(3);
+        lines[0].color = PenColor::Red;
+    }
+    let changes = diff(&before, &after);
+    assert_eq!(changes.strokes.len(), 2);
+    assert!(matches!(
+        &changes.strokes[0],
+        Change::Modified { after, .. } if after.color == PenColor::Red
+    ));
+    assert!(matches!(&changes.strokes[1], Change::Removed(_)));
+}
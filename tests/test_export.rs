@@ -0,0 +1,460 @@
+use std::fs::read;
+
+use remarkable_lines::{
+    export::{
+        image::Image,
+        pdf::{annotate_pdf, annotate_pdf_with_options, PdfOptions},
+        template::{PathSegment, Template, Templates},
+        thumbnail::{thumbnail, thumbnail_path, THUMBNAIL_HEIGHT, THUMBNAIL_WIDTH},
+        timeline::{animated_svg, duration, frames, timeline, ReplayOptions},
+        PageDrawing, AUTHOR_COLORS,
+    },
+    notebook::Notebook,
+    shared::{pen_color::PenColor, tool::Tool},
+    v6::{
+        scene_builder::SceneBuilder,
+        scene_item::{line::Line, point::Point},
+    },
+    RemarkableFile,
+};
+
+const DATA_DIR: &str = "./tests/fixtures/xochitl";
+const TEMPLATE_DIR: &str = "./tests/fixtures/templates";
+
+fn count(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .filter(|w| w == &needle)
+        .count()
+}
+
+#[test]
+fn test_page_drawing() {
+    let notebook = Notebook::from_files(
+        "doc",
+        None,
+        r#"{"fileType": "notebook", "pages": ["a"]}"#,
+        |_| {
+            Ok(Some(
+                read("./tests/fixtures/test_v6_single_page_line.rm").unwrap(),
+            ))
+        },
+    )
+    .unwrap();
+    let drawing = PageDrawing::new(notebook.pages[0].file().unwrap());
+    assert_eq!(drawing.strokes.len(), 1);
+    assert_eq!(drawing.strokes[0].points.len(), 46);
+    // v6 x is centered on the page
+    assert!(drawing.strokes[0]
+        .points
+        .iter()
+        .all(|p| (0.0..1404.0).contains(&p.x)));
+}
+
+#[test]
+fn test_color_by_author() {
+    let notebook = Notebook::from_files(
+        "doc",
+        None,
+        r#"{"fileType": "notebook", "pages": ["a"]}"#,
+        |_| {
+            Ok(Some(
+                read("./tests/fixtures/test_v6_single_page_line.rm").unwrap(),
+            ))
+        },
+    )
+    .unwrap();
+    let authors = notebook.authors();
+    assert_eq!(authors, vec!["d63ff1c3-0764-6e54-b226-29f98c368f79"]);
+
+    let mut drawing = PageDrawing::new(notebook.pages[0].file().unwrap());
+    assert_eq!(drawing.strokes[0].color, PenColor::Black);
+    drawing.color_by_author(&["someone else".to_owned(), authors[0].clone()]);
+    assert_eq!(drawing.strokes[0].color, AUTHOR_COLORS[1]);
+
+    let options = PdfOptions {
+        color_by_author: true,
+        ..PdfOptions::default()
+    };
+    let pdf = notebook.to_pdf_with_options(&options).unwrap();
+    assert_ne!(pdf, notebook.to_pdf().unwrap());
+}
+
+#[test]
+fn test_annotate_pdf() {
+    // first notebook page shows the second pdf page, then an inserted page and the first
+    let content = r#"{
+        "fileType": "pdf",
+        "pages": ["a", "b", "c"],
+        "redirectionPageMap": [1, -1, 0]
+    }"#;
+    let mut notebook = Notebook::from_files("doc", None, content, |id| {
+        Ok(match id {
+            "a" => Some(read("./tests/fixtures/test_v6_single_page_line.rm").unwrap()),
+            "c" => Some(read("./tests/fixtures/test_v5_advent_of_code.rm").unwrap()),
+            _ => None,
+        })
+    })
+    .unwrap();
+    let redirects: Vec<Option<u32>> = notebook.content.pages.iter().map(|p| p.redirect).collect();
+    assert_eq!(redirects, vec![Some(1), None, Some(0)]);
+
+    assert!(notebook.to_annotated_pdf().is_err());
+    notebook.other_files.insert(
+        "doc.pdf".to_owned(),
+        read("./tests/fixtures/two_pages.pdf").unwrap(),
+    );
+
+    let annotated = notebook.to_annotated_pdf().unwrap();
+    assert!(annotated.starts_with(b"%PDF-"));
+    assert_eq!(count(&annotated, b"/Type /Page "), 3);
+    assert_eq!(count(&annotated, b"/Count 3"), 1);
+    // the inserted page has the size of the rotated page before it
+    assert_eq!(count(&annotated, b"/Rotate 90"), 1);
+    assert_eq!(count(&annotated, b"/MediaBox [0 0 842 595]"), 1);
+    assert!(count(&annotated, b" RG /RmAlpha100 gs") >= 2);
+
+    // written files use a classic cross reference table and can be read again
+    let again = annotate_pdf(&annotated, &notebook).unwrap();
+    assert_eq!(count(&again, b"/Type /Page "), 3);
+
+    assert!(annotate_pdf(b"not a pdf", &notebook).is_err());
+}
+
+#[test]
+fn test_pdf_page_tree_depth() {
+    let content = r#"{"fileType": "pdf", "pages": ["a"], "redirectionPageMap": [0]}"#;
+    let notebook = Notebook::from_files("doc", None, content, |_| Ok(None)).unwrap();
+    let page_tree = |depth: u32| {
+        let mut pdf = "%PDF-1.7\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n".to_owned();
+        for number in 2..depth + 2 {
+            pdf += &format!(
+                "{number} 0 obj\n<< /Type /Pages /Kids [{} 0 R] /Count 1 >>\nendobj\n",
+                number + 1
+            );
+        }
+        pdf += &format!(
+            "{} 0 obj\n<< /Type /Page /MediaBox [0 0 100 100] >>\nendobj\n",
+            depth + 2
+        );
+        pdf += "trailer\n<< /Root 1 0 R >>\n%%EOF\n";
+        pdf.into_bytes()
+    };
+    assert!(annotate_pdf(&page_tree(10), &notebook).is_ok());
+    assert!(annotate_pdf(&page_tree(100_000), &notebook).is_err());
+}
+
+#[test]
+fn test_pdf_highlight_annotations() {
+    let content = r#"{"fileType": "pdf", "pages": ["a"], "redirectionPageMap": [0]}"#;
+    let notebook = Notebook::from_files("doc", None, content, |_| {
+        Ok(Some(
+            read("./tests/fixtures/test_v6_highlights.rm").unwrap(),
+        ))
+    })
+    .unwrap();
+    let highlights = notebook.highlights();
+    assert_eq!(highlights.len(), 2);
+    assert_eq!(highlights[0].highlight.text, "The quick brown fox");
+    assert_eq!(highlights[0].source_page, Some(0));
+
+    let source = read("./tests/fixtures/two_pages.pdf").unwrap();
+    let annotated = annotate_pdf(&source, &notebook).unwrap();
+    // one annotation per glyph range, touching ranges are not merged
+    assert_eq!(count(&annotated, b"/Subtype /Highlight"), 3);
+    assert_eq!(count(&annotated, b"/Contents (The quick)"), 1);
+    assert_eq!(count(&annotated, b"/Contents ( brown fox)"), 1);
+    // non ascii text is written as UTF-16
+    assert_eq!(count(&annotated, b"\xFE\xFF\x00l\x00a\x00z\x00y"), 1);
+    assert_eq!(count(&annotated, b" re f"), 4);
+
+    let options = PdfOptions {
+        highlight_annotations: false,
+        ..PdfOptions::default()
+    };
+    let burned = annotate_pdf_with_options(&source, &notebook, &options).unwrap();
+    assert_eq!(count(&burned, b"/Subtype /Highlight"), 0);
+    assert_eq!(count(&burned, b" re f"), 4);
+}
+
+#[test]
+fn test_pdf_highlight_rectangles() {
+    // the second page of the PDF is shown rotated by 90 degrees
+    let content = r#"{"fileType": "pdf", "pages": ["a"], "redirectionPageMap": [1]}"#;
+    let page = read("./tests/fixtures/test_v6_highlights.rm").unwrap();
+    let drawing = PageDrawing::new(&RemarkableFile::read(&page[..]).unwrap());
+    let notebook = Notebook::from_files("doc", None, content, |_| Ok(Some(page.clone()))).unwrap();
+
+    let source = read("./tests/fixtures/two_pages.pdf").unwrap();
+    let annotated = annotate_pdf(&source, &notebook).unwrap();
+    // highlight rectangles are in the coordinates of the shown page, only the rotation applies
+    let [left, bottom, right, top] = drawing.highlights[0].rectangles.iter().fold(
+        [f64::MAX, f64::MAX, f64::MIN, f64::MIN],
+        |[l, b, r, t], rectangle| {
+            [
+                l.min(595.0 - rectangle.y - rectangle.h),
+                b.min(rectangle.x),
+                r.max(595.0 - rectangle.y),
+                t.max(rectangle.x + rectangle.w),
+            ]
+        },
+    );
+    let rect = format!(
+        "/Rect [{} {} {} {}]",
+        format_real(left),
+        format_real(bottom),
+        format_real(right),
+        format_real(top)
+    );
+    assert_eq!(count(&annotated, rect.as_bytes()), 1);
+}
+
+fn format_real(value: f64) -> String {
+    let formatted = format!("{value:.4}");
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_owned()
+}
+
+#[test]
+fn test_png() {
+    let png = read(format!("{TEMPLATE_DIR}/P Grid small.png")).unwrap();
+    let image = Image::from_png(&png).unwrap();
+    assert_eq!((image.width, image.height, image.channels), (4, 3, 2));
+    // rows are filtered with none, sub and up
+    for y in 0..3 {
+        for x in 0..4 {
+            let expected = if (x + y) % 2 == 1 {
+                [255, 255, 255, 0]
+            } else {
+                [0, 0, 0, 255]
+            };
+            assert_eq!(image.pixel(x, y), Some(expected));
+        }
+    }
+    assert_eq!(image.pixel(4, 0), None);
+    assert_eq!(image.pixel(0, 3), None);
+
+    let png = read(format!("{TEMPLATE_DIR}/P Dots.png")).unwrap();
+    let image = Image::from_png(&png).unwrap();
+    assert_eq!(image.channels, 3);
+    assert_eq!(image.pixel(1, 0), Some([10, 20, 30, 255]));
+    assert_eq!(image.pixel(1, 1), Some([255, 255, 255, 255]));
+
+    assert!(Image::from_png(b"\x89PNG\r\n\x1a\n").is_err());
+    assert!(Image::from_png(&png[..40]).is_err());
+}
+
+#[test]
+fn test_svg_template() {
+    let svg = std::fs::read_to_string(format!("{TEMPLATE_DIR}/P Lines medium.svg")).unwrap();
+    let paths = match Template::from_svg(&svg).unwrap() {
+        Template::Vector(paths) => paths,
+        Template::Image(_) => panic!("Expected a vector template"),
+    };
+    // background, two paths with lines, the margin and a dot, definitions aren't drawn
+    assert_eq!(paths.len(), 5);
+    assert_eq!(paths[0].fill, Some([255, 255, 255]));
+    assert_eq!(paths[0].segments[2], PathSegment::LineTo(1404.0, 1872.0));
+
+    // the view box is stretched over the screen
+    let lines = &paths[1];
+    assert_eq!(lines.stroke, Some([192, 192, 192]));
+    assert_eq!(lines.fill, None);
+    assert_eq!(lines.stroke_width, 2.0);
+    assert_eq!(
+        lines.segments,
+        vec![
+            PathSegment::MoveTo(0.0, 200.0),
+            PathSegment::LineTo(1404.0, 200.0),
+            PathSegment::MoveTo(0.0, 400.0),
+            PathSegment::LineTo(1404.0, 400.0),
+        ]
+    );
+    assert_eq!(paths[2].stroke, Some([128, 128, 128]));
+    assert_eq!(paths[3].segments[0], PathSegment::MoveTo(120.0, 0.0));
+    assert_eq!(paths[3].stroke, Some([255, 0, 0]));
+    assert_eq!(paths[4].segments.len(), 6);
+
+    assert!(Template::from_svg("<svg><path d=\"M 0 0 L 1\"/></svg>").is_err());
+}
+
+#[test]
+fn test_pdf_templates() {
+    let id = "3a7e1b4c-9f0d-4c8e-a2b5-6d1f0e9c7b21";
+    let notebook = Notebook::load(DATA_DIR, id).unwrap();
+    // the template in .content wins over .pagedata
+    assert_eq!(
+        notebook.page_templates(),
+        vec![
+            Some("Blank".to_owned()),
+            Some("P Lines medium".to_owned()),
+            Some("Blank".to_owned())
+        ]
+    );
+
+    let pdf = notebook.to_pdf().unwrap();
+    assert_eq!(count(&pdf, b"/Type /Page "), 3);
+    assert_eq!(count(&pdf, b"/RmTemplate"), 0);
+
+    let mut options = PdfOptions {
+        templates: Some(Templates::new(TEMPLATE_DIR)),
+        ..PdfOptions::default()
+    };
+    let pdf = notebook.to_pdf_with_options(&options).unwrap();
+    assert_eq!(count(&pdf, b"/RmTemplate Do"), 1);
+    assert_eq!(count(&pdf, b"/Subtype /Form"), 1);
+
+    // every page gets the same template and it is only stored once
+    options.templates.as_mut().unwrap().override_name = Some("P Grid small".to_owned());
+    let pdf = notebook.to_pdf_with_options(&options).unwrap();
+    assert_eq!(count(&pdf, b"/RmTemplate Do"), 3);
+    assert_eq!(count(&pdf, b"/Subtype /Image"), 2);
+    assert_eq!(count(&pdf, b"/SMask"), 1);
+
+    // unknown templates are left out
+    options.templates.as_mut().unwrap().override_name = Some("Missing".to_owned());
+    let pdf = notebook.to_pdf_with_options(&options).unwrap();
+    assert_eq!(count(&pdf, b"/RmTemplate"), 0);
+}
+
+#[test]
+fn test_png_round_trip() {
+    let png = read(format!("{TEMPLATE_DIR}/P Grid small.png")).unwrap();
+    let image = Image::from_png(&png).unwrap();
+    assert_eq!(Image::from_png(&image.to_png()).unwrap(), image);
+
+    let image = Image {
+        width: 50,
+        height: 20,
+        channels: 3,
+        data: (0..3000).map(|i| (i * 7 % 251) as u8).collect(),
+    };
+    assert_eq!(Image::from_png(&image.to_png()).unwrap(), image);
+}
+
+#[test]
+fn test_thumbnails() {
+    let notebook = Notebook::from_files(
+        "doc",
+        None,
+        r#"{"fileType": "notebook", "pages": ["a"]}"#,
+        |_| {
+            Ok(Some(
+                read("./tests/fixtures/test_v6_single_page_line.rm").unwrap(),
+            ))
+        },
+    )
+    .unwrap();
+    let drawing = PageDrawing::new(notebook.pages[0].file().unwrap());
+    let image = thumbnail(&drawing, None);
+    assert_eq!(
+        (image.width, image.height),
+        (THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)
+    );
+    let dark = image.data.chunks(3).filter(|p| p[0] < 128).count();
+    assert!(dark > 10);
+    // nothing drawn without strokes
+    let empty = thumbnail(&PageDrawing::default(), None);
+    assert!(empty.data.iter().all(|c| *c == 255));
+
+    let id = "3a7e1b4c-9f0d-4c8e-a2b5-6d1f0e9c7b21";
+    let mut notebook = Notebook::load(DATA_DIR, id).unwrap();
+    notebook
+        .add_thumbnails(Some(&Templates::new(TEMPLATE_DIR)))
+        .unwrap();
+    let names: Vec<String> = notebook
+        .pages
+        .iter()
+        .map(|page| thumbnail_path(id, &page.id))
+        .collect();
+    assert!(names
+        .iter()
+        .all(|name| notebook.other_files.contains_key(name)));
+
+    // the second page is lined, the first line is at a ninth of the height
+    let lined = Image::from_png(&notebook.other_files[&names[1]]).unwrap();
+    assert_eq!(
+        (lined.width, lined.height),
+        (THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)
+    );
+    let line_y = 200 * THUMBNAIL_HEIGHT / 1872;
+    let [r, _, _, _] = lined.pixel(THUMBNAIL_WIDTH - 10, line_y).unwrap();
+    assert!(r < 255);
+    let [r, _, _, _] = lined.pixel(THUMBNAIL_WIDTH - 10, line_y + 8).unwrap();
+    assert_eq!(r, 255);
+    // the margin is red
+    let [r, g, _, _] = lined.pixel(24, 100).unwrap();
+    assert!(r > g);
+}
+
+#[test]
+fn test_timeline() {
+    let points = |y: f32| {
+        (0..=20)
+            .map(|i| Point {
+                x: i as f32 * 10.0 - 100.0,
+                y,
+                speed: 0.0,
+                direction: 0.0,
+                width: 40.0,
+                pressure: 100.0,
+            })
+            .collect()
+    };
+    let mut builder = SceneBuilder::new("0c4d4d3a-8f3b-4b6e-9a53-0f4f1a2b3c4d").unwrap();
+    let bottom = builder.add_layer("Bottom").unwrap();
+    let top = builder.add_layer("Top").unwrap();
+    // drawn first but shown above the second line
+    builder
+        .add_line(
+            top,
+            Line::new(Tool::FineLiner, PenColor::Red, points(500.0)),
+        )
+        .unwrap();
+    builder
+        .add_line(
+            bottom,
+            Line::new(Tool::FineLiner, PenColor::Blue, points(1000.0)),
+        )
+        .unwrap();
+    let file = RemarkableFile::read(&builder.build().to_bytes().unwrap()[..]).unwrap();
+    let drawing = PageDrawing::new(&file);
+    assert_eq!(drawing.strokes[0].color, PenColor::Blue);
+
+    let options = ReplayOptions {
+        frames_per_second: 10.0,
+        ..ReplayOptions::default()
+    };
+    let strokes = timeline(&drawing, &options);
+    let colors: Vec<PenColor> = strokes.iter().map(|s| s.stroke.color).collect();
+    assert_eq!(colors, vec![PenColor::Red, PenColor::Blue]);
+    assert_eq!(strokes[0].start, 0.0);
+    let expected = 200.0 / options.pixels_per_second;
+    assert!((strokes[0].duration - expected).abs() < 1e-4);
+    assert!((strokes[1].start - expected - options.pause).abs() < 1e-4);
+
+    let svg = animated_svg(&drawing, &options);
+    assert_eq!(count(svg.as_bytes(), b"<animate "), 2);
+    let hex = |color: PenColor| {
+        let [r, g, b, _] = color.to_rgba();
+        format!("stroke=\"#{r:02x}{g:02x}{b:02x}\"")
+    };
+    // strokes come in the order they were drawn
+    assert!(svg.find(&hex(PenColor::Red)).unwrap() < svg.find(&hex(PenColor::Blue)).unwrap());
+
+    let frames: Vec<Image> = frames(&drawing, None, 351, 468, &options).collect();
+    assert_eq!(
+        frames.len(),
+        (duration(&strokes) * options.frames_per_second).ceil() as usize + 1
+    );
+    let white = [255, 255, 255, 255];
+    // the red line is at y 500 of the page, the blue one at y 1000
+    assert_eq!(frames[0].pixel(175, 125).unwrap(), white);
+    assert_ne!(frames[2].pixel(175, 125).unwrap(), white);
+    assert_eq!(frames[2].pixel(175, 250).unwrap(), white);
+    let last = frames.last().unwrap();
+    assert_ne!(last.pixel(175, 125).unwrap(), white);
+    assert_ne!(last.pixel(175, 250).unwrap(), white);
+}
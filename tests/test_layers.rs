@@ -0,0 +1,299 @@
+mod common;
+
+use std::fs::read;
+
+use remarkable_lines::{
+    diff::{diff, Change, LayerChange},
+    export::{
+        timeline::{timeline, ReplayOptions},
+        PageDrawing,
+    },
+    layers::{LayerInfo, SplitBy},
+    other::{layer::Layer, Page},
+    shared::{pen_color::PenColor, tool::Tool},
+    v6::{
+        block::Block,
+        scene_builder::SceneBuilder,
+        scene_item::{line::Line, SceneItem},
+        scene_tree::SceneTree,
+    },
+    ParseErrorKind, RemarkableFile,
+};
+
+use common::points;
+
+/// Page with three lines in "Layer 1" and an empty "Layer 2" above it
+fn v6_page() -> RemarkableFile {
+    let mut builder = SceneBuilder::new("0c4d4d3a-8f3b-4b6e-9a53-0f4f1a2b3c4d").unwrap();
+    let layer = builder.add_layer("Layer 1").unwrap();
+    for (tool, color) in [
+        (Tool::FineLiner, PenColor::Black),
+        (Tool::Marker, PenColor::Blue),
+        (Tool::FineLiner, PenColor::Red),
+    ] {
+        builder
+            .add_line(layer, Line::new(tool, color, points(3)))
+            .unwrap();
+    }
+    builder.add_layer("Layer 2").unwrap();
+    builder.build()
+}
+
+/// Write and read the page again so only what is in its blocks remains
+fn reread(file: &RemarkableFile) -> RemarkableFile {
+    RemarkableFile::read(&file.to_bytes().unwrap()[..]).unwrap()
+}
+
+fn names(file: &RemarkableFile) -> Vec<String> {
+    file.layers().into_iter().map(|layer| layer.name).collect()
+}
+
+#[test]
+fn test_layer_names_and_visibility() {
+    let mut file = v6_page();
+    file.rename_layer(0, "Sketch").unwrap();
+    file.set_layer_visible(1, false).unwrap();
+
+    let mut file = reread(&file);
+    assert_eq!(
+        file.layers(),
+        vec![
+            LayerInfo {
+                name: "Sketch".to_owned(),
+                visible: true,
+                lines: 3
+            },
+            LayerInfo {
+                name: "Layer 2".to_owned(),
+                visible: false,
+                lines: 0
+            },
+        ]
+    );
+    assert_eq!(
+        file.rename_layer(2, "Missing").unwrap_err().kind,
+        ParseErrorKind::InvalidInput
+    );
+}
+
+#[test]
+fn test_move_and_delete_layers() {
+    let mut file = v6_page();
+    file.move_layer(0, 1).unwrap();
+    let mut file = reread(&file);
+    assert_eq!(names(&file), vec!["Layer 2", "Layer 1"]);
+
+    file.delete_layer(1).unwrap();
+    let file = reread(&file);
+    assert_eq!(names(&file), vec!["Layer 2"]);
+    let RemarkableFile::V6 { tree, .. } = &file else {
+        panic!("invalid version")
+    };
+    assert!(tree.visible_items().is_empty());
+}
+
+#[test]
+fn test_merge_layers() {
+    let mut file = v6_page();
+    file.merge_layers(0, 1).unwrap();
+    let file = reread(&file);
+    assert_eq!(names(&file), vec!["Layer 2"]);
+    let RemarkableFile::V6 { tree, .. } = &file else {
+        panic!("invalid version")
+    };
+    let colors: Vec<PenColor> = tree
+        .visible_items()
+        .into_iter()
+        .filter_map(|(_, item)| match item {
+            SceneItem::Line(line) => Some(line.color),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(colors, vec![PenColor::Black, PenColor::Blue, PenColor::Red]);
+
+    // the moved lines are the same lines in another layer
+    let changes = diff(&v6_page(), &file);
+    assert_eq!(
+        changes.layers,
+        vec![LayerChange::Removed("Layer 1".to_owned())]
+    );
+    assert_eq!(changes.strokes.len(), 3);
+    for change in changes.strokes {
+        let Change::Modified { before, after } = change else {
+            panic!("moved line not matched: {change:?}")
+        };
+        assert_eq!((before.id, before.points), (after.id, after.points));
+        assert_eq!(
+            (before.layer.as_str(), after.layer.as_str()),
+            ("Layer 1", "Layer 2")
+        );
+    }
+}
+
+#[test]
+fn test_split_layer() {
+    let mut file = v6_page();
+    assert_eq!(file.split_layer(0, SplitBy::Tool).unwrap(), 1);
+    let mut file = reread(&file);
+    assert_eq!(
+        file.layers()
+            .into_iter()
+            .map(|layer| (layer.name, layer.lines))
+            .collect::<Vec<_>>(),
+        vec![
+            ("Layer 1".to_owned(), 2),
+            ("Layer 1 Marker".to_owned(), 1),
+            ("Layer 2".to_owned(), 0),
+        ]
+    );
+
+    assert_eq!(file.split_layer(0, SplitBy::Color).unwrap(), 1);
+    let file = reread(&file);
+    assert_eq!(
+        names(&file),
+        vec!["Layer 1", "Layer 1 Red", "Layer 1 Marker", "Layer 2"]
+    );
+}
+
+/// Color and author of every stroke in the order it was drawn
+fn drawn(file: &RemarkableFile) -> Vec<(PenColor, Option<String>)> {
+    let drawing = PageDrawing::new(file);
+    timeline(&drawing, &ReplayOptions::default())
+        .into_iter()
+        .map(|stroke| (stroke.stroke.color, stroke.stroke.author.clone()))
+        .collect()
+}
+
+#[test]
+fn test_moved_lines_keep_origin() {
+    let (first, second) = (
+        "0c4d4d3a-8f3b-4b6e-9a53-0f4f1a2b3c4d",
+        "5e1f7c2a-1d2b-4c3d-8e4f-5a6b7c8d9e0f",
+    );
+    // the second author draws in between the lines of the first one
+    let mut builder = SceneBuilder::from_file(v6_page(), second).unwrap();
+    let layer = builder.tree().layers()[1].node_id;
+    let line = Line::new(Tool::Pencil, PenColor::Green, points(2));
+    let green = builder.add_line(layer, line).unwrap();
+    let mut builder = SceneBuilder::from_file(builder.build(), first).unwrap();
+    let layer = builder.tree().layers()[0].node_id;
+    let line = Line::new(Tool::Pencil, PenColor::Grey, points(2));
+    builder.add_line(layer, line).unwrap();
+    let mut file = reread(&builder.build());
+
+    let before = drawn(&file);
+    assert_eq!(before.len(), 5);
+    assert_eq!(before[3], (PenColor::Green, Some(second.to_owned())));
+
+    // moving is done by the first author, who gives the moved lines new ids
+    file.split_layer(0, SplitBy::Tool).unwrap();
+    let mut file = reread(&file);
+    assert_eq!(drawn(&file), before);
+    assert_eq!(file.layers().len(), 4);
+    while file.layers().len() > 1 {
+        file.merge_layers(1, 0).unwrap();
+    }
+    let file = reread(&file);
+    assert_eq!(file.layers()[0].lines, 5);
+    assert_eq!(drawn(&file), before);
+
+    let RemarkableFile::V6 { tree, .. } = &file else {
+        panic!("invalid version")
+    };
+    let (_, item) = tree
+        .visible_sequence_items()
+        .into_iter()
+        .find(|(_, item)| matches!(&item.value, SceneItem::Line(l) if l.color == PenColor::Green))
+        .unwrap();
+    assert_ne!(item.item_id, green);
+    assert_eq!(item.origin_id(), green);
+    assert_eq!(tree.author_of(&item.origin_id()), Some(second));
+    assert_eq!(tree.items_by_author(second).len(), 1);
+}
+
+#[test]
+fn test_failed_edit_keeps_page() {
+    // ids of authors above 255 can't be used to write to the page
+    let mut file = v6_page();
+    if let RemarkableFile::V6 { blocks, .. } = &mut file {
+        for block in blocks.iter_mut() {
+            if let Block::AuthorsIds(b) = block {
+                let authors = std::mem::take(&mut b.authors);
+                b.authors = authors.into_values().map(|uuid| (300, uuid)).collect();
+            }
+        }
+    }
+    let layers = file.layers();
+    assert_eq!(
+        file.rename_layer(0, "Sketch").unwrap_err().kind,
+        ParseErrorKind::InvalidInput
+    );
+    assert_eq!(file.layers(), layers);
+
+    // an edit failing halfway leaves nothing of it behind
+    let RemarkableFile::V6 { mut blocks, .. } = v6_page() else {
+        panic!("invalid version")
+    };
+    let last = blocks
+        .iter_mut()
+        .filter_map(|block| match block {
+            Block::SceneLineItem(b) => Some(b),
+            _ => None,
+        })
+        .last()
+        .unwrap();
+    last.item.item_id.part2 = u32::MAX - 1;
+    let mut file = RemarkableFile::V6 {
+        tree: SceneTree::from_blocks(&blocks).unwrap(),
+        blocks,
+    };
+    let written = file.to_bytes().unwrap();
+    assert!(file.merge_layers(0, 1).is_err());
+    assert_eq!(file.to_bytes().unwrap(), written);
+    assert_eq!(file.layers()[0].lines, 3);
+}
+
+#[test]
+fn test_layers_of_pages() {
+    let layers = |count: usize| Page {
+        layers: (0..count).map(|_| Layer { lines: vec![] }).collect(),
+    };
+    let mut file = RemarkableFile::Other {
+        version: 5,
+        pages: vec![layers(2), layers(1)],
+    };
+    // the second layer only exists on the first page
+    assert_eq!(
+        file.delete_layer(1).unwrap_err().kind,
+        ParseErrorKind::InvalidInput
+    );
+    assert!(file.merge_layers(1, 0).is_err());
+    assert!(file.split_layer(1, SplitBy::Tool).is_err());
+    assert!(file.move_layer(0, 1).is_err());
+    file.delete_layer(0).unwrap();
+    assert_eq!(file.layers().len(), 1);
+}
+
+#[test]
+fn test_layers_v5() {
+    let test_page = read("./tests/fixtures/test_v5_advent_of_code.rm").unwrap();
+    let mut file = RemarkableFile::read(&test_page[..]).unwrap();
+    let lines = file.layers()[0].lines;
+    assert_eq!(names(&file), vec!["Layer 1"]);
+    assert_eq!(
+        file.rename_layer(0, "Notes").unwrap_err().kind,
+        ParseErrorKind::Unsupported
+    );
+
+    let added = file.split_layer(0, SplitBy::Tool).unwrap();
+    let layers = reread(&file).layers();
+    assert_eq!(layers.len(), added + 1);
+    assert_eq!(layers.iter().map(|layer| layer.lines).sum::<usize>(), lines);
+
+    while file.layers().len() > 1 {
+        file.merge_layers(1, 0).unwrap();
+    }
+    assert_eq!(reread(&file).layers()[0].lines, lines);
+    file.delete_layer(0).unwrap();
+    assert!(reread(&file).layers().is_empty());
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use remarkable_lines::{
+    v6::{
+        block::{Block, TreeNodeBlock},
+        crdt::CrdtId,
+        lwwvalue::LwwValue,
+        scene_builder::SceneBuilder,
+        scene_item::{
+            group::Group,
+            text::{ParagraphStyle, TextItem},
+            SceneItem,
+        },
+    },
+    RemarkableFile,
+};
+
+use common::read_file;
+
+fn blocks(file: &mut RemarkableFile) -> &mut Vec<Block> {
+    match file {
+        RemarkableFile::V6 { blocks, .. } => blocks,
+        RemarkableFile::Other { .. } => panic!("invalid version"),
+    }
+}
+
+fn rename_layer(label: &str, part1: u8, part2: u32) -> Block {
+    Block::TreeNode(TreeNodeBlock {
+        group: Group::default()
+            .node_id(CrdtId {
+                part1: 0,
+                part2: 11,
+            })
+            .label(LwwValue {
+                timestamp: CrdtId { part1, part2 },
+                value: label.to_owned(),
+            }),
+    })
+}
+
+#[test]
+fn test_merge() {
+    let base = read_file("test_v6_single_page_line.rm");
+
+    // ours renames the layer last and draws a second line
+    let mut ours = read_file("test_v6_single_page_line.rm");
+    blocks(&mut ours).push(rename_layer("Ours", 1, 20));
+    let mut line = blocks(&mut ours)
+        .iter()
+        .find(|b| matches!(b, Block::SceneLineItem(_)))
+        .unwrap()
+        .clone();
+    if let Block::SceneLineItem(b) = &mut line {
+        b.item.item_id = CrdtId {
+            part1: 1,
+            part2: 21,
+        };
+    }
+    blocks(&mut ours).push(line);
+
+    // theirs erases the original line and renames the layer earlier
+    let mut theirs = read_file("test_v6_single_page_line.rm");
+    blocks(&mut theirs).push(rename_layer("Theirs", 2, 18));
+    for block in blocks(&mut theirs).iter_mut() {
+        if let Block::SceneLineItem(b) = block {
+            b.item.value = None;
+            b.item.deleted_length = 1;
+        }
+    }
+
+    let merged = RemarkableFile::merge(&base, &ours, &theirs).unwrap();
+    let written = merged.to_bytes().unwrap();
+    let merged = RemarkableFile::read(&written[..]).unwrap();
+    let tree = match &merged {
+        RemarkableFile::V6 { tree, .. } => tree,
+        RemarkableFile::Other { .. } => panic!("invalid version"),
+    };
+
+    let items = tree.visible_items();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].0.label.value, "Ours");
+    assert!(matches!(items[0].1, SceneItem::Line(_)));
+    let page_info = match &merged {
+        RemarkableFile::V6 { blocks, .. } => blocks.iter().find_map(|b| match b {
+            Block::PageInfo(info) => Some(info.merges_count),
+            _ => None,
+        }),
+        RemarkableFile::Other { .. } => None,
+    };
+    assert_eq!(page_info, Some(1));
+
+    // a later clock value wins regardless of which side it is on
+    let mut theirs = read_file("test_v6_single_page_line.rm");
+    blocks(&mut theirs).push(rename_layer("Theirs", 0, 30));
+    let merged = RemarkableFile::merge(&base, &ours, &theirs).unwrap();
+    let RemarkableFile::V6 { tree, .. } = merged else {
+        panic!("invalid version")
+    };
+    assert_eq!(tree.visible_items()[0].0.label.value, "Theirs");
+    assert_eq!(tree.visible_items().len(), 2);
+
+    let v5 = read_file("test_v5_math.rm");
+    assert!(RemarkableFile::merge(&v5, &v5, &v5).is_err());
+}
+
+#[test]
+fn test_merge_split_text() {
+    let mut builder = SceneBuilder::new("0c4d4d3a-8f3b-4b6e-9a53-0f4f1a2b3c4d").unwrap();
+    builder
+        .add_paragraph("abcdef", ParagraphStyle::PLAIN)
+        .unwrap();
+    let base = builder.build().to_bytes().unwrap();
+    let edit = |author: &str, change: &dyn Fn(&mut SceneBuilder)| {
+        let file = RemarkableFile::read(&base[..]).unwrap();
+        let mut builder = SceneBuilder::from_file(file, author).unwrap();
+        change(&mut builder);
+        RemarkableFile::read(&builder.build().to_bytes().unwrap()[..]).unwrap()
+    };
+
+    // ours splits the run of characters by typing into it, theirs deletes from the same run
+    let ours = edit("5e1f7c2a-1d2b-4c3d-8e4f-5a6b7c8d9e0f", &|builder| {
+        builder.insert_text(3, "X").unwrap();
+    });
+    let theirs = edit("7a2b3c4d-5e6f-4a1b-8c2d-3e4f5a6b7c8d", &|builder| {
+        builder.delete_text(1..2).unwrap();
+    });
+    let base = RemarkableFile::read(&base[..]).unwrap();
+
+    for (a, b) in [(&ours, &theirs), (&theirs, &ours)] {
+        let merged = RemarkableFile::merge(&base, a, b).unwrap();
+        let merged = RemarkableFile::read(&merged.to_bytes().unwrap()[..]).unwrap();
+        let RemarkableFile::V6 { tree, .. } = &merged else {
+            panic!("invalid version")
+        };
+        let text = tree.root_text().unwrap();
+        let chars: String = text.chars().into_iter().map(|(_, c)| c).collect();
+        assert_eq!(chars, "acXdef");
+        // every character is in exactly one item, the deleted one as a tombstone
+        let lengths = text.items.items.values().map(|item| match &item.value {
+            TextItem::Text(value) if value.is_empty() => (0, item.deleted_length),
+            TextItem::Text(value) => (value.chars().count() as u32, 0),
+            TextItem::FormatCode(_) => (1, 0),
+        });
+        let (chars, deleted) = lengths.fold((0, 0), |(c, d), (a, b)| (c + a, d + b));
+        assert_eq!((chars, deleted), (6, 1));
+    }
+}
@@ -0,0 +1,234 @@
+use std::fs::read;
+
+use remarkable_lines::{
+    json::JsonValue,
+    notebook::{Library, Notebook, PageState},
+    v6::block::Block,
+    ParseErrorKind, ParseLimits, RemarkableFile,
+};
+
+const DATA_DIR: &str = "./tests/fixtures/xochitl";
+const NOTEBOOK_ID: &str = "3a7e1b4c-9f0d-4c8e-a2b5-6d1f0e9c7b21";
+
+#[test]
+fn test_load_notebook() {
+    let notebook = Notebook::load(DATA_DIR, NOTEBOOK_ID).unwrap();
+
+    assert_eq!(notebook.name(), "Meeting notes");
+    assert_eq!(notebook.content.file_type, "notebook");
+    assert_eq!(notebook.content.format_version, 2);
+
+    // ordered by index with the deleted page left out
+    let ids: Vec<&str> = notebook.pages.iter().map(|p| p.id.as_str()).collect();
+    assert_eq!(
+        ids,
+        vec![
+            "b1e2c3d4-0000-4000-8000-000000000001",
+            "b1e2c3d4-0000-4000-8000-000000000002",
+            "b1e2c3d4-0000-4000-8000-000000000003",
+        ]
+    );
+    assert!(matches!(notebook.pages[0].state, PageState::Parsed(_)));
+    assert!(matches!(notebook.pages[1].state, PageState::Parsed(_)));
+    assert_eq!(notebook.missing_pages().count(), 1);
+    assert_eq!(notebook.invalid_pages().count(), 0);
+}
+
+#[test]
+fn test_notebook_content_v1() {
+    let notebook = Notebook::from_files(
+        "doc",
+        None,
+        r#"{"fileType": "notebook", "pages": ["a", "b"]}"#,
+        |id| Ok((id == "a").then(|| b"not a remarkable file".to_vec())),
+    )
+    .unwrap();
+    assert_eq!(notebook.name(), "doc");
+    assert!(matches!(notebook.pages[0].state, PageState::Invalid(_)));
+    assert!(matches!(notebook.pages[1].state, PageState::Missing));
+}
+
+#[test]
+fn test_json_round_trip() {
+    let input = r#"{"a": [1, 2.5, -3e2], "b": "x\"é\n", "c": {"d": null, "e": true}}"#;
+    let value = JsonValue::parse(input).unwrap();
+    assert_eq!(value.get("b").and_then(|b| b.as_str()), Some("x\"é\n"));
+    assert_eq!(JsonValue::parse(&value.to_string()).unwrap(), value);
+    assert_eq!(JsonValue::parse(&value.to_string_pretty()).unwrap(), value);
+    assert!(JsonValue::parse("{\"a\": }").is_err());
+    assert!(JsonValue::parse(&"[".repeat(1000)).is_err());
+}
+
+#[test]
+fn test_rmdoc_round_trip() {
+    let archive = read("./tests/fixtures/meeting_notes.rmdoc").unwrap();
+    let notebook = Notebook::read_rmdoc(&archive).unwrap();
+    assert_eq!(notebook.id, NOTEBOOK_ID);
+    assert_eq!(notebook.name(), "Meeting notes");
+    assert_eq!(notebook.pages.len(), 3);
+    assert!(matches!(notebook.pages[1].state, PageState::Parsed(_)));
+    assert!(notebook
+        .other_files
+        .contains_key(&format!("{NOTEBOOK_ID}.pagedata")));
+
+    let written = Notebook::read_rmdoc(&notebook.to_rmdoc().unwrap()).unwrap();
+    assert_eq!(written.id, notebook.id);
+    assert_eq!(written.name(), notebook.name());
+    assert_eq!(written.other_files, notebook.other_files);
+    for (a, b) in written.pages.iter().zip(notebook.pages.iter()) {
+        assert_eq!(a.id, b.id);
+        assert_eq!(a.data, b.data);
+    }
+
+    // the largest file is the .content file of 2019 bytes, all of them together are 4064 bytes
+    let limits = |max_archive_entry_size, max_archive_size| ParseLimits {
+        max_archive_entry_size,
+        max_archive_size,
+        ..Default::default()
+    };
+    Notebook::read_rmdoc_with_limits(&archive, limits(2019, 4064)).unwrap();
+    for limits in [limits(2018, 4064), limits(2019, 4063)] {
+        let err = Notebook::read_rmdoc_with_limits(&archive, limits).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::LimitExceeded);
+    }
+}
+
+#[test]
+fn test_tags() {
+    let notebook = Notebook::load(DATA_DIR, NOTEBOOK_ID).unwrap();
+    assert_eq!(notebook.tags(), ["work"]);
+    assert!(notebook.content.pages[0].tags.is_empty());
+    assert_eq!(notebook.content.pages[1].tags, ["meeting", "todo"]);
+
+    let meeting: Vec<_> = notebook.pages_tagged("meeting").collect();
+    assert_eq!(meeting.len(), 2);
+    assert_eq!(meeting[0].0.id, "b1e2c3d4-0000-4000-8000-000000000002");
+    assert!(matches!(meeting[0].1, Some(RemarkableFile::V6 { .. })));
+    // the last page has no .rm file
+    assert_eq!(meeting[1].0.id, "b1e2c3d4-0000-4000-8000-000000000003");
+    assert!(meeting[1].1.is_none());
+
+    // document tags don't apply to pages
+    assert_eq!(notebook.pages_tagged("work").count(), 0);
+}
+
+#[test]
+fn test_library() {
+    let library = Library::load(DATA_DIR).unwrap();
+    assert!(library.errors.is_empty());
+
+    assert!(library.root.documents.is_empty());
+    assert_eq!(library.root.folders.len(), 1);
+    let work = &library.root.folders[0];
+    assert_eq!(work.name, "Work");
+    assert_eq!(work.documents.len(), 1);
+    assert_eq!(work.documents[0].id, NOTEBOOK_ID);
+    assert_eq!(work.documents[0].pages.len(), 3);
+
+    assert_eq!(library.trash.documents.len(), 1);
+    assert_eq!(library.trash.documents[0].name(), "Old sketches");
+
+    let documents = library.root.documents_recursive();
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].0, vec!["Work"]);
+}
+
+#[test]
+fn test_write_notebook() {
+    let data_dir = std::env::temp_dir().join(format!("rm-write-{}", std::process::id()));
+
+    // written back unchanged
+    let notebook = Notebook::load(DATA_DIR, NOTEBOOK_ID).unwrap();
+    notebook.write(&data_dir).unwrap();
+    let written = Notebook::load(&data_dir, NOTEBOOK_ID).unwrap();
+    assert_eq!(written.name(), "Meeting notes");
+    assert_eq!(written.other_files, notebook.other_files);
+    for (a, b) in written.pages.iter().zip(notebook.pages.iter()) {
+        assert_eq!(a.id, b.id);
+        assert_eq!(a.data, b.data);
+    }
+
+    let mut notebook = Notebook::new("Written");
+    let author = notebook.content.authors()[&1].clone();
+    let test_page = read("./tests/fixtures/test_v6_normal_ab.rm").unwrap();
+    let file = match RemarkableFile::read(&test_page[..]).unwrap() {
+        RemarkableFile::V6 { tree, blocks } => RemarkableFile::V6 {
+            tree,
+            blocks: blocks
+                .into_iter()
+                .filter(|b| !matches!(b, Block::AuthorsIds(_)))
+                .collect(),
+        },
+        RemarkableFile::Other { .. } => panic!("invalid version"),
+    };
+    notebook.add_page(Some(file), Some("P Grid small")).unwrap();
+    notebook.add_page(None, None).unwrap();
+    let test_page = read("./tests/fixtures/test_v5_advent_of_code.rm").unwrap();
+    let file = RemarkableFile::read(&test_page[..]).unwrap();
+    notebook.add_page(Some(file), None).unwrap();
+    notebook.write(&data_dir).unwrap();
+
+    let written = Notebook::load(&data_dir, &notebook.id).unwrap();
+    assert_eq!(written.name(), "Written");
+    assert_eq!(written.content.file_type, "notebook");
+    assert_eq!(written.content.format_version, 2);
+    let ids: Vec<&str> = written.pages.iter().map(|p| p.id.as_str()).collect();
+    let expected: Vec<&str> = notebook.pages.iter().map(|p| p.id.as_str()).collect();
+    assert_eq!(ids, expected);
+    assert!(matches!(written.pages[0].state, PageState::Parsed(_)));
+    assert!(matches!(written.pages[1].state, PageState::Missing));
+    assert!(matches!(written.pages[2].state, PageState::Parsed(_)));
+    assert_eq!(
+        written.page_templates(),
+        vec![
+            Some("P Grid small".to_owned()),
+            Some("Blank".to_owned()),
+            Some("Blank".to_owned())
+        ]
+    );
+
+    // the page is attributed to the author of the document
+    match written.pages[0].file() {
+        Some(RemarkableFile::V6 { blocks, .. }) => match &blocks[0] {
+            Block::AuthorsIds(block) => assert_eq!(block.authors[&1], author),
+            _ => panic!("expected authors first"),
+        },
+        _ => panic!("invalid version"),
+    }
+    assert_eq!(written.content.authors()[&1], author);
+
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+#[test]
+fn test_add_page_authors() {
+    let test_page = read("./tests/fixtures/test_v6_normal_ab.rm").unwrap();
+    let mut file = RemarkableFile::read(&test_page[..]).unwrap();
+    let author = |i: u16| format!("6f0c2b1a-3d4e-4f5a-8b6c-7d8e9f0a1b{i:02x}");
+    if let RemarkableFile::V6 { blocks, .. } = &mut file {
+        for block in blocks.iter_mut() {
+            if let Block::AuthorsIds(block) = block {
+                block.authors.clear();
+                for i in 1..=20 {
+                    block.authors.insert(i * 3, author(i));
+                }
+            }
+        }
+    }
+
+    // authors are added in the order of their ids in the page, which keeps its own ids
+    let mut notebook = Notebook::new("Authors");
+    let document_author = notebook.content.authors()[&1].clone();
+    let page = notebook.add_page(Some(file), None).unwrap();
+    match page.file() {
+        Some(RemarkableFile::V6 { blocks, .. }) => match &blocks[0] {
+            Block::AuthorsIds(block) => assert_eq!(block.authors[&60], author(20)),
+            _ => panic!("expected authors first"),
+        },
+        _ => panic!("invalid version"),
+    }
+    let mut expected = vec![document_author];
+    expected.extend((1..=20).map(author));
+    let authors: Vec<String> = notebook.content.authors().into_values().collect();
+    assert_eq!(authors, expected);
+}
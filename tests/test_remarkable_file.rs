@@ -1,20 +1,22 @@
 use std::{collections::HashMap, fs::read, hash::Hash};
 
 use remarkable_lines::{
+    shared::{pen_color::PenColor, tool::Tool},
     v6::{
         block::*,
         crdt::{CrdtId, CrdtSequenceItem},
         lwwvalue::LwwValue,
         scene_item::{
             group::Group,
+            line::Line,
             text::{ParagraphStyle, Text, TextItem},
         },
     },
-    RemarkableFile,
+    ParseErrorKind, ParseLimits, RemarkableFile,
 };
 
 fn vec_to_hashmap<K: Eq + Hash, V>(items: Vec<(K, V)>) -> HashMap<K, V> {
-    return items.into_iter().collect();
+    items.into_iter().collect()
 }
 
 #[test]
@@ -141,3 +143,176 @@ fn test_v6_single_page_line() {
         }
     }
 }
+
+#[test]
+fn test_tool_and_color_codes_round_trip() {
+    assert_eq!(Tool::from(0x01), Tool::Pencil);
+    assert_eq!(Tool::from(0x0e), Tool::Pencil2);
+    assert_eq!(Tool::from(0x0e).base(), Tool::Pencil);
+    assert_eq!(Tool::from(0x17), Tool::Shader);
+    for code in 0..0x20 {
+        assert_eq!(u32::from(Tool::from(code)), code);
+        assert_eq!(u32::from(PenColor::from(code)), code);
+    }
+    assert_eq!(Tool::from(0xff), Tool::Unknown(0xff));
+    assert_eq!(PenColor::from(0xff), PenColor::Unknown(0xff));
+}
+
+#[test]
+fn test_pen_color_rgba() {
+    assert_eq!(PenColor::Black.to_rgba(), [0, 0, 0, 255]);
+    let custom = PenColor::Highlight.with_argb(0x80ff0010);
+    assert_eq!(custom.to_rgba(), [0xff, 0x00, 0x10, 0x80]);
+    assert_eq!(u32::from(custom), 0x09);
+}
+
+#[test]
+fn test_v6_line_points() {
+    let test_page = read("./tests/fixtures/test_v6_single_page_line.rm").unwrap();
+    let rm_file = RemarkableFile::read(&test_page[..]).unwrap();
+
+    match rm_file {
+        RemarkableFile::Other { .. } => panic!("invalid version"),
+        RemarkableFile::V6 { blocks, .. } => {
+            let lines: Vec<_> = blocks
+                .into_iter()
+                .filter_map(|b| match b {
+                    Block::SceneLineItem(b) => b.item.value,
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(lines.len(), 1);
+            assert_eq!(lines[0].tool, Tool::BallPoint2);
+            assert_eq!(lines[0].color, PenColor::Black);
+            assert_eq!(lines[0].points.len(), 46);
+        }
+    }
+}
+
+#[test]
+fn test_limits() {
+    let test_page = read("./tests/fixtures/test_v6_single_page_line.rm").unwrap();
+    let limits = ParseLimits {
+        max_points_per_line: 10,
+        ..Default::default()
+    };
+    let err = RemarkableFile::read_with_limits(&test_page[..], limits).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::LimitExceeded);
+
+    // truncated input should never panic
+    for i in 0..test_page.len() {
+        let _ = RemarkableFile::read(&test_page[..i]);
+    }
+}
+
+#[test]
+fn test_sniff() {
+    let test_page = read("./tests/fixtures/test_v6_normal_ab.rm").unwrap();
+    let info = RemarkableFile::sniff(&test_page);
+    assert_eq!(info.version, Some(6));
+    assert!(info.supported);
+    assert!(!info.truncated);
+    assert_eq!(info.block_count, Some(8));
+    assert_eq!(info.block_types.get(&0x02), Some(&2));
+
+    let test_page = read("./tests/fixtures/test_v5_math.rm").unwrap();
+    let info = RemarkableFile::sniff(&test_page);
+    assert_eq!(info.header, "reMarkable .lines file, version=5");
+    assert_eq!(info.version, Some(5));
+    assert_eq!(info.block_count, None);
+
+    let info = RemarkableFile::sniff(b"%PDF-1.7");
+    assert_eq!(info.version, None);
+    assert!(!info.supported);
+}
+
+#[test]
+fn test_write_round_trip() {
+    // files written by a recent firmware come out byte for byte
+    for name in [
+        "test_v5_advent_of_code.rm",
+        "test_v6_highlights.rm",
+        "test_v6_normal_ab.rm",
+        "test_v6_single_page_line.rm",
+    ] {
+        let test_page = read(format!("./tests/fixtures/{name}")).unwrap();
+        let rm_file = RemarkableFile::read(&test_page[..]).unwrap();
+        assert_eq!(rm_file.to_bytes().unwrap(), test_page, "{name}");
+    }
+
+    // edited lines of older blocks are upgraded, which keeps every point
+    let test_page = read("./tests/fixtures/test_v6_single_page_line.rm").unwrap();
+    let mut rm_file = RemarkableFile::read(&test_page[..]).unwrap();
+    if let RemarkableFile::V6 { blocks, .. } = &mut rm_file {
+        for block in blocks.iter_mut() {
+            if let Block::SceneLineItem(b) = block {
+                if let Some(line) = b.item.value.as_mut() {
+                    line.points[0].x += 1.0;
+                }
+            }
+        }
+    }
+    let written = rm_file.to_bytes().unwrap();
+    assert!(written.len() < test_page.len());
+    let reread = RemarkableFile::read(&written[..]).unwrap();
+    assert_eq!(reread.to_bytes().unwrap(), written);
+    let lines = |file: RemarkableFile| match file {
+        RemarkableFile::V6 { blocks, .. } => blocks
+            .into_iter()
+            .filter_map(|b| match b {
+                Block::SceneLineItem(b) => b.item.value,
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        RemarkableFile::Other { .. } => panic!("invalid version"),
+    };
+    let (original, reread) = (lines(rm_file), lines(reread));
+    assert_eq!(original.len(), reread.len());
+    for (a, b) in original.iter().zip(reread.iter()) {
+        assert_eq!(a.points.len(), b.points.len());
+        for (p, q) in a.points.iter().zip(b.points.iter()) {
+            assert_eq!((p.x, p.y), (q.x, q.y));
+            assert!((p.width - q.width).abs() <= 0.5);
+        }
+    }
+
+    // the trailing byte after the last line is not kept
+    let test_page = read("./tests/fixtures/test_v5_math.rm").unwrap();
+    let rm_file = RemarkableFile::read(&test_page[..]).unwrap();
+    let written = rm_file.to_bytes().unwrap();
+    assert_eq!(written[..], test_page[..test_page.len() - 1]);
+}
+
+#[test]
+fn test_line_fields_kept() {
+    let test_page = read("./tests/fixtures/test_v6_single_page_line.rm").unwrap();
+    let lines = |file: &mut RemarkableFile, edit: &dyn Fn(&mut Line)| {
+        let mut edited = vec![];
+        if let RemarkableFile::V6 { blocks, .. } = file {
+            for block in blocks.iter_mut() {
+                if let Block::SceneLineItem(b) = block {
+                    if let Some(line) = b.item.value.as_mut() {
+                        edit(line);
+                        edited.push(line.clone());
+                    }
+                }
+            }
+        }
+        edited
+    };
+
+    // a line without a timestamp followed by a field of a newer firmware, tag 9 holding 4 bytes
+    let mut rm_file = RemarkableFile::read(&test_page[..]).unwrap();
+    lines(&mut rm_file, &|line| {
+        line.timestamp = None;
+        line.unknown_fields = vec![0x94, 1, 2, 3, 4];
+    });
+    let written = rm_file.to_bytes().unwrap();
+    let mut reread = RemarkableFile::read(&written[..]).unwrap();
+    let reread_lines = lines(&mut reread, &|_| ());
+    assert_eq!(reread_lines.len(), 1);
+    assert_eq!(reread_lines[0].timestamp, None);
+    assert_eq!(reread_lines[0].unknown_fields, vec![0x94, 1, 2, 3, 4]);
+    assert_eq!(reread_lines[0].points.len(), 46);
+    assert_eq!(reread.to_bytes().unwrap(), written);
+}
@@ -0,0 +1,673 @@
+mod common;
+
+use std::{collections::HashMap, fs::read};
+
+use remarkable_lines::{
+    diff::{diff, Change},
+    shared::{pen_color::PenColor, tool::Tool},
+    v6::{
+        block::{Block, SceneItemBlock, SceneTreeBlock, TreeNodeBlock},
+        crdt::{CrdtId, CrdtSequence, CrdtSequenceItem},
+        lwwvalue::LwwValue,
+        scene_builder::SceneBuilder,
+        scene_item::{
+            glyph_range::{GlyphRange, Rectangle},
+            group::Group,
+            line::Line,
+            text::{ParagraphStyle, Text, TextItem},
+            SceneItem,
+        },
+        scene_tree::SceneTree,
+    },
+    ParseErrorKind, ParseLimits, RemarkableFile,
+};
+
+use common::points;
+
+fn id(part2: u32) -> CrdtId {
+    CrdtId { part1: 1, part2 }
+}
+
+fn item<T>(item_id: u32, left: u32, right: u32, value: T) -> CrdtSequenceItem<T> {
+    CrdtSequenceItem {
+        item_id: id(item_id),
+        left_id: if left == 0 {
+            CrdtId::default()
+        } else {
+            id(left)
+        },
+        right_id: if right == 0 {
+            CrdtId::default()
+        } else {
+            id(right)
+        },
+        deleted_length: 0,
+        value,
+    }
+}
+
+#[test]
+fn test_crdt_sequence_order() {
+    // 13 was inserted between 10 and 11, 12 at the start
+    let sequence: CrdtSequence<&str> = [
+        item(11, 10, 0, "c"),
+        item(10, 0, 0, "a"),
+        item(13, 10, 11, "b"),
+        item(12, 0, 10, "start"),
+    ]
+    .into_iter()
+    .collect();
+    let values: Vec<&str> = sequence.iter().map(|i| i.value).collect();
+    assert_eq!(values, vec!["start", "a", "b", "c"]);
+
+    // concurrent inserts at the same spot are ordered by author, not by clock value
+    let concurrent = |part1, part2, value| CrdtSequenceItem {
+        item_id: CrdtId { part1, part2 },
+        left_id: CrdtId::default(),
+        right_id: CrdtId::default(),
+        deleted_length: 0,
+        value,
+    };
+    let sequence: CrdtSequence<&str> = [
+        concurrent(2, 1, "second author"),
+        concurrent(1, 7, "first author"),
+    ]
+    .into_iter()
+    .collect();
+    let values: Vec<&str> = sequence.iter().map(|i| i.value).collect();
+    assert_eq!(values, vec!["first author", "second author"]);
+}
+
+#[test]
+fn test_nested_groups() {
+    let root = SceneTree::new().root_id();
+    let node = |tree_id: CrdtId, parent_id: CrdtId| {
+        Block::SceneTree(SceneTreeBlock {
+            tree_id,
+            node_id: CrdtId::default(),
+            is_update: true,
+            parent_id,
+        })
+    };
+    let group_item = |parent_id: CrdtId, item_id: u32, group: CrdtId| {
+        Block::SceneGroupItem(SceneItemBlock {
+            parent_id,
+            item: item(item_id, 0, 0, Some(group)),
+        })
+    };
+    let glyph = GlyphRange {
+        start: 0,
+        length: 3,
+        text: "fox".to_owned(),
+        color: PenColor::Yellow,
+        rectangles: vec![Rectangle {
+            x: 0.0,
+            y: 0.0,
+            w: 10.0,
+            h: 10.0,
+        }],
+    };
+    let (layer, nested) = (id(10), id(11));
+    let blocks = [
+        node(layer, root),
+        node(nested, layer),
+        group_item(root, 20, layer),
+        group_item(layer, 21, nested),
+        // added after the nested group was added to the layer
+        Block::SceneGlyphItem(SceneItemBlock {
+            parent_id: nested,
+            item: item(22, 0, 0, Some(glyph)),
+        }),
+    ];
+    let tree = SceneTree::from_blocks(&blocks).unwrap();
+    let items = tree.visible_items();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].0.node_id, nested);
+
+    // the copies of the groups inside their parents hold the final state
+    let child_group = |children: &CrdtSequence<SceneItem>| match &children.iter().next()?.value {
+        SceneItem::Group(group) => Some(group.clone()),
+        _ => None,
+    };
+    let check_copies = |tree: &SceneTree| {
+        let layer_group = child_group(&tree.root().unwrap().children).unwrap();
+        let nested_group = child_group(&layer_group.children).unwrap();
+        assert_eq!(nested_group.node_id, nested);
+        assert!(matches!(
+            &nested_group.children.iter().next().unwrap().value,
+            SceneItem::GlyphRange(g) if g.text == "fox"
+        ));
+    };
+    check_copies(&tree);
+
+    // applying one block at a time updates the copies of the changed group and its ancestors
+    let mut live = SceneTree::new();
+    for block in blocks.iter() {
+        live.apply(block).unwrap();
+    }
+    check_copies(&live);
+}
+
+#[test]
+fn test_nesting_depth() {
+    let root = SceneTree::new().root_id();
+    let chain = |length: u32| {
+        let groups: Vec<CrdtId> = (0..length).map(|i| id(10 + i)).collect();
+        let mut blocks: Vec<Block> = groups
+            .iter()
+            .map(|group| {
+                Block::SceneTree(SceneTreeBlock {
+                    tree_id: *group,
+                    node_id: CrdtId::default(),
+                    is_update: true,
+                    parent_id: root,
+                })
+            })
+            .collect();
+        // the deepest group is added to its parent first, before the parent is placed itself
+        for (i, group) in groups.iter().enumerate().rev() {
+            let parent_id = if i == 0 { root } else { groups[i - 1] };
+            blocks.push(Block::SceneGroupItem(SceneItemBlock {
+                parent_id,
+                item: item(10 + length + i as u32, 0, 0, Some(*group)),
+            }));
+        }
+        blocks
+    };
+
+    let limits = ParseLimits {
+        max_nesting_depth: 8,
+        ..ParseLimits::default()
+    };
+    assert!(SceneTree::from_blocks_with_limits(&chain(8), &limits).is_ok());
+    let err = SceneTree::from_blocks_with_limits(&chain(9), &limits).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::LimitExceeded);
+    let err = SceneTree::from_blocks(&chain(100_000)).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::LimitExceeded);
+}
+
+fn glyph(start: u32, text: &str, color: PenColor) -> SceneItem {
+    SceneItem::GlyphRange(GlyphRange {
+        start,
+        length: text.chars().count() as u32,
+        text: text.to_owned(),
+        color,
+        rectangles: vec![Rectangle {
+            x: start as f64,
+            y: 0.0,
+            w: 10.0,
+            h: 10.0,
+        }],
+    })
+}
+
+#[test]
+fn test_highlights() {
+    let mut tree = SceneTree::new();
+    let root = tree.root_id();
+    let glyphs = [
+        glyph(20, "brown fox", PenColor::Yellow),
+        glyph(0, "The quick", PenColor::Yellow),
+        glyph(4, "quick brown", PenColor::Yellow),
+        glyph(30, "jumps", PenColor::Green),
+        glyph(26, "fox jumps", PenColor::Yellow),
+    ];
+    for (i, glyph) in glyphs.into_iter().enumerate() {
+        tree.add_item(item(10 + i as u32, 0, 0, glyph), root)
+            .unwrap();
+    }
+
+    let highlights = tree.highlights();
+    let texts: Vec<(&str, u32)> = highlights
+        .iter()
+        .map(|h| (h.text.as_str(), h.start))
+        .collect();
+    assert_eq!(
+        texts,
+        vec![
+            ("The quick brown", 0),
+            ("brown fox jumps", 20),
+            ("jumps", 30)
+        ]
+    );
+    assert_eq!(highlights[0].length, 15);
+    assert_eq!(highlights[0].rectangles.len(), 2);
+    assert_eq!(highlights[2].color, PenColor::Green);
+}
+
+fn lww<T>(part1: u8, part2: u32, value: T) -> LwwValue<T> {
+    LwwValue {
+        timestamp: CrdtId { part1, part2 },
+        value,
+    }
+}
+
+#[test]
+fn test_lww_value() {
+    // the clock value is compared before the author
+    assert!(CrdtId { part1: 2, part2: 5 } < CrdtId { part1: 1, part2: 6 });
+    assert!(CrdtId { part1: 1, part2: 6 } < CrdtId { part1: 2, part2: 6 });
+
+    let mut label = lww(1, 10, "a");
+    assert!(!label.update(CrdtId { part1: 2, part2: 9 }, "b"));
+    assert!(!label.merge(lww(1, 10, "c")));
+    assert_eq!(label.value, "a");
+    assert!(label.merge(lww(2, 10, "d")));
+    assert_eq!(label.value, "d");
+    assert_eq!(
+        label.timestamp,
+        CrdtId {
+            part1: 2,
+            part2: 10
+        }
+    );
+}
+
+#[test]
+fn test_tree_node_updates() {
+    let node_id = id(11);
+    let tree_node = |label: LwwValue<String>, visible: LwwValue<bool>| {
+        let mut group = Group::default().node_id(node_id).label(label);
+        group.visible = visible;
+        Block::TreeNode(TreeNodeBlock { group })
+    };
+    let blocks = [
+        Block::SceneTree(SceneTreeBlock {
+            tree_id: node_id,
+            node_id: CrdtId::default(),
+            is_update: true,
+            parent_id: CrdtId { part1: 0, part2: 1 },
+        }),
+        tree_node(lww(0, 12, "Layer 1".to_owned()), lww(0, 0, true)),
+        tree_node(lww(1, 20, "Renamed".to_owned()), lww(0, 0, true)),
+        // an older label is ignored while the newer visibility is applied
+        tree_node(lww(1, 15, "Stale".to_owned()), lww(1, 16, false)),
+    ];
+    let tree = SceneTree::from_blocks(&blocks).unwrap();
+    let node = tree.node(&node_id).unwrap();
+    assert_eq!(node.label.value, "Renamed");
+    assert!(!node.visible.value);
+}
+
+#[test]
+fn test_authors() {
+    let test_page = read("./tests/fixtures/test_v6_single_page_line.rm").unwrap();
+    let RemarkableFile::V6 { tree, .. } = RemarkableFile::read(&test_page[..]).unwrap() else {
+        panic!("invalid version")
+    };
+    let author = "d63ff1c3-0764-6e54-b226-29f98c368f79";
+    assert_eq!(
+        tree.author_of(&CrdtId {
+            part1: 1,
+            part2: 15
+        }),
+        Some(author)
+    );
+    assert_eq!(
+        tree.author_of(&CrdtId {
+            part1: 2,
+            part2: 15
+        }),
+        None
+    );
+    assert_eq!(tree.items_by_author(author).len(), 1);
+    assert!(tree.items_by_author("someone else").is_empty());
+}
+
+#[test]
+fn test_apply_blocks() {
+    for name in [
+        "test_v6_highlights.rm",
+        "test_v6_normal_ab.rm",
+        "test_v6_single_page_line.rm",
+    ] {
+        let test_page = read(format!("./tests/fixtures/{name}")).unwrap();
+        let RemarkableFile::V6 { tree, blocks } = RemarkableFile::read(&test_page[..]).unwrap()
+        else {
+            panic!("invalid version")
+        };
+        let mut live = SceneTree::new();
+        for block in blocks.iter() {
+            live.apply(block).unwrap();
+        }
+        let items = |tree: &SceneTree| {
+            tree.visible_items()
+                .into_iter()
+                .map(|(group, item)| format!("{:?} {item:?}", group.node_id))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(items(&live), items(&tree), "{name}");
+        assert_eq!(live.highlights().len(), tree.highlights().len(), "{name}");
+        assert_eq!(live.root_text().is_some(), tree.root_text().is_some());
+    }
+
+    // a later block for the same id deletes the line
+    let test_page = read("./tests/fixtures/test_v6_single_page_line.rm").unwrap();
+    let RemarkableFile::V6 { mut tree, blocks } = RemarkableFile::read(&test_page[..]).unwrap()
+    else {
+        panic!("invalid version")
+    };
+    let mut deleted = blocks
+        .iter()
+        .find_map(|b| match b {
+            Block::SceneLineItem(b) => Some(b.clone()),
+            _ => None,
+        })
+        .unwrap();
+    deleted.item.value = None;
+    deleted.item.deleted_length = 1;
+    assert_eq!(tree.visible_items().len(), 1);
+    tree.apply(&Block::SceneLineItem(deleted)).unwrap();
+    assert!(tree.visible_items().is_empty());
+}
+
+#[test]
+fn test_group_tombstone() {
+    // a SceneGroupItemBlock without a node id deletes the group from its parent
+    let (root, layer) = (SceneTree::new().root_id(), id(10));
+    let group_item = |value: Option<CrdtId>| {
+        let mut item = item(20, 0, 0, value);
+        item.deleted_length = value.is_none() as u32;
+        Block::SceneGroupItem(SceneItemBlock {
+            parent_id: root,
+            item,
+        })
+    };
+    let mut blocks = vec![
+        Block::SceneTree(SceneTreeBlock {
+            tree_id: layer,
+            node_id: CrdtId::default(),
+            is_update: true,
+            parent_id: root,
+        }),
+        group_item(Some(layer)),
+    ];
+    let mut tree = SceneTree::from_blocks(&blocks).unwrap();
+    assert_eq!(tree.root().unwrap().children.items.len(), 1);
+
+    blocks.push(group_item(None));
+    tree.apply(&blocks[2]).unwrap();
+    assert!(tree.root().unwrap().children.items.is_empty());
+    let tree = SceneTree::from_blocks(&blocks).unwrap();
+    assert!(tree.root().unwrap().children.items.is_empty());
+}
+
+#[test]
+fn test_compact() {
+    let test_page = read("./tests/fixtures/test_v6_normal_ab.rm").unwrap();
+    let mut file = RemarkableFile::read(&test_page[..]).unwrap();
+    let line_page = read("./tests/fixtures/test_v6_single_page_line.rm").unwrap();
+    let RemarkableFile::V6 {
+        blocks: line_blocks,
+        ..
+    } = RemarkableFile::read(&line_page[..]).unwrap()
+    else {
+        panic!("invalid version")
+    };
+    let RemarkableFile::V6 { blocks, .. } = &mut file else {
+        panic!("invalid version")
+    };
+
+    // pile up history: a line drawn and erased, a stale layer update and deleted text
+    let mut line = line_blocks
+        .into_iter()
+        .find_map(|b| match b {
+            Block::SceneLineItem(b) => Some(b),
+            _ => None,
+        })
+        .unwrap();
+    line.item.item_id = id(100);
+    blocks.push(Block::SceneLineItem(line.clone()));
+    line.item.value = None;
+    line.item.deleted_length = 1;
+    blocks.push(Block::SceneLineItem(line));
+    let stale = blocks
+        .iter()
+        .find(|b| matches!(b, Block::TreeNode(_)))
+        .unwrap()
+        .clone();
+    blocks.push(stale);
+    for block in blocks.iter_mut() {
+        if let Block::RootText(b) = block {
+            b.text.items.push(CrdtSequenceItem {
+                deleted_length: 4,
+                ..item(101, 16, 0, TextItem::Text(String::new()))
+            });
+        }
+    }
+    let file = RemarkableFile::read(&file.to_bytes().unwrap()[..]).unwrap();
+
+    let written = file.compact().unwrap().to_bytes().unwrap();
+    assert!(written.len() < file.to_bytes().unwrap().len());
+    assert!(written.len() <= test_page.len());
+
+    let compacted = RemarkableFile::read(&written[..]).unwrap();
+    let (RemarkableFile::V6 { tree: a, blocks }, RemarkableFile::V6 { tree: b, .. }) =
+        (&compacted, &file)
+    else {
+        panic!("invalid version")
+    };
+    let items = |tree: &SceneTree| {
+        tree.visible_items()
+            .into_iter()
+            .map(|(group, item)| format!("{:?} {item:?}", group.node_id))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(items(a), items(b));
+    assert_eq!(
+        a.node(&CrdtId {
+            part1: 0,
+            part2: 11
+        })
+        .map(|g| g.label.value.clone()),
+        Some("Layer 1".to_owned())
+    );
+    assert!(!blocks.iter().any(|b| match b {
+        Block::SceneLineItem(_) => true,
+        Block::RootText(b) => b.text.items.items.contains_key(&id(101)),
+        _ => false,
+    }));
+    let tree_nodes = blocks
+        .iter()
+        .filter(|b| matches!(b, Block::TreeNode(_)))
+        .count();
+    assert_eq!(tree_nodes, 2);
+}
+
+#[test]
+fn test_scene_builder() {
+    let mut builder = SceneBuilder::new("0c4d4d3a-8f3b-4b6e-9a53-0f4f1a2b3c4d").unwrap();
+    let layer = builder.add_layer("Layer 1").unwrap();
+    let first = builder
+        .add_line(
+            layer,
+            Line::new(Tool::FineLiner, PenColor::Black, points(3)),
+        )
+        .unwrap();
+    let second = builder
+        .add_line(layer, Line::new(Tool::Marker, PenColor::Blue, points(5)))
+        .unwrap();
+    assert!(first < second);
+    builder
+        .add_paragraph("Worksheet", ParagraphStyle::HEADING)
+        .unwrap();
+    let body = builder
+        .add_paragraph("Name:", ParagraphStyle::PLAIN)
+        .unwrap();
+
+    let written = builder.build().to_bytes().unwrap();
+    let RemarkableFile::V6 { tree, .. } = RemarkableFile::read(&written[..]).unwrap() else {
+        panic!("invalid version")
+    };
+    let items = tree.visible_items();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].0.label.value, "Layer 1");
+    assert!(matches!(items[0].1, SceneItem::Line(line) if line.tool == Tool::FineLiner));
+    assert!(matches!(items[1].1, SceneItem::Line(line) if line.points.len() == 5));
+
+    let text = tree.root_text().unwrap();
+    let text: String = text
+        .items
+        .iter()
+        .filter_map(|item| match &item.value {
+            TextItem::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(text, "Worksheet\nName:");
+    let styles = &tree.root_text().unwrap().styles;
+    assert_eq!(styles[&CrdtId::default()].value, ParagraphStyle::HEADING);
+    let newline = CrdtId {
+        part1: body.part1,
+        part2: body.part2 - 1,
+    };
+    assert_eq!(styles[&newline].value, ParagraphStyle::PLAIN);
+
+    // a second author continues after the newest id of the page
+    let mut builder = SceneBuilder::from_file(
+        RemarkableFile::read(&written[..]).unwrap(),
+        "5e1f7c2a-1d2b-4c3d-8e4f-5a6b7c8d9e0f",
+    )
+    .unwrap();
+    let line = builder
+        .add_line(layer, Line::new(Tool::Pencil, PenColor::Red, points(2)))
+        .unwrap();
+    assert_eq!(line.part1, 2);
+    assert!(line.part2 > body.part2);
+    assert_eq!(
+        builder.tree().author_of(&line),
+        Some("5e1f7c2a-1d2b-4c3d-8e4f-5a6b7c8d9e0f")
+    );
+    assert_eq!(builder.tree().visible_items().len(), 3);
+}
+
+#[test]
+fn test_scene_builder_ids_exhausted() {
+    let author = "0c4d4d3a-8f3b-4b6e-9a53-0f4f1a2b3c4d";
+    let mut builder = SceneBuilder::new(author).unwrap();
+    let layer = builder.add_layer("Layer 1").unwrap();
+    builder
+        .add_line(layer, Line::new(Tool::Pencil, PenColor::Black, points(2)))
+        .unwrap();
+    let RemarkableFile::V6 { mut blocks, .. } = builder.build() else {
+        panic!("invalid version")
+    };
+    // the newest id of the page is close to the largest one
+    for block in blocks.iter_mut() {
+        if let Block::SceneLineItem(b) = block {
+            b.item.item_id = id(u32::MAX - 2);
+        }
+    }
+    let file = RemarkableFile::V6 {
+        tree: SceneTree::from_blocks(&blocks).unwrap(),
+        blocks,
+    };
+
+    let mut builder = SceneBuilder::from_file(file, author).unwrap();
+    assert!(builder.insert_text(0, "abc").is_err());
+    let line = || Line::new(Tool::Pencil, PenColor::Black, points(2));
+    assert_eq!(builder.add_line(layer, line()).unwrap(), id(u32::MAX - 1));
+    assert_eq!(builder.add_line(layer, line()).unwrap(), id(u32::MAX));
+    assert!(builder.add_line(layer, line()).is_err());
+}
+
+fn text_string(text: &Text) -> String {
+    text.chars().into_iter().map(|(_, c)| c).collect()
+}
+
+#[test]
+fn test_text_editing() {
+    let mut text = Text {
+        items: CrdtSequence::from_iter([item(1, 0, 0, TextItem::Text("Hello".to_owned()))]),
+        styles: HashMap::new(),
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+    };
+    text.insert(2, "XY", id(10)).unwrap();
+    assert_eq!(text_string(&text), "HeXYllo");
+    // the item is split and the new one points at the characters around it
+    let inserted = &text.items.items[&id(10)];
+    assert_eq!((inserted.left_id, inserted.right_id), (id(2), id(3)));
+    assert_eq!(text.items.items.len(), 3);
+
+    text.delete(1..4).unwrap();
+    assert_eq!(text_string(&text), "Hllo");
+    let tombstone = &text.items.items[&id(2)];
+    assert!(matches!(&tombstone.value, TextItem::Text(t) if t.is_empty()));
+    assert_eq!(tombstone.deleted_length, 1);
+    assert_eq!(text.items.items[&id(10)].deleted_length, 2);
+
+    // inserting next to deleted characters keeps them in place
+    text.insert(1, "a", id(20)).unwrap();
+    assert_eq!(text_string(&text), "Hallo");
+    assert!(text.insert(6, "!", id(30)).is_err());
+    // ids of the text, deleted characters included, can't be used again
+    let err = text.insert(0, "abc", id(9)).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidInput);
+    assert!(text.insert(0, "b", id(2)).is_err());
+    assert_eq!(text_string(&text), "Hallo");
+    assert!(text.delete(3..9).is_err());
+
+    text.set_paragraph_style(0, ParagraphStyle::HEADING, id(21))
+        .unwrap();
+    text.set_paragraph_style(0, ParagraphStyle::BOLD, id(5))
+        .unwrap();
+    assert_eq!(
+        text.styles[&CrdtId::default()].value,
+        ParagraphStyle::HEADING
+    );
+    assert!(text
+        .set_paragraph_style(1, ParagraphStyle::PLAIN, id(22))
+        .is_err());
+}
+
+#[test]
+fn test_scene_builder_text() {
+    let mut builder = SceneBuilder::new("0c4d4d3a-8f3b-4b6e-9a53-0f4f1a2b3c4d").unwrap();
+    builder
+        .add_paragraph("Worksheet", ParagraphStyle::HEADING)
+        .unwrap();
+    builder
+        .add_paragraph("Name:", ParagraphStyle::PLAIN)
+        .unwrap();
+    let before = builder.build();
+    let written = before.to_bytes().unwrap();
+
+    let mut builder = SceneBuilder::from_file(
+        RemarkableFile::read(&written[..]).unwrap(),
+        "5e1f7c2a-1d2b-4c3d-8e4f-5a6b7c8d9e0f",
+    )
+    .unwrap();
+    let name = builder.insert_text(15, " Ada").unwrap();
+    assert_eq!(name.part1, 2);
+    builder.delete_text(0..4).unwrap();
+    builder
+        .set_paragraph_style(1, ParagraphStyle::BULLET)
+        .unwrap();
+    builder
+        .add_paragraph("Date:", ParagraphStyle::PLAIN)
+        .unwrap();
+
+    let after = RemarkableFile::read(&builder.build().to_bytes().unwrap()[..]).unwrap();
+    let RemarkableFile::V6 { tree, .. } = &after else {
+        panic!("invalid version")
+    };
+    let text = tree.root_text().unwrap();
+    assert_eq!(text_string(text), "sheet\nName: Ada\nDate:");
+    let newline = text.chars()[5].0;
+    assert_eq!(text.styles[&newline].value, ParagraphStyle::BULLET);
+    assert_eq!(
+        text.styles[&CrdtId::default()].value,
+        ParagraphStyle::HEADING
+    );
+
+    let changes = diff(&before, &after);
+    assert_eq!(
+        changes.text,
+        vec![
+            Change::Removed("Work".to_owned()),
+            Change::Added(" Ada\nDate:".to_owned()),
+        ]
+    );
+}